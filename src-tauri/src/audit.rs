@@ -0,0 +1,239 @@
+//! Append-only audit log of statements run on audited connections. Each
+//! entry carries the SHA-256 of the previous one, so editing, reordering or
+//! deleting an entry breaks the chain from that point on. Removing entries
+//! from the end is only detectable against a `head_hash` noted earlier.
+
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// `prev_hash` of the first entry.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Hashed fields of an entry, in serialization order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub seq: u64,
+    pub timestamp: String,
+    /// Operating system account running the app.
+    pub os_user: Option<String>,
+    /// Account the connection logs in as.
+    pub db_user: String,
+    pub connection_id: String,
+    pub connection_name: String,
+    pub db_type: String,
+    /// `query` or `ddl`.
+    pub source: String,
+    pub statement: String,
+    pub success: bool,
+    pub error: Option<String>,
+    pub rows_affected: Option<u64>,
+    pub prev_hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    #[serde(flatten)]
+    pub record: AuditRecord,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditVerification {
+    pub valid: bool,
+    pub entries: u64,
+    /// Hash of the last entry; compare with a previously recorded value to
+    /// detect truncation.
+    pub head_hash: Option<String>,
+    /// Line number (1-based) of the first entry that fails verification.
+    pub first_invalid_line: Option<u64>,
+    pub problem: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditExportRequest {
+    pub path: String,
+    /// `json` or `csv`.
+    pub format: String,
+    pub connection_id: Option<String>,
+    /// RFC 3339, inclusive.
+    pub since: Option<String>,
+    /// RFC 3339, exclusive.
+    pub until: Option<String>,
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+    /// Sequence number and hash of the last entry, read on first append.
+    head: Mutex<Option<(u64, String)>>,
+}
+
+impl AuditLog {
+    pub fn open(path: PathBuf) -> Self {
+        Self { path, head: Mutex::new(None) }
+    }
+
+    /// Appends a record, filling in `seq` and `prev_hash`.
+    pub fn append(&self, mut record: AuditRecord) -> Result<AuditEntry, Box<dyn std::error::Error>> {
+        let mut head = self.head.lock().map_err(|_| "Audit log lock poisoned")?;
+        if head.is_none() {
+            *head = Some(self.read_head()?);
+        }
+        let (last_seq, last_hash) = head.as_ref().cloned().unwrap_or_default();
+        record.seq = last_seq + 1;
+        record.prev_hash = last_hash;
+        let entry = AuditEntry { hash: hash_record(&record)?, record };
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        file.sync_data()?;
+        *head = Some((entry.record.seq, entry.hash.clone()));
+        Ok(entry)
+    }
+
+    fn read_head(&self) -> Result<(u64, String), Box<dyn std::error::Error>> {
+        let mut head = (0, GENESIS_HASH.to_string());
+        for line in self.lines()? {
+            let (_, line) = line?;
+            let entry: AuditEntry = serde_json::from_str(&line)
+                .map_err(|e| format!("Audit log is corrupt, refusing to append: {}", e))?;
+            head = (entry.record.seq, entry.hash);
+        }
+        Ok(head)
+    }
+
+    /// Non-empty lines with their 1-based line numbers.
+    fn lines(&self) -> Result<impl Iterator<Item = std::io::Result<(u64, String)>>, Box<dyn std::error::Error>> {
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => Some(file),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        Ok(file
+            .into_iter()
+            .flat_map(|file| std::io::BufReader::new(file).lines())
+            .enumerate()
+            .map(|(i, line)| line.map(|line| (i as u64 + 1, line)))
+            .filter(|line| !matches!(line, Ok((_, text)) if text.trim().is_empty())))
+    }
+
+    /// Recomputes every hash and checks the chain and sequence numbers.
+    pub fn verify(&self) -> Result<AuditVerification, Box<dyn std::error::Error>> {
+        let _guard = self.head.lock().map_err(|_| "Audit log lock poisoned")?;
+        let mut prev_hash = GENESIS_HASH.to_string();
+        let mut entries = 0u64;
+        let invalid = |entries: u64, line: u64, problem: String| AuditVerification {
+            valid: false,
+            entries,
+            head_hash: None,
+            first_invalid_line: Some(line),
+            problem: Some(problem),
+        };
+
+        for line in self.lines()? {
+            let (number, line) = line?;
+            let entry: AuditEntry = match serde_json::from_str(&line) {
+                Ok(entry) => entry,
+                Err(e) => return Ok(invalid(entries, number, format!("Unreadable entry: {}", e))),
+            };
+            if entry.record.seq != entries + 1 {
+                return Ok(invalid(entries, number, format!(
+                    "Expected sequence number {}, found {}", entries + 1, entry.record.seq
+                )));
+            }
+            if entry.record.prev_hash != prev_hash {
+                return Ok(invalid(entries, number, "Previous hash does not match the preceding entry".to_string()));
+            }
+            if hash_record(&entry.record)? != entry.hash {
+                return Ok(invalid(entries, number, "Entry content does not match its hash".to_string()));
+            }
+            prev_hash = entry.hash;
+            entries += 1;
+        }
+
+        Ok(AuditVerification {
+            valid: true,
+            entries,
+            head_hash: (entries > 0).then_some(prev_hash),
+            first_invalid_line: None,
+            problem: None,
+        })
+    }
+
+    /// Writes matching entries to a JSON or CSV file; returns how many.
+    pub fn export(&self, request: &AuditExportRequest) -> Result<usize, Box<dyn std::error::Error>> {
+        let since = request.since.as_deref().map(parse_time).transpose()?;
+        let until = request.until.as_deref().map(parse_time).transpose()?;
+        let mut entries = Vec::new();
+        for line in self.lines()? {
+            let (number, line) = line?;
+            let entry: AuditEntry = serde_json::from_str(&line)
+                .map_err(|e| format!("Unreadable audit entry on line {}: {}", number, e))?;
+            let time = parse_time(&entry.record.timestamp)?;
+            if request.connection_id.as_ref().is_some_and(|id| *id != entry.record.connection_id)
+                || since.is_some_and(|since| time < since)
+                || until.is_some_and(|until| time >= until)
+            {
+                continue;
+            }
+            entries.push(entry);
+        }
+
+        match request.format.as_str() {
+            "json" => std::fs::write(&request.path, serde_json::to_string_pretty(&entries)?)?,
+            "csv" => write_csv(Path::new(&request.path), &entries)?,
+            other => return Err(format!("Unsupported audit export format: {}", other).into()),
+        }
+        Ok(entries.len())
+    }
+}
+
+fn hash_record(record: &AuditRecord) -> Result<String, serde_json::Error> {
+    let digest = Sha256::digest(serde_json::to_vec(record)?);
+    Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+fn parse_time(value: &str) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&chrono::Utc))
+        .map_err(|e| format!("Invalid timestamp {}: {}", value, e))
+}
+
+fn write_csv(path: &Path, entries: &[AuditEntry]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record([
+        "seq", "timestamp", "os_user", "db_user", "connection_id", "connection_name", "db_type",
+        "source", "statement", "success", "error", "rows_affected", "prev_hash", "hash",
+    ])?;
+    for entry in entries {
+        let r = &entry.record;
+        writer.write_record([
+            r.seq.to_string(),
+            r.timestamp.clone(),
+            r.os_user.clone().unwrap_or_default(),
+            r.db_user.clone(),
+            r.connection_id.clone(),
+            r.connection_name.clone(),
+            r.db_type.clone(),
+            r.source.clone(),
+            r.statement.clone(),
+            r.success.to_string(),
+            r.error.clone().unwrap_or_default(),
+            r.rows_affected.map(|n| n.to_string()).unwrap_or_default(),
+            r.prev_hash.clone(),
+            entry.hash.clone(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Operating system account name, from the environment.
+pub fn os_user() -> Option<String> {
+    std::env::var("USER").or_else(|_| std::env::var("USERNAME")).ok()
+}
@@ -0,0 +1,639 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use arrow::array::{
+    Array, ArrayRef, AsArray, BinaryArray, BooleanArray, Float32Array, Float64Array, Int16Array, Int32Array,
+    Int64Array, StringArray,
+};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, GzipLevel, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use serde::{Deserialize, Serialize};
+use sqlx::Executor;
+use crate::database::DatabaseConnection;
+use crate::dialect;
+use crate::export::RowWriter;
+use crate::mongodb::MongoConnection;
+use mongodb::bson::{spec::BinarySubtype, Binary, Bson, Document};
+
+const RECORD_BATCH_ROWS: usize = 8192;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ParquetOptions {
+    /// `none`, `snappy` (default), `zstd`, `gzip` or `lz4`.
+    pub compression: Option<String>,
+    pub compression_level: Option<i32>,
+    /// Maximum rows per row group; also bounds the writer's buffered rows.
+    pub row_group_size: Option<usize>,
+}
+
+/// Maps a `QueryRow::types` entry to an Arrow type. SQL results carry the
+/// sqlx type name (`INTEGER`, `BLOB`, ...), Mongo and Redis results names
+/// such as `Int64`; text and anything else is exported as Utf8.
+pub fn arrow_type_for(type_name: &str) -> DataType {
+    match type_name {
+        "BOOLEAN" | "Boolean" => DataType::Boolean,
+        "SMALLINT" => DataType::Int16,
+        "INTEGER" | "Int" | "Int32" => DataType::Int32,
+        "BIGINT" | "Int64" => DataType::Int64,
+        "REAL" => DataType::Float32,
+        "DOUBLE" | "Double" => DataType::Float64,
+        "BLOB" => DataType::Binary,
+        _ => DataType::Utf8,
+    }
+}
+
+fn arrow_schema(columns: &[String], types: &[String]) -> SchemaRef {
+    let fields: Vec<Field> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let data_type = types.get(i).map(|t| arrow_type_for(t)).unwrap_or(DataType::Utf8);
+            Field::new(name, data_type, true)
+        })
+        .collect();
+    Arc::new(Schema::new(fields))
+}
+
+fn as_i64(value: &serde_json::Value) -> Option<i64> {
+    match value {
+        serde_json::Value::Number(n) => n.as_i64().or_else(|| n.as_f64().map(|f| f as i64)),
+        serde_json::Value::String(s) => s.trim().parse().ok(),
+        serde_json::Value::Bool(b) => Some(*b as i64),
+        _ => None,
+    }
+}
+
+fn as_f64(value: &serde_json::Value) -> Option<f64> {
+    match value {
+        serde_json::Value::Number(n) => n.as_f64(),
+        serde_json::Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn as_bool(value: &serde_json::Value) -> Option<bool> {
+    match value {
+        serde_json::Value::Bool(b) => Some(*b),
+        serde_json::Value::Number(n) => n.as_i64().map(|i| i != 0),
+        serde_json::Value::String(s) => match s.to_ascii_lowercase().as_str() {
+            "true" | "t" | "1" | "yes" => Some(true),
+            "false" | "f" | "0" | "no" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn as_text(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Builds a record batch column by column. Values that cannot be coerced to
+/// the column's type become nulls rather than failing the export.
+fn record_batch(schema: &SchemaRef, rows: &[Vec<serde_json::Value>]) -> Result<RecordBatch, Box<dyn std::error::Error>> {
+    let null = serde_json::Value::Null;
+    let mut arrays: Vec<ArrayRef> = Vec::with_capacity(schema.fields().len());
+
+    for (col, field) in schema.fields().iter().enumerate() {
+        let cells = rows.iter().map(|row| row.get(col).unwrap_or(&null));
+        let array: ArrayRef = match field.data_type() {
+            DataType::Boolean => Arc::new(cells.map(as_bool).collect::<BooleanArray>()),
+            DataType::Int16 => Arc::new(cells.map(|v| as_i64(v).and_then(|i| i16::try_from(i).ok())).collect::<Int16Array>()),
+            DataType::Int32 => Arc::new(cells.map(|v| as_i64(v).and_then(|i| i32::try_from(i).ok())).collect::<Int32Array>()),
+            DataType::Int64 => Arc::new(cells.map(as_i64).collect::<Int64Array>()),
+            DataType::Float32 => Arc::new(cells.map(|v| as_f64(v).map(|f| f as f32)).collect::<Float32Array>()),
+            DataType::Float64 => Arc::new(cells.map(as_f64).collect::<Float64Array>()),
+            DataType::Binary => Arc::new(
+                cells
+                    .map(|v| as_text(v).and_then(|hex| decode_hex(&hex)))
+                    .collect::<Vec<_>>()
+                    .iter()
+                    .map(Option::as_deref)
+                    .collect::<BinaryArray>(),
+            ),
+            _ => Arc::new(cells.map(as_text).collect::<StringArray>()),
+        };
+        arrays.push(array);
+    }
+
+    Ok(RecordBatch::try_new(schema.clone(), arrays)?)
+}
+
+fn parquet_properties(options: &ParquetOptions) -> Result<WriterProperties, Box<dyn std::error::Error>> {
+    let compression = match options.compression.as_deref().unwrap_or("snappy") {
+        "none" | "uncompressed" => Compression::UNCOMPRESSED,
+        "snappy" => Compression::SNAPPY,
+        "zstd" => Compression::ZSTD(match options.compression_level {
+            Some(level) => ZstdLevel::try_new(level)?,
+            None => ZstdLevel::default(),
+        }),
+        "gzip" => Compression::GZIP(match options.compression_level {
+            Some(level) => GzipLevel::try_new(level as u32)?,
+            None => GzipLevel::default(),
+        }),
+        "lz4" => Compression::LZ4_RAW,
+        other => return Err(Box::new(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Unknown Parquet compression: {}", other)
+        ))),
+    };
+
+    let mut builder = WriterProperties::builder().set_compression(compression);
+    if let Some(row_group_size) = options.row_group_size {
+        builder = builder.set_max_row_group_size(row_group_size.max(1));
+    }
+    Ok(builder.build())
+}
+
+enum ColumnarOutput {
+    Parquet(ArrowWriter<BufWriter<File>>),
+    Arrow(arrow::ipc::writer::FileWriter<BufWriter<File>>),
+}
+
+/// Export writer for Parquet and Arrow IPC files. Rows are buffered into
+/// record batches of `RECORD_BATCH_ROWS`; the Parquet writer additionally
+/// buffers up to one row group.
+pub struct ColumnarWriter {
+    path: std::path::PathBuf,
+    parquet: Option<ParquetOptions>,
+    schema: Option<SchemaRef>,
+    output: Option<ColumnarOutput>,
+    pending: Vec<Vec<serde_json::Value>>,
+}
+
+impl ColumnarWriter {
+    pub fn parquet(path: &Path, options: ParquetOptions) -> Self {
+        Self { path: path.to_path_buf(), parquet: Some(options), schema: None, output: None, pending: Vec::new() }
+    }
+
+    pub fn arrow(path: &Path) -> Self {
+        Self { path: path.to_path_buf(), parquet: None, schema: None, output: None, pending: Vec::new() }
+    }
+
+    fn flush_pending(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let (Some(schema), Some(output)) = (&self.schema, &mut self.output) else {
+            return Ok(());
+        };
+        let batch = record_batch(schema, &self.pending)?;
+        match output {
+            ColumnarOutput::Parquet(writer) => writer.write(&batch)?,
+            ColumnarOutput::Arrow(writer) => writer.write(&batch)?,
+        }
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+impl RowWriter for ColumnarWriter {
+    fn write_header(&mut self, columns: &[String], types: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let schema = arrow_schema(columns, types);
+        let file = BufWriter::new(File::create(&self.path)?);
+        self.output = Some(match &self.parquet {
+            Some(options) => ColumnarOutput::Parquet(ArrowWriter::try_new(file, schema.clone(), Some(parquet_properties(options)?))?),
+            None => ColumnarOutput::Arrow(arrow::ipc::writer::FileWriter::try_new(file, &schema)?),
+        });
+        self.schema = Some(schema);
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[serde_json::Value]) -> Result<(), Box<dyn std::error::Error>> {
+        self.pending.push(row.to_vec());
+        if self.pending.len() >= RECORD_BATCH_ROWS {
+            self.flush_pending()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.flush_pending()?;
+        match self.output.take() {
+            Some(ColumnarOutput::Parquet(writer)) => {
+                writer.close()?;
+            }
+            Some(ColumnarOutput::Arrow(mut writer)) => writer.finish()?,
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportColumn {
+    pub name: String,
+    pub arrow_type: String,
+    pub sql_type: String,
+    pub nullable: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnarPreview {
+    pub columns: Vec<ImportColumn>,
+    pub row_count: Option<i64>,
+    pub sample_rows: Vec<Vec<serde_json::Value>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnarImportRequest {
+    /// Id to pass to `cancel_import`.
+    pub import_id: String,
+    pub connection_id: String,
+    pub path: String,
+    /// Target table, or collection for MongoDB connections.
+    pub table: String,
+    /// Issue `CREATE TABLE` with the inferred column types before loading.
+    pub create_table: Option<bool>,
+    /// Rows per INSERT statement / `insertMany` call.
+    pub batch_size: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnarImportSummary {
+    pub table: String,
+    pub rows_imported: u64,
+    pub created_table: bool,
+    pub columns: Vec<ImportColumn>,
+    /// SQL imports are rolled back when cancelled; MongoDB keeps the batches
+    /// inserted before.
+    pub cancelled: bool,
+    pub elapsed_ms: u64,
+}
+
+/// SQL column type for an Arrow type in the given dialect.
+pub fn sql_type_for(db_type: &str, data_type: &DataType) -> String {
+    let postgres = dialect::is_postgres(db_type);
+    let mysql = dialect::is_mysql(db_type);
+    let sql_type = match data_type {
+        DataType::Boolean => if mysql { "TINYINT(1)" } else if postgres { "BOOLEAN" } else { "INTEGER" },
+        DataType::Int8 | DataType::Int16 | DataType::UInt8 => if postgres || mysql { "SMALLINT" } else { "INTEGER" },
+        DataType::Int32 | DataType::UInt16 => "INTEGER",
+        DataType::Int64 | DataType::UInt32 => if postgres || mysql { "BIGINT" } else { "INTEGER" },
+        DataType::UInt64 => if mysql { "BIGINT UNSIGNED" } else if postgres { "NUMERIC(20)" } else { "INTEGER" },
+        DataType::Float16 | DataType::Float32 => if mysql { "FLOAT" } else { "REAL" },
+        DataType::Float64 => if postgres { "DOUBLE PRECISION" } else if mysql { "DOUBLE" } else { "REAL" },
+        DataType::Decimal128(precision, scale) | DataType::Decimal256(precision, scale) => {
+            return if postgres || mysql {
+                format!("DECIMAL({}, {})", precision, scale)
+            } else {
+                "NUMERIC".to_string()
+            };
+        }
+        DataType::Date32 | DataType::Date64 => if postgres || mysql { "DATE" } else { "TEXT" },
+        DataType::Timestamp(_, tz) => {
+            if postgres {
+                if tz.is_some() { "TIMESTAMPTZ" } else { "TIMESTAMP" }
+            } else if mysql {
+                "DATETIME(6)"
+            } else {
+                "TEXT"
+            }
+        }
+        DataType::Binary | DataType::LargeBinary | DataType::FixedSizeBinary(_) => {
+            if postgres { "BYTEA" } else if mysql { "LONGBLOB" } else { "BLOB" }
+        }
+        _ => if mysql { "LONGTEXT" } else { "TEXT" },
+    };
+    sql_type.to_string()
+}
+
+fn import_columns(db_type: &str, schema: &Schema) -> Vec<ImportColumn> {
+    schema
+        .fields()
+        .iter()
+        .map(|field| ImportColumn {
+            name: field.name().clone(),
+            arrow_type: field.data_type().to_string(),
+            sql_type: sql_type_for(db_type, field.data_type()),
+            nullable: field.is_nullable(),
+        })
+        .collect()
+}
+
+fn is_binary(data_type: &DataType) -> bool {
+    matches!(data_type, DataType::Binary | DataType::LargeBinary | DataType::FixedSizeBinary(_))
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok()).collect()
+}
+
+/// Converts one record batch to JSON rows. Booleans and numbers keep their
+/// JSON types; everything else goes through Arrow's display formatting, which
+/// renders dates and timestamps in ISO 8601 and binary values as hex. The
+/// importers turn that hex back into bytes.
+fn batch_rows(batch: &RecordBatch) -> Result<Vec<Vec<serde_json::Value>>, Box<dyn std::error::Error>> {
+    let options = FormatOptions::default();
+    let mut columns: Vec<Vec<serde_json::Value>> = Vec::with_capacity(batch.num_columns());
+
+    for array in batch.columns() {
+        let mut values = Vec::with_capacity(array.len());
+        match array.data_type() {
+            DataType::Boolean => {
+                let typed = array.as_boolean();
+                for i in 0..array.len() {
+                    values.push(if typed.is_null(i) { serde_json::Value::Null } else { serde_json::Value::Bool(typed.value(i)) });
+                }
+            }
+            DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64
+            | DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => {
+                let as_int64 = arrow::compute::cast(array, &DataType::Int64)?;
+                let typed = as_int64.as_primitive::<arrow::datatypes::Int64Type>();
+                for i in 0..array.len() {
+                    values.push(if typed.is_null(i) { serde_json::Value::Null } else { typed.value(i).into() });
+                }
+            }
+            DataType::Float16 | DataType::Float32 | DataType::Float64 => {
+                let as_float64 = arrow::compute::cast(array, &DataType::Float64)?;
+                let typed = as_float64.as_primitive::<arrow::datatypes::Float64Type>();
+                for i in 0..array.len() {
+                    values.push(if typed.is_null(i) {
+                        serde_json::Value::Null
+                    } else {
+                        serde_json::Number::from_f64(typed.value(i))
+                            .map(serde_json::Value::Number)
+                            .unwrap_or(serde_json::Value::Null)
+                    });
+                }
+            }
+            _ => {
+                let formatter = ArrayFormatter::try_new(array.as_ref(), &options)?;
+                for i in 0..array.len() {
+                    values.push(if array.is_null(i) {
+                        serde_json::Value::Null
+                    } else {
+                        serde_json::Value::String(formatter.value(i).to_string())
+                    });
+                }
+            }
+        }
+        columns.push(values);
+    }
+
+    Ok((0..batch.num_rows())
+        .map(|row| columns.iter().map(|column| column[row].clone()).collect())
+        .collect())
+}
+
+fn is_arrow_ipc(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_ascii_lowercase()).as_deref(),
+        Some("arrow") | Some("feather") | Some("ipc")
+    )
+}
+
+type BatchIter = Box<dyn Iterator<Item = Result<RecordBatch, arrow::error::ArrowError>> + Send>;
+
+/// Opens a Parquet (or, by extension, Arrow IPC) file as a batch iterator.
+fn open_batches(path: &Path, batch_size: usize) -> Result<(SchemaRef, Option<i64>, BatchIter), Box<dyn std::error::Error>> {
+    let file = File::open(path)?;
+    if is_arrow_ipc(path) {
+        let reader = arrow::ipc::reader::FileReader::try_new(file, None)?;
+        return Ok((reader.schema(), None, Box::new(reader)));
+    }
+
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)?.with_batch_size(batch_size);
+    let schema = builder.schema().clone();
+    let row_count = builder.metadata().file_metadata().num_rows();
+    Ok((schema, Some(row_count), Box::new(builder.build()?)))
+}
+
+pub fn preview_file(path: &Path, db_type: &str, sample_rows: usize) -> Result<ColumnarPreview, Box<dyn std::error::Error>> {
+    let (schema, row_count, mut batches) = open_batches(path, sample_rows.max(1))?;
+    let sample = match batches.next() {
+        Some(batch) => {
+            let mut rows = batch_rows(&batch?)?;
+            rows.truncate(sample_rows);
+            rows
+        }
+        None => Vec::new(),
+    };
+
+    Ok(ColumnarPreview {
+        columns: import_columns(db_type, &schema),
+        row_count,
+        sample_rows: sample,
+    })
+}
+
+fn create_table_sql(db_type: &str, table: &str, columns: &[ImportColumn]) -> String {
+    let definitions: Vec<String> = columns
+        .iter()
+        .map(|c| {
+            format!(
+                "{} {}{}",
+                dialect::quote_identifier(db_type, &c.name),
+                c.sql_type,
+                if c.nullable { "" } else { " NOT NULL" }
+            )
+        })
+        .collect();
+    format!("CREATE TABLE {} ({})", dialect::quote_table(db_type, table), definitions.join(", "))
+}
+
+fn binary_literal(db_type: &str, value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(hex) if decode_hex(hex).is_some() => {
+            if dialect::is_postgres(db_type) {
+                format!("decode('{}', 'hex')", hex)
+            } else {
+                format!("X'{}'", hex)
+            }
+        }
+        other => dialect::literal(db_type, other),
+    }
+}
+
+fn insert_sql(db_type: &str, table: &str, columns: &[ImportColumn], binary: &[bool], rows: &[Vec<serde_json::Value>]) -> String {
+    let column_list: Vec<String> = columns.iter().map(|c| dialect::quote_identifier(db_type, &c.name)).collect();
+    let values: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let literals: Vec<String> = row
+                .iter()
+                .zip(binary)
+                .map(|(v, &binary)| if binary { binary_literal(db_type, v) } else { dialect::literal(db_type, v) })
+                .collect();
+            format!("({})", literals.join(", "))
+        })
+        .collect();
+    format!(
+        "INSERT INTO {} ({}) VALUES {}",
+        dialect::quote_table(db_type, table),
+        column_list.join(", "),
+        values.join(", ")
+    )
+}
+
+/// Loads a Parquet or Arrow IPC file into a SQL table with batched multi-row
+/// INSERTs. File reading happens on a blocking thread that feeds batches
+/// through a channel. Everything runs in one transaction that a failure or
+/// `cancel` rolls back; MySQL commits before `CREATE TABLE`, so there the
+/// table is created first and left in place.
+pub async fn import_into_sql(
+    conn: &DatabaseConnection,
+    request: &ColumnarImportRequest,
+    cancel: &AtomicBool,
+) -> Result<ColumnarImportSummary, Box<dyn std::error::Error>> {
+    let start = std::time::Instant::now();
+    let db_type = conn.db_type();
+    let batch_size = request.batch_size.unwrap_or(500).max(1);
+    let (schema, _, mut receiver) = spawn_reader(Path::new(&request.path), batch_size)?;
+    let columns = import_columns(db_type, &schema);
+    let binary: Vec<bool> = schema.fields().iter().map(|f| is_binary(f.data_type())).collect();
+
+    let created_table = request.create_table.unwrap_or(false);
+    let create = create_table_sql(db_type, &request.table, &columns);
+    if created_table && dialect::is_mysql(db_type) {
+        conn.execute_ddl(&create).await?;
+    }
+
+    let mut tx = conn.begin().await?;
+    let mut rows_imported = 0;
+    let mut cancelled = false;
+    let outcome: Result<(), String> = async {
+        if created_table && !dialect::is_mysql(db_type) {
+            (&mut *tx).execute(create.as_str()).await.map_err(|e| e.to_string())?;
+        }
+        while let Some(rows) = receiver.recv().await {
+            if cancel.load(Ordering::Relaxed) {
+                cancelled = true;
+                break;
+            }
+            let rows = rows?;
+            if rows.is_empty() {
+                continue;
+            }
+            let sql = insert_sql(db_type, &request.table, &columns, &binary, &rows);
+            (&mut *tx).execute(sql.as_str()).await.map_err(|e| e.to_string())?;
+            rows_imported += rows.len() as u64;
+        }
+        Ok(())
+    }
+    .await;
+    drop(receiver);
+
+    match outcome {
+        Ok(()) if !cancelled => tx.commit().await?,
+        Ok(()) => {
+            tx.rollback().await?;
+            rows_imported = 0;
+        }
+        Err(error) => {
+            let _ = tx.rollback().await;
+            return Err(error.into());
+        }
+    }
+
+    Ok(ColumnarImportSummary {
+        table: request.table.clone(),
+        rows_imported,
+        created_table: created_table && (!cancelled || dialect::is_mysql(db_type)),
+        columns,
+        cancelled,
+        elapsed_ms: start.elapsed().as_millis() as u64,
+    })
+}
+
+/// Loads a Parquet or Arrow IPC file into a MongoDB collection with `insertMany`.
+pub async fn import_into_mongo(
+    conn: &MongoConnection,
+    request: &ColumnarImportRequest,
+    cancel: &AtomicBool,
+) -> Result<ColumnarImportSummary, Box<dyn std::error::Error>> {
+    let start = std::time::Instant::now();
+    let batch_size = request.batch_size.unwrap_or(1000).max(1);
+    let (schema, _, receiver) = spawn_reader(Path::new(&request.path), batch_size)?;
+    let columns = import_columns("mongodb", &schema);
+    let names: Vec<String> = columns.iter().map(|c| c.name.clone()).collect();
+    let binary: Vec<bool> = schema.fields().iter().map(|f| is_binary(f.data_type())).collect();
+
+    let (rows_imported, cancelled) = drain_batches(receiver, cancel, |rows| {
+        let documents: Result<Vec<Document>, String> = rows.into_iter().map(|row| to_document(&names, &binary, row)).collect();
+        async move { conn.insert_bson_documents(&request.table, documents?).await.map_err(|e| e.to_string()) }
+    }).await?;
+
+    Ok(ColumnarImportSummary {
+        table: request.table.clone(),
+        rows_imported,
+        created_table: false,
+        columns,
+        cancelled,
+        elapsed_ms: start.elapsed().as_millis() as u64,
+    })
+}
+
+/// Nulls are left out; binary columns become BSON binary.
+fn to_document(names: &[String], binary: &[bool], row: Vec<serde_json::Value>) -> Result<Document, String> {
+    let mut document = Document::new();
+    for ((name, &binary), value) in names.iter().zip(binary).zip(row) {
+        let bson = match value {
+            serde_json::Value::Null => continue,
+            serde_json::Value::String(hex) if binary => Bson::Binary(Binary {
+                subtype: BinarySubtype::Generic,
+                bytes: decode_hex(&hex).ok_or_else(|| format!("Column {} holds invalid binary data", name))?,
+            }),
+            other => mongodb::bson::to_bson(&other).map_err(|e| e.to_string())?,
+        };
+        document.insert(name.clone(), bson);
+    }
+    Ok(document)
+}
+
+type RowBatchReceiver = tokio::sync::mpsc::Receiver<Result<Vec<Vec<serde_json::Value>>, String>>;
+
+fn spawn_reader(path: &Path, batch_size: usize) -> Result<(SchemaRef, Option<i64>, RowBatchReceiver), Box<dyn std::error::Error>> {
+    let (schema, row_count, batches) = open_batches(path, batch_size)?;
+    let (sender, receiver) = tokio::sync::mpsc::channel(4);
+
+    tokio::task::spawn_blocking(move || {
+        for batch in batches {
+            let rows = batch
+                .map_err(|e| e.to_string())
+                .and_then(|batch| batch_rows(&batch).map_err(|e| e.to_string()));
+            let failed = rows.is_err();
+            if sender.blocking_send(rows).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    Ok((schema, row_count, receiver))
+}
+
+/// Rows loaded, and whether `cancel` stopped the import between batches.
+async fn drain_batches<F, Fut>(mut receiver: RowBatchReceiver, cancel: &AtomicBool, mut load: F) -> Result<(u64, bool), Box<dyn std::error::Error>>
+where
+    F: FnMut(Vec<Vec<serde_json::Value>>) -> Fut,
+    Fut: std::future::Future<Output = Result<u64, String>>,
+{
+    let mut rows_imported = 0;
+    while let Some(rows) = receiver.recv().await {
+        if cancel.load(Ordering::Relaxed) {
+            return Ok((rows_imported, true));
+        }
+        let rows = rows.map_err(std::io::Error::other)?;
+        if rows.is_empty() {
+            continue;
+        }
+        let count = rows.len() as u64;
+        load(rows).await.map_err(std::io::Error::other)?;
+        rows_imported += count;
+    }
+    Ok((rows_imported, false))
+}
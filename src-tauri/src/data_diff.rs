@@ -0,0 +1,472 @@
+//! Row-level comparison of a table or query between two SQL connections:
+//! rows only on one side, rows whose columns differ, and optionally a script
+//! that brings the target in line with the source.
+
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use crate::database::DatabaseConnection;
+use crate::dialect;
+
+const DEFAULT_CHUNK_SIZE: usize = 1000;
+const DEFAULT_MAX_DIFFERENCES: usize = 10_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataDiffRequest {
+    pub source_connection_id: String,
+    pub target_connection_id: String,
+    /// Table compared on both sides. Either `table` or `query` must be set.
+    pub table: Option<String>,
+    /// Query run on both sides instead of a table scan; requires `key_columns`.
+    pub query: Option<String>,
+    /// Table the sync script writes to; defaults to `table`.
+    pub target_table: Option<String>,
+    pub key_columns: Option<Vec<String>>,
+    pub columns: Option<Vec<String>>,
+    pub chunk_size: Option<usize>,
+    pub max_differences: Option<usize>,
+    pub generate_script: Option<bool>,
+    /// Typed confirmation for statements the safety policy asks to confirm.
+    pub confirmation: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnDifference {
+    pub column: String,
+    pub source: serde_json::Value,
+    pub target: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangedRow {
+    pub key: Vec<serde_json::Value>,
+    pub differences: Vec<ColumnDifference>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataDiffResult {
+    pub key_columns: Vec<String>,
+    pub columns: Vec<String>,
+    pub only_in_source: Vec<Vec<serde_json::Value>>,
+    pub only_in_target: Vec<Vec<serde_json::Value>>,
+    pub changed: Vec<ChangedRow>,
+    pub source_rows: u64,
+    pub target_rows: u64,
+    pub chunks: usize,
+    pub chunks_matched_by_hash: usize,
+    pub truncated: bool,
+    pub sync_script: Option<String>,
+}
+
+impl DataDiffResult {
+    fn difference_count(&self) -> usize {
+        self.only_in_source.len() + self.only_in_target.len() + self.changed.len()
+    }
+}
+
+struct Side<'a> {
+    conn: &'a DatabaseConnection,
+    relation: String,
+}
+
+impl Side<'_> {
+    fn db_type(&self) -> &str {
+        self.conn.db_type()
+    }
+
+    fn select(&self, columns: &[String], keys: &[String], conditions: &[String], limit: Option<usize>) -> String {
+        let db_type = self.db_type();
+        let select_list: Vec<String> = columns.iter().map(|c| dialect::quote_identifier(db_type, c)).collect();
+        let order_by: Vec<String> = keys.iter().map(|c| dialect::quote_identifier(db_type, c)).collect();
+
+        let mut sql = format!("SELECT {} FROM {}", select_list.join(", "), self.relation);
+        if !conditions.is_empty() {
+            sql.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+        }
+        sql.push_str(&format!(" ORDER BY {}", order_by.join(", ")));
+        if let Some(limit) = limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+        sql
+    }
+
+    /// Server-side checksum of a key range as `"<count>:<hash>"`, or `None` when
+    /// the dialect has no usable hash function.
+    fn checksum_sql(&self, columns: &[String], keys: &[String], conditions: &[String]) -> Option<String> {
+        let db_type = self.db_type();
+        let quoted: Vec<String> = columns.iter().map(|c| dialect::quote_identifier(db_type, c)).collect();
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", conditions.join(" AND "))
+        };
+
+        if dialect::is_postgres(db_type) {
+            let order_by: Vec<String> = keys.iter().map(|c| dialect::quote_identifier(db_type, c)).collect();
+            Some(format!(
+                "SELECT count(*)::text || ':' || coalesce(md5(string_agg(md5(ROW({})::text), '' ORDER BY {})), '') FROM {}{}",
+                quoted.join(", "),
+                order_by.join(", "),
+                self.relation,
+                where_clause
+            ))
+        } else if dialect::is_mysql(db_type) {
+            let null_flags: Vec<String> = quoted.iter().map(|c| format!("ISNULL({})", c)).collect();
+            Some(format!(
+                "SELECT CONCAT(COUNT(*), ':', COALESCE(BIT_XOR(CAST(CONV(SUBSTRING(MD5(CONCAT_WS('|', {}, CONCAT({}))), 1, 16), 16, 10) AS UNSIGNED)), 0)) FROM {}{}",
+                quoted.join(", "),
+                null_flags.join(", "),
+                self.relation,
+                where_clause
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+fn relation_for(db_type: &str, table: Option<&str>, query: Option<&str>) -> Result<String, Box<dyn std::error::Error>> {
+    match (table, query) {
+        (_, Some(query)) => Ok(format!("({}) AS diff_src", query.trim().trim_end_matches(';'))),
+        (Some(table), None) => Ok(dialect::quote_table(db_type, table)),
+        (None, None) => Err(Box::new(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Either a table or a query is required for data comparison"
+        ))),
+    }
+}
+
+fn range_conditions(
+    db_type: &str,
+    keys: &[String],
+    lower: Option<&[serde_json::Value]>,
+    upper: Option<&[serde_json::Value]>,
+) -> Vec<String> {
+    let tuple = dialect::column_tuple(db_type, keys);
+    let mut conditions = Vec::new();
+    if let Some(lower) = lower {
+        conditions.push(format!("{} > {}", tuple, dialect::literal_tuple(db_type, lower)));
+    }
+    if let Some(upper) = upper {
+        conditions.push(format!("{} <= {}", tuple, dialect::literal_tuple(db_type, upper)));
+    }
+    conditions
+}
+
+/// Normalises values so that equal data read through different drivers compares
+/// equal (e.g. `1` vs `"1"`, `true` vs `1`).
+fn canonical(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => "\u{0}null".to_string(),
+        serde_json::Value::Bool(b) => if *b { "1".to_string() } else { "0".to_string() },
+        serde_json::Value::Number(n) => match n.as_f64() {
+            Some(f) if f.fract() == 0.0 && f.abs() < 1e15 => format!("{}", f as i64),
+            _ => n.to_string(),
+        },
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn canonical_key(row: &[serde_json::Value], key_indexes: &[usize]) -> String {
+    key_indexes
+        .iter()
+        .map(|&i| canonical(&row[i]))
+        .collect::<Vec<_>>()
+        .join("\u{1f}")
+}
+
+fn key_values(row: &[serde_json::Value], key_indexes: &[usize]) -> Vec<serde_json::Value> {
+    key_indexes.iter().map(|&i| row[i].clone()).collect()
+}
+
+/// Compares a table (or query result) between two connections.
+///
+/// The source key space is walked in chunks of `chunk_size` keys. For each key
+/// range both sides are first checksummed on the server when they share a
+/// dialect; only ranges whose checksums differ are fetched and compared row by
+/// row, and the target side of a range is paged so memory stays bounded even
+/// when the target holds many extra rows.
+pub async fn diff_table_data(
+    source: &DatabaseConnection,
+    target: &DatabaseConnection,
+    request: &DataDiffRequest,
+) -> Result<DataDiffResult, Box<dyn std::error::Error>> {
+    let chunk_size = request.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE).max(1);
+    let max_differences = request.max_differences.unwrap_or(DEFAULT_MAX_DIFFERENCES);
+
+    let source_side = Side {
+        conn: source,
+        relation: relation_for(source.db_type(), request.table.as_deref(), request.query.as_deref())?,
+    };
+    let target_side = Side {
+        conn: target,
+        relation: relation_for(target.db_type(), request.table.as_deref(), request.query.as_deref())?,
+    };
+
+    let key_columns = match &request.key_columns {
+        Some(keys) if !keys.is_empty() => keys.clone(),
+        _ => match (&request.table, &request.query) {
+            (Some(table), None) => source.primary_key_columns(table).await?,
+            _ => Vec::new(),
+        },
+    };
+    if key_columns.is_empty() {
+        return Err(Box::new(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "No key columns: the table has no primary key, specify key_columns explicitly"
+        )));
+    }
+
+    let mut columns = match &request.columns {
+        Some(columns) if !columns.is_empty() => columns.clone(),
+        _ => {
+            let probe = format!("SELECT * FROM {}", source_side.relation);
+            source.describe_columns(&probe).await?
+        }
+    };
+    for key in key_columns.iter().rev() {
+        if !columns.contains(key) {
+            columns.insert(0, key.clone());
+        }
+    }
+    let key_indexes: Vec<usize> = key_columns
+        .iter()
+        .map(|k| columns.iter().position(|c| c == k).unwrap_or(0))
+        .collect();
+
+    let use_checksums = source.db_type() == target.db_type()
+        || (dialect::is_mysql(source.db_type()) && dialect::is_mysql(target.db_type()));
+
+    let mut result = DataDiffResult {
+        key_columns: key_columns.clone(),
+        columns: columns.clone(),
+        only_in_source: Vec::new(),
+        only_in_target: Vec::new(),
+        changed: Vec::new(),
+        source_rows: 0,
+        target_rows: 0,
+        chunks: 0,
+        chunks_matched_by_hash: 0,
+        truncated: false,
+        sync_script: None,
+    };
+
+    let mut lower: Option<Vec<serde_json::Value>> = None;
+    loop {
+        let key_conditions = range_conditions(source.db_type(), &key_columns, lower.as_deref(), None);
+        let key_sql = source_side.select(&key_columns, &key_columns, &key_conditions, Some(chunk_size));
+        let boundary_keys = source.execute_query(&key_sql).await?.rows;
+        let upper = if boundary_keys.len() == chunk_size {
+            boundary_keys.last().cloned()
+        } else {
+            None
+        };
+        result.chunks += 1;
+
+        compare_range(
+            &source_side,
+            &target_side,
+            &columns,
+            &key_columns,
+            &key_indexes,
+            lower.as_deref(),
+            upper.as_deref(),
+            chunk_size,
+            use_checksums,
+            max_differences,
+            &mut result,
+        ).await?;
+
+        if result.truncated || upper.is_none() {
+            break;
+        }
+        lower = upper;
+    }
+
+    log::debug!(
+        "Data diff finished: {} chunks ({} matched by hash), {} differences",
+        result.chunks, result.chunks_matched_by_hash, result.difference_count()
+    );
+
+    if request.generate_script.unwrap_or(false) {
+        let script_table = request.target_table.as_deref().or(request.table.as_deref()).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "A target_table is required to generate a sync script for a query comparison"
+            )
+        })?;
+        result.sync_script = Some(generate_sync_script(target.db_type(), script_table, &result));
+    }
+
+    Ok(result)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn compare_range(
+    source: &Side<'_>,
+    target: &Side<'_>,
+    columns: &[String],
+    key_columns: &[String],
+    key_indexes: &[usize],
+    lower: Option<&[serde_json::Value]>,
+    upper: Option<&[serde_json::Value]>,
+    chunk_size: usize,
+    use_checksums: bool,
+    max_differences: usize,
+    result: &mut DataDiffResult,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if use_checksums {
+        let source_sql = source.checksum_sql(columns, key_columns, &range_conditions(source.db_type(), key_columns, lower, upper));
+        let target_sql = target.checksum_sql(columns, key_columns, &range_conditions(target.db_type(), key_columns, lower, upper));
+        if let (Some(source_sql), Some(target_sql)) = (source_sql, target_sql) {
+            let source_sum = first_string(&source.conn.execute_query(&source_sql).await?.rows);
+            let target_sum = first_string(&target.conn.execute_query(&target_sql).await?.rows);
+            if source_sum.is_some() && source_sum == target_sum {
+                let count = source_sum
+                    .as_deref()
+                    .and_then(|sum| sum.split(':').next())
+                    .and_then(|count| count.parse::<u64>().ok())
+                    .unwrap_or(0);
+                result.source_rows += count;
+                result.target_rows += count;
+                result.chunks_matched_by_hash += 1;
+                return Ok(());
+            }
+        }
+    }
+
+    let source_sql = source.select(columns, key_columns, &range_conditions(source.db_type(), key_columns, lower, upper), None);
+    let source_rows = source.conn.execute_query(&source_sql).await?.rows;
+    result.source_rows += source_rows.len() as u64;
+
+    let source_by_key: HashMap<String, &Vec<serde_json::Value>> = source_rows
+        .iter()
+        .map(|row| (canonical_key(row, key_indexes), row))
+        .collect();
+    let mut matched: HashSet<String> = HashSet::new();
+
+    let mut cursor: Option<Vec<serde_json::Value>> = lower.map(|l| l.to_vec());
+    loop {
+        let conditions = range_conditions(target.db_type(), key_columns, cursor.as_deref(), upper);
+        let page_sql = target.select(columns, key_columns, &conditions, Some(chunk_size));
+        let page = target.conn.execute_query(&page_sql).await?.rows;
+        result.target_rows += page.len() as u64;
+
+        for target_row in &page {
+            let key = canonical_key(target_row, key_indexes);
+            match source_by_key.get(&key) {
+                Some(source_row) => {
+                    matched.insert(key);
+                    let differences: Vec<ColumnDifference> = columns
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| canonical(&source_row[*i]) != canonical(&target_row[*i]))
+                        .map(|(i, column)| ColumnDifference {
+                            column: column.clone(),
+                            source: source_row[i].clone(),
+                            target: target_row[i].clone(),
+                        })
+                        .collect();
+                    if !differences.is_empty() {
+                        result.changed.push(ChangedRow {
+                            key: key_values(target_row, key_indexes),
+                            differences,
+                        });
+                    }
+                }
+                None => result.only_in_target.push(target_row.clone()),
+            }
+
+            if result.difference_count() >= max_differences {
+                result.truncated = true;
+                return Ok(());
+            }
+        }
+
+        if page.len() < chunk_size {
+            break;
+        }
+        cursor = page.last().map(|row| key_values(row, key_indexes));
+    }
+
+    for row in &source_rows {
+        if !matched.contains(&canonical_key(row, key_indexes)) {
+            result.only_in_source.push(row.clone());
+            if result.difference_count() >= max_differences {
+                result.truncated = true;
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn first_string(rows: &[Vec<serde_json::Value>]) -> Option<String> {
+    rows.first()
+        .and_then(|row| row.first())
+        .map(|value| match value {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+}
+
+/// Builds the script that makes the target match the source: deletes first,
+/// then updates, then inserts, so key conflicts cannot occur midway.
+pub fn generate_sync_script(db_type: &str, table: &str, diff: &DataDiffResult) -> String {
+    let quoted_table = dialect::quote_table(db_type, table);
+    let key_indexes: Vec<usize> = diff.key_columns
+        .iter()
+        .map(|k| diff.columns.iter().position(|c| c == k).unwrap_or(0))
+        .collect();
+
+    let mut script = String::new();
+    script.push_str(&format!(
+        "-- Sync script for {}: {} insert(s), {} update(s), {} delete(s)\n",
+        table,
+        diff.only_in_source.len(),
+        diff.changed.len(),
+        diff.only_in_target.len()
+    ));
+    if diff.truncated {
+        script.push_str("-- WARNING: the comparison was truncated; this script is incomplete\n");
+    }
+    script.push_str("BEGIN;\n");
+
+    for row in &diff.only_in_target {
+        script.push_str(&format!(
+            "DELETE FROM {} WHERE {};\n",
+            quoted_table,
+            dialect::key_predicate(db_type, &diff.key_columns, &key_values(row, &key_indexes))
+        ));
+    }
+
+    for change in &diff.changed {
+        let assignments: Vec<String> = change.differences
+            .iter()
+            .map(|d| format!("{} = {}", dialect::quote_identifier(db_type, &d.column), dialect::literal(db_type, &d.source)))
+            .collect();
+        script.push_str(&format!(
+            "UPDATE {} SET {} WHERE {};\n",
+            quoted_table,
+            assignments.join(", "),
+            dialect::key_predicate(db_type, &diff.key_columns, &change.key)
+        ));
+    }
+
+    if !diff.only_in_source.is_empty() {
+        let column_list: Vec<String> = diff.columns.iter().map(|c| dialect::quote_identifier(db_type, c)).collect();
+        for row in &diff.only_in_source {
+            let values: Vec<String> = row.iter().map(|v| dialect::literal(db_type, v)).collect();
+            script.push_str(&format!(
+                "INSERT INTO {} ({}) VALUES ({});\n",
+                quoted_table,
+                column_list.join(", "),
+                values.join(", ")
+            ));
+        }
+    }
+
+    script.push_str("COMMIT;\n");
+    script
+}
@@ -1,12 +1,23 @@
-use sqlx::{Row, TypeInfo, PgPool, MySqlPool, SqlitePool, Executor};
-use crate::types::{ConnectionConfig, ColumnInfo, TableInfo, QueryRow};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use futures::TryStreamExt;
+use sqlx::{Column, Row, TypeInfo, ValueRef, PgPool, MySqlPool, SqlitePool, Executor};
+use crate::dialect;
+use crate::dsn;
+use crate::driver_options;
+use crate::ssh::{self, SshTunnel};
+use crate::tls;
+use crate::tls_relay::{self, ServerNameRelay};
+use crate::types::{ConnectionConfig, ColumnInfo, TableInfo, QueryRow, ResolvedLimits, SchemaObject};
 
 fn convert_to_json_value(row: &sqlx::any::AnyRow, index: usize) -> serde_json::Value {
     let column = row.column(index);
     let type_info = column.type_info.clone();
     let type_name = type_info.name();
     
-    let is_null = row.try_get::<Option<i32>, _>(index).is_err();
+    let is_null = row.try_get_raw(index).map(|value| value.is_null()).unwrap_or(true);
     if is_null {
         return serde_json::Value::Null;
     }
@@ -39,6 +50,12 @@ fn convert_to_json_value(row: &sqlx::any::AnyRow, index: usize) -> serde_json::V
                 return serde_json::Value::String(s);
             }
         }
+        // Hex, which the Parquet export and the imports turn back into bytes.
+        "BLOB" => {
+            if let Ok(bytes) = row.try_get::<Vec<u8>, _>(index) {
+                return serde_json::Value::String(bytes.iter().map(|b| format!("{:02x}", b)).collect());
+            }
+        }
         _ => {}
     }
     
@@ -49,41 +66,126 @@ fn convert_to_json_value(row: &sqlx::any::AnyRow, index: usize) -> serde_json::V
     serde_json::Value::String(format!("{:?}", type_info))
 }
 
+/// Column names and type names (`INTEGER`, `TEXT`, `BLOB`, ...) of a result
+/// row, in the same form as `QueryRow::columns` / `QueryRow::types`.
+pub fn row_columns(row: &sqlx::any::AnyRow) -> (Vec<String>, Vec<String>) {
+    let mut columns = Vec::new();
+    let mut types = Vec::new();
+    for i in 0..row.len() {
+        let column = row.column(i);
+        columns.push(column.name.to_string());
+        types.push(column.type_info.name().to_string());
+    }
+    (columns, types)
+}
+
+pub fn row_values(row: &sqlx::any::AnyRow) -> Vec<serde_json::Value> {
+    (0..row.len()).map(|i| convert_to_json_value(row, i)).collect()
+}
+
+/// How long past a query's timeout the client waits for the server to
+/// cancel it before giving up on the connection.
+const CLIENT_TIMEOUT_GRACE: Duration = Duration::from_secs(1);
+
+/// Session statement that makes a connection refuse writes.
+fn read_only_session_sql(db_type: &str) -> Option<&'static str> {
+    match db_type {
+        "postgresql" => Some("SET SESSION CHARACTERISTICS AS TRANSACTION READ ONLY"),
+        "mysql" | "mariadb" => Some("SET SESSION TRANSACTION READ ONLY"),
+        "sqlite" => Some("PRAGMA query_only = ON"),
+        _ => None,
+    }
+}
+
+/// Statements run on every new connection: a `USE` for MySQL databases the
+/// URL cannot name, driver session options, the pool's init SQL, then the
+/// read-only statement. Read-only goes last so init SQL cannot switch the
+/// session back to read-write.
+pub fn session_sql(config: &ConnectionConfig) -> Vec<String> {
+    let mut statements: Vec<String> = dsn::mysql_use_statement(config).into_iter().collect();
+    statements.extend(driver_options::session_statements(config));
+    if let Some(init_sql) = config.pool.as_ref().and_then(|pool| pool.init_sql.as_ref()) {
+        statements.extend(init_sql.iter().filter(|sql| !sql.trim().is_empty()).cloned());
+    }
+    if config.is_read_only() {
+        statements.extend(read_only_session_sql(&config.r#type).map(str::to_string));
+    }
+    statements
+}
+
+/// Waits for a pooled connection, as seen by this client. Covers queries,
+/// statements and transactions; schema introspection is not counted.
+#[derive(Debug, Default)]
+struct PoolMetrics {
+    acquires: AtomicU64,
+    waiting: AtomicU64,
+    timeouts: AtomicU64,
+    total_wait_us: AtomicU64,
+    max_wait_us: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolStats {
+    /// Open physical connections.
+    pub size: u32,
+    pub idle: u32,
+    pub in_use: u32,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// Callers currently waiting for a connection.
+    pub waiting: u64,
+    pub acquires: u64,
+    /// Acquires that gave up after the acquire timeout.
+    pub timeouts: u64,
+    pub avg_wait_ms: f64,
+    pub max_wait_ms: f64,
+}
+
+/// Columns filled in by the server rather than written by clients.
+#[derive(Debug, Clone, Default)]
+pub struct GeneratedColumns {
+    /// Generated from an expression; they cannot be written at all.
+    pub computed: Vec<String>,
+    /// Postgres `GENERATED ALWAYS AS IDENTITY`; writable only with
+    /// `OVERRIDING SYSTEM VALUE`.
+    pub identity_always: Vec<String>,
+    /// MySQL `AUTO_INCREMENT`, the only columns `LAST_INSERT_ID()` reports.
+    pub auto_increment: Vec<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct DatabaseConnection {
     config: ConnectionConfig,
     pool: sqlx::AnyPool,
+    metrics: Arc<PoolMetrics>,
     db_type: String,
+    url: String,
+    /// Where the driver connects: the SSH tunnel's local end when tunnelled.
+    host: String,
+    port: u16,
+    /// Kept open while any clone of the connection is alive.
+    _tunnel: Option<Arc<SshTunnel>>,
+    relay: Option<Arc<ServerNameRelay>>,
 }
 
 impl DatabaseConnection {
     pub async fn new(config: ConnectionConfig) -> Result<Self, Box<dyn std::error::Error>> {
-        let password = config.password.clone().unwrap_or_default();
+        driver_options::validate(&config)?;
         let db_type = config.r#type.clone();
-        let url = match config.r#type.as_str() {
-            "sqlite" => format!("sqlite:{}", config.database),
-            "postgresql" => {
-                let ssl = config.ssl.unwrap_or(false);
-                format!(
-                    "postgresql://{}:{}@{}:{}/{}?sslmode={}",
-                    config.username,
-                    password,
-                    config.host,
-                    config.port,
-                    config.database,
-                    if ssl { "require" } else { "disable" }
-                )
-            }
-            "mysql" | "mariadb" => {
-                format!(
-                    "mysql://{}:{}@{}:{}/{}",
-                    config.username,
-                    password,
-                    config.host,
-                    config.port,
-                    config.database
-                )
+        let tls_config = config.tls_config();
+        let (host, port, tunnel) = match config.r#type.as_str() {
+            "sqlite" => (config.host.clone(), config.port, None),
+            _ => ssh::connect_target(&config).await?,
+        };
+        let relay = match db_type.as_str() {
+            "sqlite" => None,
+            _ => tls_relay::open(&db_type, &tls_config, &host, port).await?,
+        };
+        let url = match (config.r#type.as_str(), &relay) {
+            ("postgresql" | "mysql" | "mariadb", Some(relay)) => {
+                dsn::driver_socket_url(&config, relay.server_name(), port, &tls_config, relay.socket_path())
             }
+            ("sqlite" | "postgresql" | "mysql" | "mariadb", None) => dsn::driver_url(&config, &host, port, &tls_config),
             _ => return Err(Box::new(std::io::Error::new(
                 std::io::ErrorKind::InvalidInput,
                 format!("Unsupported database type: {}", config.r#type)
@@ -91,59 +193,506 @@ impl DatabaseConnection {
         };
         
         sqlx::any::install_default_drivers();
-        let pool = sqlx::AnyPool::connect(&url).await?;
+        let session_sql = session_sql(&config);
+        let pool_config = config.pool.clone().unwrap_or_default();
+
+        let mut options = sqlx::any::AnyPoolOptions::new();
+        let max = pool_config.max_connections.unwrap_or(options.get_max_connections());
+        let min = pool_config.min_connections.unwrap_or(options.get_min_connections());
+        if max == 0 || min > max {
+            return Err(format!("Pool sizes are invalid: min {} and max {} connections", min, max).into());
+        }
+        options = options.max_connections(max).min_connections(min);
+        if let Some(secs) = pool_config.acquire_timeout_secs {
+            options = options.acquire_timeout(Duration::from_secs(secs));
+        }
+        if let Some(secs) = pool_config.idle_timeout_secs {
+            options = options.idle_timeout((secs > 0).then(|| Duration::from_secs(secs)));
+        }
+        if let Some(secs) = pool_config.max_lifetime_secs {
+            options = options.max_lifetime((secs > 0).then(|| Duration::from_secs(secs)));
+        }
+        if !session_sql.is_empty() {
+            let session_sql = Arc::new(session_sql);
+            options = options.after_connect(move |conn, _meta| {
+                let session_sql = session_sql.clone();
+                Box::pin(async move {
+                    for statement in session_sql.iter() {
+                        conn.execute(statement.as_str()).await?;
+                    }
+                    Ok(())
+                })
+            });
+        }
+        let pool = options
+            .connect(&url)
+            .await
+            .map_err(|e| -> Box<dyn std::error::Error> {
+                match e {
+                    // Kept as is so callers can read the SQLSTATE.
+                    sqlx::Error::Database(_) => Box::new(e),
+                    e => tls::explain_error(&e.to_string(), &tls_config, &host).into(),
+                }
+            })?;
         
-        Ok(Self { config, pool, db_type })
+        Ok(Self { config, pool, metrics: Arc::default(), db_type, url, host, port, _tunnel: tunnel, relay })
     }
     
-    pub async fn execute_query(&self, sql: &str) -> Result<QueryRow, Box<dyn std::error::Error>> {
-        let start = std::time::Instant::now();
+    pub fn config(&self) -> &ConnectionConfig {
+        &self.config
+    }
+    
+    pub fn db_type(&self) -> &str {
+        &self.db_type
+    }
+    
+    /// Connection URL, for driver-specific clients that bypass the Any pool
+    /// (bulk loading through `COPY`).
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+    
+    /// Host and port behind `url`, for clients that cannot use its
+    /// parameters (the native MySQL client used by `LOAD DATA`).
+    pub fn connect_target(&self) -> (&str, u16) {
+        (&self.host, self.port)
+    }
+    
+    /// Whether TLS is checked against a server name other than the connect
+    /// host, through a local relay only the sqlx drivers understand.
+    pub fn has_server_name_relay(&self) -> bool {
+        self.relay.is_some()
+    }
+    
+    /// Takes a connection from the pool, recording how long that took.
+    async fn acquire(&self) -> Result<sqlx::pool::PoolConnection<sqlx::Any>, sqlx::Error> {
+        let metrics = &self.metrics;
+        metrics.waiting.fetch_add(1, Ordering::Relaxed);
+        let started = Instant::now();
+        let result = self.pool.acquire().await;
+        let waited = started.elapsed().as_micros() as u64;
+        metrics.waiting.fetch_sub(1, Ordering::Relaxed);
+        metrics.acquires.fetch_add(1, Ordering::Relaxed);
+        metrics.total_wait_us.fetch_add(waited, Ordering::Relaxed);
+        metrics.max_wait_us.fetch_max(waited, Ordering::Relaxed);
+        if matches!(result, Err(sqlx::Error::PoolTimedOut)) {
+            metrics.timeouts.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        let size = self.pool.size();
+        let idle = self.pool.num_idle() as u32;
+        let options = self.pool.options();
+        let acquires = self.metrics.acquires.load(Ordering::Relaxed);
+        let total_wait_us = self.metrics.total_wait_us.load(Ordering::Relaxed);
+        PoolStats {
+            size,
+            idle,
+            in_use: size.saturating_sub(idle),
+            max_connections: options.get_max_connections(),
+            min_connections: options.get_min_connections(),
+            waiting: self.metrics.waiting.load(Ordering::Relaxed),
+            acquires,
+            timeouts: self.metrics.timeouts.load(Ordering::Relaxed),
+            avg_wait_ms: if acquires == 0 { 0.0 } else { total_wait_us as f64 / acquires as f64 / 1000.0 },
+            max_wait_ms: self.metrics.max_wait_us.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+    
+    pub async fn begin(&self) -> Result<sqlx::Transaction<'static, sqlx::Any>, Box<dyn std::error::Error>> {
+        Ok(sqlx::Transaction::begin(self.acquire().await?).await?)
+    }
+    
+    /// Streams the rows of a statement instead of buffering them like `execute_query`.
+    pub fn stream_query<'a>(&'a self, sql: &'a str) -> futures::stream::BoxStream<'a, Result<sqlx::any::AnyRow, sqlx::Error>> {
+        sqlx::query(sql).fetch(&self.pool)
+    }
+    
+    /// Column names a statement would return, without executing it.
+    pub async fn describe_columns(&self, sql: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let describe = (&self.pool).describe(sql).await?;
+        Ok(describe.columns().iter().map(|c| c.name().to_string()).collect())
+    }
+    
+    /// Primary key columns of a table in key order; empty when the table has none.
+    pub async fn primary_key_columns(&self, table: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let (schema, table_name) = match table.split_once('.') {
+            Some((schema, name)) => (Some(schema), name),
+            None => (None, table),
+        };
         
-        println!("[DEBUG] Executing SQL: {}", sql);
-        println!("[DEBUG] Database type: {}", self.db_type);
+        let rows = match self.db_type.as_str() {
+            "sqlite" => {
+                let pragma = format!("PRAGMA table_info({})", dialect::quote_table(&self.db_type, table));
+                let rows = sqlx::query(&pragma).fetch_all(&self.pool).await?;
+                let mut keyed: Vec<(i64, String)> = Vec::new();
+                for row in &rows {
+                    let pk: i64 = row.try_get(5)?;
+                    if pk > 0 {
+                        keyed.push((pk, row.try_get(1)?));
+                    }
+                }
+                keyed.sort();
+                return Ok(keyed.into_iter().map(|(_, name)| name).collect());
+            }
+            "postgresql" => {
+                sqlx::query(
+                    "SELECT kcu.column_name::text \
+                     FROM information_schema.table_constraints tc \
+                     JOIN information_schema.key_column_usage kcu \
+                       ON tc.constraint_name = kcu.constraint_name \
+                      AND tc.table_schema = kcu.table_schema \
+                      AND tc.table_name = kcu.table_name \
+                     WHERE tc.constraint_type = 'PRIMARY KEY' \
+                       AND tc.table_name = $1 \
+                       AND tc.table_schema = COALESCE($2, current_schema()) \
+                     ORDER BY kcu.ordinal_position"
+                )
+                .bind(table_name.to_string())
+                .bind(schema.map(|s| s.to_string()))
+                .fetch_all(&self.pool)
+                .await?
+            }
+            "mysql" | "mariadb" => {
+                sqlx::query(
+                    "SELECT column_name \
+                     FROM information_schema.key_column_usage \
+                     WHERE constraint_name = 'PRIMARY' \
+                       AND table_name = ? \
+                       AND table_schema = COALESCE(?, DATABASE()) \
+                     ORDER BY ordinal_position"
+                )
+                .bind(table_name.to_string())
+                .bind(schema.map(|s| s.to_string()))
+                .fetch_all(&self.pool)
+                .await?
+            }
+            _ => return Ok(Vec::new()),
+        };
         
-        let rows = sqlx::query(sql)
-            .fetch_all(&self.pool)
-            .await?;
+        let mut columns = Vec::new();
+        for row in &rows {
+            columns.push(row.try_get::<String, _>(0)?);
+        }
+        Ok(columns)
+    }
+    
+    /// Columns of the first UNIQUE constraint (or unique index on SQLite), for
+    /// tables that have no primary key.
+    pub async fn unique_key_columns(&self, table: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let (schema, table_name) = match table.split_once('.') {
+            Some((schema, name)) => (Some(schema), name),
+            None => (None, table),
+        };
         
-        let execution_time = start.elapsed();
-        println!("[DEBUG] Query executed in {:?}, returned {} rows", execution_time, rows.len());
+        let rows = match self.db_type.as_str() {
+            "sqlite" => {
+                let pragma = format!("PRAGMA index_list({})", dialect::quote_table(&self.db_type, table));
+                let indexes = sqlx::query(&pragma).fetch_all(&self.pool).await?;
+                for index in &indexes {
+                    let unique: i64 = index.try_get(2)?;
+                    let origin: String = index.try_get(3)?;
+                    let partial: i64 = index.try_get(4)?;
+                    if unique == 0 || partial != 0 || origin == "pk" {
+                        continue;
+                    }
+                    let name: String = index.try_get(1)?;
+                    let pragma = format!("PRAGMA index_info({})", dialect::quote_identifier(&self.db_type, &name));
+                    let columns = sqlx::query(&pragma).fetch_all(&self.pool).await?;
+                    let mut names = Vec::new();
+                    for column in &columns {
+                        names.push(column.try_get::<String, _>(2)?);
+                    }
+                    return Ok(names);
+                }
+                return Ok(Vec::new());
+            }
+            "postgresql" => {
+                sqlx::query(
+                    "SELECT tc.constraint_name::text, kcu.column_name::text \
+                     FROM information_schema.table_constraints tc \
+                     JOIN information_schema.key_column_usage kcu \
+                       ON tc.constraint_name = kcu.constraint_name \
+                      AND tc.table_schema = kcu.table_schema \
+                      AND tc.table_name = kcu.table_name \
+                     WHERE tc.constraint_type = 'UNIQUE' \
+                       AND tc.table_name = $1 \
+                       AND tc.table_schema = COALESCE($2, current_schema()) \
+                     ORDER BY tc.constraint_name, kcu.ordinal_position"
+                )
+                .bind(table_name.to_string())
+                .bind(schema.map(|s| s.to_string()))
+                .fetch_all(&self.pool)
+                .await?
+            }
+            "mysql" | "mariadb" => {
+                sqlx::query(
+                    "SELECT tc.constraint_name, kcu.column_name \
+                     FROM information_schema.table_constraints tc \
+                     JOIN information_schema.key_column_usage kcu \
+                       ON tc.constraint_name = kcu.constraint_name \
+                      AND tc.table_schema = kcu.table_schema \
+                      AND tc.table_name = kcu.table_name \
+                     WHERE tc.constraint_type = 'UNIQUE' \
+                       AND tc.table_name = ? \
+                       AND tc.table_schema = COALESCE(?, DATABASE()) \
+                     ORDER BY tc.constraint_name, kcu.ordinal_position"
+                )
+                .bind(table_name.to_string())
+                .bind(schema.map(|s| s.to_string()))
+                .fetch_all(&self.pool)
+                .await?
+            }
+            _ => return Ok(Vec::new()),
+        };
         
         let mut columns = Vec::new();
-        let mut types = Vec::new();
-        let mut results = Vec::new();
-        
-        if let Some(row) = rows.first() {
-            for i in 0..row.len() {
-                let column = row.column(i);
-                columns.push(column.name.to_string());
-                types.push(format!("{:?}", column.type_info));
+        let mut first_constraint: Option<String> = None;
+        for row in &rows {
+            let constraint: String = row.try_get(0)?;
+            match &first_constraint {
+                Some(name) if *name != constraint => break,
+                Some(_) => {}
+                None => first_constraint = Some(constraint),
             }
-            println!("[DEBUG] Found {} columns: {:?}", columns.len(), columns);
+            columns.push(row.try_get::<String, _>(1)?);
+        }
+        Ok(columns)
+    }
+    
+    /// Declared column types (`integer`, `character varying(40)`, `my_enum`)
+    /// keyed by column name. Only Postgres needs them, to cast bound text
+    /// parameters; other engines return an empty map.
+    pub async fn column_type_names(&self, table: &str) -> Result<std::collections::HashMap<String, String>, Box<dyn std::error::Error>> {
+        let mut types = std::collections::HashMap::new();
+        if self.db_type != "postgresql" {
+            return Ok(types);
         }
         
+        let rows = sqlx::query(
+            "SELECT a.attname::text, format_type(a.atttypid, a.atttypmod) \
+             FROM pg_attribute a \
+             WHERE a.attrelid = $1::regclass AND a.attnum > 0 AND NOT a.attisdropped"
+        )
+        .bind(dialect::quote_table(&self.db_type, table))
+        .fetch_all(&self.pool)
+        .await?;
+        
         for row in &rows {
-            let mut values = Vec::new();
-            for i in 0..row.len() {
-                let json_value = convert_to_json_value(row, i);
-                values.push(json_value);
+            types.insert(row.try_get::<String, _>(0)?, row.try_get::<String, _>(1)?);
+        }
+        Ok(types)
+    }
+    
+    /// Columns whose values the server computes. Read through `to_jsonb` on
+    /// Postgres so servers older than `attgenerated` (12) still answer.
+    pub async fn generated_columns(&self, table: &str) -> Result<GeneratedColumns, Box<dyn std::error::Error>> {
+        let (schema, table_name) = match table.split_once('.') {
+            Some((schema, name)) => (Some(schema), name),
+            None => (None, table),
+        };
+        
+        let mut generated = GeneratedColumns::default();
+        match self.db_type.as_str() {
+            "sqlite" => {
+                let pragma = format!("PRAGMA table_xinfo({})", dialect::quote_table(&self.db_type, table));
+                for row in sqlx::query(&pragma).fetch_all(&self.pool).await? {
+                    // 2 and 3 mark virtual and stored generated columns.
+                    let hidden: i64 = row.try_get(6)?;
+                    if matches!(hidden, 2 | 3) {
+                        generated.computed.push(row.try_get(1)?);
+                    }
+                }
+            }
+            "postgresql" => {
+                let rows = sqlx::query(
+                    "SELECT a.attname::text, COALESCE(to_jsonb(a) ->> 'attgenerated', ''), COALESCE(to_jsonb(a) ->> 'attidentity', '') \
+                     FROM pg_attribute a \
+                     WHERE a.attrelid = $1::regclass AND a.attnum > 0 AND NOT a.attisdropped"
+                )
+                .bind(dialect::quote_table(&self.db_type, table))
+                .fetch_all(&self.pool)
+                .await?;
+                for row in &rows {
+                    let name: String = row.try_get(0)?;
+                    if !row.try_get::<String, _>(1)?.is_empty() {
+                        generated.computed.push(name);
+                    } else if row.try_get::<String, _>(2)? == "a" {
+                        generated.identity_always.push(name);
+                    }
+                }
+            }
+            "mysql" | "mariadb" => {
+                let rows = sqlx::query(
+                    "SELECT column_name, UPPER(extra) \
+                     FROM information_schema.columns \
+                     WHERE table_name = ? \
+                       AND table_schema = COALESCE(?, DATABASE())"
+                )
+                .bind(table_name.to_string())
+                .bind(schema.map(|s| s.to_string()))
+                .fetch_all(&self.pool)
+                .await?;
+                for row in &rows {
+                    let name: String = row.try_get(0)?;
+                    let extra: String = row.try_get::<Option<String>, _>(1)?.unwrap_or_default();
+                    // MariaDB reports `VIRTUAL`/`PERSISTENT`/`STORED` alone.
+                    if extra.contains("GENERATED") && !extra.contains("DEFAULT_GENERATED")
+                        || matches!(extra.as_str(), "VIRTUAL" | "PERSISTENT" | "STORED")
+                    {
+                        generated.computed.push(name);
+                    } else if extra.contains("AUTO_INCREMENT") {
+                        generated.auto_increment.push(name);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(generated)
+    }
+    
+    pub async fn execute_query(&self, sql: &str) -> Result<QueryRow, Box<dyn std::error::Error>> {
+        Ok(self.execute_query_limited(sql, &ResolvedLimits::default()).await?.0)
+    }
+    
+    /// `execute_query` with a statement timeout and a row cap; also returns
+    /// whether rows were left unread at the cap and the rows affected as
+    /// reported by the driver. The timeout is set on the server where the
+    /// engine supports one, and enforced here as well.
+    pub async fn execute_query_limited(&self, sql: &str, limits: &ResolvedLimits) -> Result<(QueryRow, bool, u64), Box<dyn std::error::Error>> {
+        let start = std::time::Instant::now();
+        
+        log::debug!("Executing SQL: {}", sql);
+        log::debug!("Database type: {}", self.db_type);
+        
+        let mut conn = self.acquire().await?;
+        let restore = match limits.timeout {
+            Some(timeout) => self.set_statement_timeout(&mut conn, timeout).await?,
+            None => None,
+        };
+        let fetch = async {
+            let mut rows = Vec::new();
+            let mut rows_affected = 0;
+            let mut stream = sqlx::query(sql).fetch_many(&mut *conn);
+            while let Some(step) = stream.try_next().await? {
+                match step {
+                    sqlx::Either::Left(done) => rows_affected += done.rows_affected(),
+                    sqlx::Either::Right(_) if limits.max_rows.is_some_and(|max| rows.len() >= max) => {
+                        return Ok::<_, sqlx::Error>((rows, true, rows_affected));
+                    }
+                    sqlx::Either::Right(row) => rows.push(row),
+                }
+            }
+            Ok((rows, false, rows_affected))
+        };
+        let fetched = match limits.timeout {
+            Some(timeout) => match tokio::time::timeout(if restore.is_some() { timeout + CLIENT_TIMEOUT_GRACE } else { timeout }, fetch).await {
+                Ok(fetched) => fetched,
+                Err(_) => {
+                    // The statement may still be running; close the
+                    // connection rather than hand it back to the pool.
+                    drop(conn.detach());
+                    return Err(format!("Query cancelled after exceeding the {} ms timeout", timeout.as_millis()).into());
+                }
+            },
+            None => fetch.await,
+        };
+        if matches!(fetched, Ok((_, true, _))) {
+            // The server is still sending the rest of the result; close the
+            // connection instead of draining it back into the pool.
+            drop(conn.detach());
+        } else if let Some(restore) = restore {
+            if let Err(e) = conn.as_mut().execute(restore.as_str()).await {
+                // Keep the query's timeout from applying to later users of
+                // this pooled connection.
+                log::warn!("Could not reset the statement timeout, closing the connection: {}", e);
+                drop(conn.detach());
             }
-            results.push(values);
         }
+        let (rows, truncated, rows_affected) = fetched?;
         
-        println!("[DEBUG] Processed {} result rows", results.len());
+        let execution_time = start.elapsed();
+        log::debug!("Query executed in {:?}, returned {} rows{}", execution_time, rows.len(), if truncated { " (truncated)" } else { "" });
+        
+        let (columns, types) = rows.first().map(row_columns).unwrap_or_default();
+        if !columns.is_empty() {
+            log::debug!("Found {} columns: {:?}", columns.len(), columns);
+        }
         
-        Ok(QueryRow {
+        let results: Vec<Vec<serde_json::Value>> = rows.iter().map(row_values).collect();
+        
+        log::debug!("Processed {} result rows", results.len());
+        
+        Ok((QueryRow {
             columns,
             types,
             rows: results,
-        })
+        }, truncated, rows_affected))
+    }
+    
+    /// Sets a server-side statement timeout on `conn` and returns the
+    /// statement that undoes it; `None` for engines without one.
+    async fn set_statement_timeout(&self, conn: &mut sqlx::AnyConnection, timeout: Duration) -> Result<Option<String>, sqlx::Error> {
+        let ms = timeout.as_millis();
+        match self.db_type.as_str() {
+            // Restores the session's own value, which init SQL or session
+            // options may have set, rather than the server default.
+            "postgresql" => {
+                let previous: String = sqlx::query_scalar("SELECT current_setting('statement_timeout')")
+                    .fetch_one(&mut *conn)
+                    .await?;
+                conn.execute(format!("SET statement_timeout = {}", ms).as_str()).await?;
+                Ok(Some(format!("SET statement_timeout = {}", dialect::quote_string("postgresql", &previous))))
+            }
+            // Applies to SELECT statements only.
+            "mysql" => {
+                let previous: i64 = sqlx::query_scalar("SELECT CAST(@@SESSION.max_execution_time AS SIGNED)")
+                    .fetch_one(&mut *conn)
+                    .await?;
+                conn.execute(format!("SET SESSION max_execution_time = {}", ms).as_str()).await?;
+                Ok(Some(format!("SET SESSION max_execution_time = {}", previous)))
+            }
+            "mariadb" => {
+                let previous: String = sqlx::query_scalar("SELECT CAST(@@SESSION.max_statement_time AS CHAR)")
+                    .fetch_one(&mut *conn)
+                    .await?;
+                conn.execute(format!("SET SESSION max_statement_time = {:.3}", timeout.as_secs_f64()).as_str()).await?;
+                Ok(Some(format!("SET SESSION max_statement_time = {}", previous)))
+            }
+            _ => Ok(None),
+        }
     }
     
+    pub async fn ping(&self) -> Result<(), Box<dyn std::error::Error>> {
+        sqlx::query("SELECT 1").execute(&mut *self.acquire().await?).await?;
+        Ok(())
+    }
+
+    pub async fn server_version(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let sql = match self.db_type.as_str() {
+            "postgresql" => "SELECT version()",
+            "sqlite" => "SELECT sqlite_version()",
+            _ => "SELECT VERSION()",
+        };
+        Ok(sqlx::query_scalar::<_, String>(sql).fetch_one(&self.pool).await?)
+    }
+
+    /// Number of tables visible in the connection's database.
+    pub async fn table_count(&self) -> Result<i64, Box<dyn std::error::Error>> {
+        let sql = match self.db_type.as_str() {
+            "postgresql" => "SELECT count(*) FROM information_schema.tables WHERE table_schema NOT IN ('pg_catalog', 'information_schema')",
+            "sqlite" => "SELECT count(*) FROM sqlite_master WHERE type = 'table'",
+            _ => "SELECT COUNT(*) FROM information_schema.tables WHERE table_schema = DATABASE()",
+        };
+        Ok(sqlx::query_scalar::<_, i64>(sql).fetch_one(&self.pool).await?)
+    }
+
     pub async fn get_schema(&self) -> Result<Vec<TableInfo>, Box<dyn std::error::Error>> {
         let query = match self.db_type.as_str() {
             "sqlite" => r#"
-                SELECT name, type FROM (
+                SELECT 'public', name, type FROM (
                     SELECT name, 'table' as type FROM sqlite_master WHERE type='table'
                     UNION ALL
                     SELECT name, 'view' as type FROM sqlite_master WHERE type='view'
@@ -151,13 +700,13 @@ impl DatabaseConnection {
                 ORDER BY name
             "#,
             "postgresql" => r#"
-                SELECT table_name, table_type 
+                SELECT 'public', table_name, table_type 
                 FROM information_schema.tables 
                 WHERE table_schema = 'public'
                 ORDER BY table_name
             "#,
             "mysql" | "mariadb" => r#"
-                SELECT table_name, table_type 
+                SELECT 'public', table_name, table_type 
                 FROM information_schema.tables 
                 WHERE table_schema = DATABASE()
                 ORDER BY table_name
@@ -168,6 +717,42 @@ impl DatabaseConnection {
             ))),
         };
         
+        self.load_tables(query).await
+    }
+    
+    /// Tables and views of every user schema, labelled with the schema they
+    /// live in, for snapshots and schema comparison.
+    pub async fn get_all_tables(&self) -> Result<Vec<TableInfo>, Box<dyn std::error::Error>> {
+        let query = match self.db_type.as_str() {
+            "sqlite" => r#"
+                SELECT 'main', name, type FROM sqlite_master
+                WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite_%'
+                ORDER BY name
+            "#,
+            "postgresql" => r#"
+                SELECT table_schema::text, table_name::text, table_type::text
+                FROM information_schema.tables
+                WHERE table_schema NOT IN ('pg_catalog', 'information_schema')
+                  AND table_schema NOT LIKE 'pg_toast%' AND table_schema NOT LIKE 'pg_temp%'
+                ORDER BY table_schema, table_name
+            "#,
+            "mysql" | "mariadb" => r#"
+                SELECT table_schema, table_name, table_type
+                FROM information_schema.tables
+                WHERE table_schema = DATABASE()
+                ORDER BY table_name
+            "#,
+            _ => return Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Schema not supported for this database type"
+            ))),
+        };
+        
+        self.load_tables(query).await
+    }
+    
+    /// Reads `(schema, name, type)` rows and fetches the columns of each table.
+    async fn load_tables(&self, query: &str) -> Result<Vec<TableInfo>, Box<dyn std::error::Error>> {
         let rows = sqlx::query(query)
             .fetch_all(&self.pool)
             .await?;
@@ -175,14 +760,15 @@ impl DatabaseConnection {
         let mut tables = Vec::new();
         
         for row in &rows {
-            let table_name: String = row.try_get(0)?;
-            let table_type: String = row.try_get(1)?;
+            let schema: String = row.try_get(0)?;
+            let table_name: String = row.try_get(1)?;
+            let table_type: String = row.try_get(2)?;
             
-            let columns = self.get_columns(&table_name).await?;
+            let columns = self.get_columns(&schema, &table_name).await?;
             
             tables.push(TableInfo {
                 name: table_name,
-                schema: Some("public".to_string()),
+                schema: Some(schema),
                 r#type: if table_type.contains("VIEW") || table_type == "view" {
                     "view".to_string()
                 } else {
@@ -197,14 +783,22 @@ impl DatabaseConnection {
         Ok(tables)
     }
     
-    async fn get_columns(&self, table_name: &str) -> Result<Vec<ColumnInfo>, Box<dyn std::error::Error>> {
+    async fn get_columns(&self, schema: &str, table_name: &str) -> Result<Vec<ColumnInfo>, Box<dyn std::error::Error>> {
         let query = match self.db_type.as_str() {
             "sqlite" => format!("PRAGMA table_info({})", table_name),
             "postgresql" => format!(
-                "SELECT column_name, data_type, is_nullable, column_default, is_primary_key \
-                 FROM information_schema.columns \
-                 WHERE table_name = '{}' \
-                 ORDER BY ordinal_position",
+                "SELECT c.column_name::text, c.data_type::text, c.is_nullable::text, c.column_default::text, \
+                        CASE WHEN EXISTS ( \
+                            SELECT 1 FROM information_schema.table_constraints tc \
+                            JOIN information_schema.key_column_usage k \
+                              ON k.constraint_schema = tc.constraint_schema AND k.constraint_name = tc.constraint_name \
+                            WHERE tc.constraint_type = 'PRIMARY KEY' AND tc.table_schema = c.table_schema \
+                              AND tc.table_name = c.table_name AND k.column_name = c.column_name \
+                        ) THEN 'PRIMARY KEY' END \
+                 FROM information_schema.columns c \
+                 WHERE c.table_schema = {} AND c.table_name = '{}' \
+                 ORDER BY c.ordinal_position",
+                dialect::quote_string(&self.db_type, schema),
                 table_name
             ),
             "mysql" | "mariadb" => format!(
@@ -268,13 +862,98 @@ impl DatabaseConnection {
         Ok(columns)
     }
     
-    pub async fn execute_ddl(&self, ddl: &str) -> Result<(), Box<dyn std::error::Error>> {
-        println!("[DEBUG] Executing DDL: {}", ddl);
-        println!("[DEBUG] Database type: {}", self.db_type);
+    /// Indexes, constraints, views, triggers and routines of the user schemas,
+    /// each with the definition the server reports for it.
+    pub async fn get_schema_objects(&self) -> Result<Vec<SchemaObject>, Box<dyn std::error::Error>> {
+        // Every query returns (kind, schema, name, table, definition).
+        let queries: &[&str] = match self.db_type.as_str() {
+            "postgresql" => &[
+                "SELECT 'index', schemaname::text, indexname::text, tablename::text, indexdef \
+                 FROM pg_indexes WHERE schemaname NOT IN ('pg_catalog', 'information_schema') AND schemaname NOT LIKE 'pg_toast%'",
+                "SELECT 'constraint', n.nspname::text, c.conname::text, r.relname::text, pg_get_constraintdef(c.oid) \
+                 FROM pg_constraint c \
+                 JOIN pg_namespace n ON n.oid = c.connamespace \
+                 JOIN pg_class r ON r.oid = c.conrelid \
+                 WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')",
+                "SELECT 'view', schemaname::text, viewname::text, NULL::text, definition \
+                 FROM pg_views WHERE schemaname NOT IN ('pg_catalog', 'information_schema')",
+                "SELECT 'materialized_view', schemaname::text, matviewname::text, NULL::text, definition FROM pg_matviews",
+                "SELECT 'trigger', n.nspname::text, t.tgname::text, r.relname::text, pg_get_triggerdef(t.oid) \
+                 FROM pg_trigger t \
+                 JOIN pg_class r ON r.oid = t.tgrelid \
+                 JOIN pg_namespace n ON n.oid = r.relnamespace \
+                 WHERE NOT t.tgisinternal AND n.nspname NOT IN ('pg_catalog', 'information_schema')",
+                "SELECT CASE p.prokind WHEN 'p' THEN 'procedure' ELSE 'function' END, n.nspname::text, \
+                        p.oid::regprocedure::text, NULL::text, pg_get_functiondef(p.oid) \
+                 FROM pg_proc p JOIN pg_namespace n ON n.oid = p.pronamespace \
+                 WHERE p.prokind IN ('f', 'p') AND n.nspname NOT IN ('pg_catalog', 'information_schema') \
+                   AND NOT EXISTS (SELECT 1 FROM pg_depend d WHERE d.objid = p.oid AND d.deptype = 'e')",
+            ],
+            "mysql" | "mariadb" => &[
+                "SELECT 'index', table_schema, index_name, table_name, \
+                        CONCAT(IF(non_unique = 0, 'UNIQUE ', ''), index_type, ' (', \
+                               GROUP_CONCAT(column_name ORDER BY seq_in_index), ')') \
+                 FROM information_schema.statistics WHERE table_schema = DATABASE() \
+                 GROUP BY table_schema, table_name, index_name, non_unique, index_type",
+                "SELECT 'constraint', tc.constraint_schema, tc.constraint_name, tc.table_name, \
+                        CONCAT(tc.constraint_type, \
+                               IFNULL(CONCAT(' (', GROUP_CONCAT(k.column_name ORDER BY k.ordinal_position), ')'), ''), \
+                               IFNULL(CONCAT(' REFERENCES ', MAX(k.referenced_table_name), \
+                                             ' (', GROUP_CONCAT(k.referenced_column_name ORDER BY k.ordinal_position), ')'), '')) \
+                 FROM information_schema.table_constraints tc \
+                 LEFT JOIN information_schema.key_column_usage k \
+                   ON k.constraint_schema = tc.constraint_schema AND k.table_name = tc.table_name \
+                  AND k.constraint_name = tc.constraint_name \
+                 WHERE tc.constraint_schema = DATABASE() \
+                 GROUP BY tc.constraint_schema, tc.table_name, tc.constraint_name, tc.constraint_type",
+                "SELECT 'view', table_schema, table_name, CAST(NULL AS CHAR), view_definition \
+                 FROM information_schema.views WHERE table_schema = DATABASE()",
+                "SELECT 'trigger', trigger_schema, trigger_name, event_object_table, \
+                        CONCAT(action_timing, ' ', event_manipulation, ' ', action_statement) \
+                 FROM information_schema.triggers WHERE trigger_schema = DATABASE()",
+                "SELECT LOWER(routine_type), routine_schema, routine_name, CAST(NULL AS CHAR), routine_definition \
+                 FROM information_schema.routines WHERE routine_schema = DATABASE()",
+            ],
+            // Constraints are part of each table's CREATE statement.
+            "sqlite" => &[
+                "SELECT type, 'main', name, CASE WHEN type = 'view' THEN NULL ELSE tbl_name END, sql \
+                 FROM sqlite_master \
+                 WHERE type IN ('index', 'view', 'trigger') AND sql IS NOT NULL AND name NOT LIKE 'sqlite_%'",
+            ],
+            _ => return Ok(Vec::new()),
+        };
         
-        let result = self.pool.execute(ddl).await?;
-        println!("[DEBUG] DDL executed successfully, affected rows: {}", result.rows_affected());
+        let mut objects = Vec::new();
+        for query in queries {
+            for row in sqlx::query(query).fetch_all(&self.pool).await? {
+                objects.push(SchemaObject {
+                    kind: row.try_get(0)?,
+                    schema: row.try_get(1)?,
+                    name: row.try_get(2)?,
+                    table: row.try_get(3)?,
+                    definition: row.try_get::<Option<String>, _>(4)?.unwrap_or_default(),
+                });
+            }
+        }
         
-        Ok(())
+        Ok(objects)
+    }
+    
+    /// Runs a data-modifying statement and returns the affected row count.
+    pub async fn execute_statement(&self, sql: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        let result = self.acquire().await?.as_mut().execute(sql).await?;
+        Ok(result.rows_affected())
+    }
+    
+    /// Runs a DDL statement and returns the affected row count reported by
+    /// the driver.
+    pub async fn execute_ddl(&self, ddl: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        log::debug!("Executing DDL: {}", ddl);
+        log::debug!("Database type: {}", self.db_type);
+        
+        let result = self.acquire().await?.as_mut().execute(ddl).await?;
+        log::debug!("DDL executed successfully, affected rows: {}", result.rows_affected());
+        
+        Ok(result.rows_affected())
     }
 }
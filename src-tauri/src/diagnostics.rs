@@ -0,0 +1,468 @@
+//! Connection diagnostics: connects step by step and reports which stage
+//! fails, how long each took, and the underlying error. Nothing is kept
+//! once the checks finish.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use crate::database::DatabaseConnection;
+use crate::logging;
+use crate::mongodb::MongoConnection;
+use crate::redis::RedisConnection;
+use crate::ssh::SshTunnel;
+use crate::tls;
+use crate::tls_relay;
+use crate::types::{ConnectionConfig, TlsConfig, TlsMode};
+
+/// Upper bound for a single stage, so an unreachable host does not leave
+/// the dialog waiting on driver timeouts.
+const STAGE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// In the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Dns,
+    Tcp,
+    Ssh,
+    Tls,
+    Authentication,
+    DatabaseAccess,
+    ServerVersion,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StageStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StageResult {
+    pub stage: Stage,
+    pub status: StageStatus,
+    pub duration_ms: u64,
+    /// What was checked, or why the stage was skipped.
+    pub detail: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionDiagnostics {
+    pub success: bool,
+    pub stages: Vec<StageResult>,
+    pub total_ms: u64,
+    pub server_version: Option<String>,
+}
+
+enum Driver {
+    Sql(DatabaseConnection),
+    Mongo(MongoConnection),
+    Redis(RedisConnection),
+}
+
+struct Run<'a> {
+    config: &'a ConnectionConfig,
+    stages: Vec<StageResult>,
+}
+
+impl Run<'_> {
+    fn failed(&self) -> bool {
+        self.stages.iter().any(|stage| stage.status == StageStatus::Failed)
+    }
+
+    fn push(&mut self, stage: Stage, status: StageStatus, started: Option<Instant>, detail: Option<String>, error: Option<String>) {
+        self.stages.push(StageResult {
+            stage,
+            status,
+            duration_ms: started.map_or(0, |started| started.elapsed().as_millis() as u64),
+            detail,
+            error: error.map(|error| logging::redact_error(&error, self.config.password.as_deref())),
+        });
+    }
+
+    fn skip(&mut self, stage: Stage, reason: &str) {
+        self.push(stage, StageStatus::Skipped, None, Some(reason.to_string()), None);
+    }
+
+    /// Runs `check` as `stage` unless an earlier stage failed. The check
+    /// returns the stage detail on success.
+    async fn stage<T, F>(&mut self, stage: Stage, check: F) -> Option<T>
+    where
+        F: Future<Output = Result<(T, String), String>>,
+    {
+        if self.failed() {
+            self.skip(stage, "Skipped after an earlier failure");
+            return None;
+        }
+        let started = Instant::now();
+        let result = match tokio::time::timeout(STAGE_TIMEOUT, check).await {
+            Ok(result) => result,
+            Err(_) => Err(format!("Timed out after {} seconds", STAGE_TIMEOUT.as_secs())),
+        };
+        match result {
+            Ok((value, detail)) => {
+                self.push(stage, StageStatus::Passed, Some(started), Some(detail), None);
+                Some(value)
+            }
+            Err(error) => {
+                self.push(stage, StageStatus::Failed, Some(started), None, Some(error));
+                None
+            }
+        }
+    }
+}
+
+pub async fn test_connection(config: &ConnectionConfig) -> ConnectionDiagnostics {
+    let started = Instant::now();
+    let mut run = Run { config, stages: Vec::new() };
+    let db_type = config.r#type.as_str();
+    let network = db_type != "sqlite";
+    let srv = config.ssh.is_none() && config.srv.unwrap_or(false);
+
+    // The first network hop: the SSH server when tunnelling, else the database.
+    let (hop_host, hop_port) = match &config.ssh {
+        Some(ssh) => {
+            let first = ssh.jump_hosts.iter().flatten().next().unwrap_or(&ssh.hop);
+            (first.host.clone(), first.port.unwrap_or(22))
+        }
+        None => (config.host.clone(), config.port),
+    };
+    let hosts = host_list(&hop_host, hop_port);
+
+    // Where the driver's TLS handshake goes, and the host it connects to.
+    let mut tls_target = None;
+    if !network {
+        run.skip(Stage::Dns, "SQLite opens a local file");
+        run.skip(Stage::Tcp, "SQLite opens a local file");
+    } else if srv {
+        run.skip(Stage::Dns, "SRV records are resolved by the driver");
+        run.skip(Stage::Tcp, "Hosts come from SRV records resolved by the driver");
+    } else {
+        let addresses = run.stage(Stage::Dns, resolve(&hosts)).await;
+        match addresses {
+            Some(addresses) => {
+                if config.ssh.is_none() {
+                    tls_target = addresses.first().cloned();
+                }
+                run.stage(Stage::Tcp, reach(addresses)).await;
+            }
+            None => run.skip(Stage::Tcp, "Skipped after an earlier failure"),
+        }
+    }
+
+    let mut target = config.clone();
+    let mut _tunnel = None;
+    match &config.ssh {
+        Some(ssh) => {
+            let opened = run.stage(Stage::Ssh, async {
+                if config.srv.unwrap_or(false) || config.host.contains(',') {
+                    return Err("An SSH tunnel forwards to a single host; it cannot be combined with SRV lookup or a host list".to_string());
+                }
+                let tunnel = SshTunnel::open(ssh, &config.host, config.port).await.map_err(|e| e.to_string())?;
+                let hops = ssh.jump_hosts.as_ref().map_or(0, Vec::len) + 1;
+                let detail = format!(
+                    "{} SSH host{} connected; forwarding 127.0.0.1:{} to {}:{}",
+                    hops, if hops == 1 { "" } else { "s" }, tunnel.local_port(), config.host, config.port
+                );
+                Ok((tunnel, detail))
+            }).await;
+            if let Some(tunnel) = opened {
+                // The drivers connect through this tunnel rather than opening
+                // their own.
+                target.ssh = None;
+                target.host = "127.0.0.1".to_string();
+                target.port = tunnel.local_port();
+                if db_type == "mongodb" {
+                    target.options.get_or_insert_with(Default::default)
+                        .insert("directConnection".to_string(), "true".to_string());
+                }
+                tls_target = Some((target.host.clone(), std::net::SocketAddr::from(([127, 0, 0, 1], target.port))));
+                _tunnel = Some(tunnel);
+            }
+        }
+        None => run.skip(Stage::Ssh, "No SSH tunnel configured"),
+    }
+
+    let tls = config.tls_config();
+    if !network {
+        run.skip(Stage::Tls, "SQLite opens a local file");
+    } else if tls.mode == TlsMode::Disable {
+        run.skip(Stage::Tls, "TLS is disabled");
+    } else if srv {
+        run.skip(Stage::Tls, "Hosts come from SRV records resolved by the driver");
+    } else {
+        match tls_target {
+            Some((host, address)) => {
+                run.stage(Stage::Tls, async {
+                    handshake(db_type, &tls, &host, address).await.map_err(|e| tunnel_hint(config, e))
+                }).await;
+            }
+            None => run.skip(Stage::Tls, "Skipped after an earlier failure"),
+        }
+    }
+
+    let access = if network {
+        run.stage(Stage::Authentication, authenticate(&target)).await
+    } else {
+        run.skip(Stage::Authentication, "SQLite has no authentication");
+        Some(Access::Connect)
+    };
+    let driver = match access {
+        Some(access) => run.stage(Stage::DatabaseAccess, async {
+            let driver = match access {
+                Access::Open(driver) => *driver,
+                Access::Connect => connect(target).await.map_err(|e| tunnel_hint(config, e.to_string()))?,
+                Access::Refused(error) => return Err(error),
+            };
+            let detail = access_detail(&driver, config).await?;
+            Ok((driver, detail))
+        }).await,
+        None => {
+            run.skip(Stage::DatabaseAccess, "Skipped after an earlier failure");
+            None
+        }
+    };
+
+    let server_version = match &driver {
+        Some(driver) => run.stage(Stage::ServerVersion, async {
+            let version = match driver {
+                Driver::Sql(conn) => conn.server_version().await,
+                Driver::Mongo(conn) => conn.server_version().await,
+                Driver::Redis(conn) => conn.server_version().await,
+            }.map_err(|e| e.to_string())?;
+            Ok((version.clone(), version))
+        }).await,
+        None => {
+            run.skip(Stage::ServerVersion, "Skipped after an earlier failure");
+            None
+        }
+    };
+
+    run.stages.sort_by_key(|result| result.stage);
+    let success = !run.failed();
+    log::info!(
+        "Connection test for {} {} in {:?}",
+        config.name, if success { "passed" } else { "failed" }, started.elapsed()
+    );
+    ConnectionDiagnostics {
+        success,
+        stages: run.stages,
+        total_ms: started.elapsed().as_millis() as u64,
+        server_version,
+    }
+}
+
+/// What the database access stage starts from once authenticated.
+enum Access {
+    /// The authenticated connection already has the database open.
+    Open(Box<Driver>),
+    /// Authentication used a connection without the database; open it now.
+    Connect,
+    /// The server authenticated the user, then refused the database.
+    Refused(String),
+}
+
+/// Logs in without opening the configured database where the engine allows
+/// it. Postgres always names a database at login but checks it only after
+/// authenticating, so its SQLSTATE tells the two failures apart.
+async fn authenticate(target: &ConnectionConfig) -> Result<(Access, String), String> {
+    let detail = format!("Authenticated as {}", user(target));
+    let access = match target.r#type.as_str() {
+        "mysql" | "mariadb" | "redis" => {
+            let login = ConnectionConfig { database: String::new(), pool: None, ..target.clone() };
+            connect(login).await.map_err(|e| tunnel_hint(target, e.to_string()))?;
+            Access::Connect
+        }
+        "postgresql" => match connect(target.clone()).await {
+            Ok(driver) => Access::Open(Box::new(driver)),
+            Err(e) if matches!(sqlstate(e.as_ref()).as_deref(), Some("3D000" | "42501")) => Access::Refused(e.to_string()),
+            Err(e) => return Err(tunnel_hint(target, e.to_string())),
+        },
+        // The Mongo driver connects lazily, so authentication happens on the
+        // first command.
+        _ => {
+            let driver = connect(target.clone()).await.map_err(|e| tunnel_hint(target, e.to_string()))?;
+            if let Driver::Mongo(conn) = &driver {
+                conn.ping().await.map_err(|e| e.to_string())?;
+            }
+            Access::Open(Box::new(driver))
+        }
+    };
+    Ok((access, detail))
+}
+
+fn sqlstate(error: &(dyn std::error::Error + 'static)) -> Option<String> {
+    Some(error.downcast_ref::<sqlx::Error>()?.as_database_error()?.code()?.into_owned())
+}
+
+/// Connects to `address` and runs the TLS handshake the driver would,
+/// first asking for TLS inside the Postgres or MySQL protocol. The
+/// certificate is checked against the name the driver verifies.
+async fn handshake(db_type: &str, tls: &TlsConfig, connect_host: &str, address: SocketAddr) -> Result<((), String), String> {
+    let mut stream = TcpStream::connect(address).await.map_err(|e| format!("Cannot reach {}: {}", address, e))?;
+    let offered = match db_type {
+        "postgresql" => request_postgres_tls(&mut stream).await,
+        "mysql" | "mariadb" => request_mysql_tls(&mut stream).await,
+        _ => Ok(true),
+    }.map_err(|e| format!("Requesting TLS from {} failed: {}", address, e))?;
+    if !offered {
+        return match tls.mode {
+            TlsMode::Prefer => Ok(((), "The server does not offer TLS; prefer continues in plain text".to_string())),
+            _ => Err("The server does not offer TLS".to_string()),
+        };
+    }
+
+    let name = tls_relay::verification_name(tls, connect_host).unwrap_or(connect_host);
+    let server_name = rustls::ServerName::try_from(name).map_err(|_| format!("Invalid TLS server name {:?}", name))?;
+    let config = tls_relay::client_config(tls).map_err(|e| e.to_string())?;
+    let session = match tokio_rustls::TlsConnector::from(Arc::new(config)).connect(server_name, stream).await {
+        Ok(session) => session,
+        Err(e) if tls.mode == TlsMode::Prefer => {
+            return Ok(((), format!("Handshake failed ({}); prefer continues in plain text", e)));
+        }
+        Err(e) => return Err(tls::explain_error(&e.to_string(), tls, connect_host)),
+    };
+    let version = session.get_ref().1.protocol_version().map_or("TLS".to_string(), |v| format!("{:?}", v));
+    let checked = match tls.mode {
+        TlsMode::VerifyFull => format!("certificate chain and name {} verified", name),
+        TlsMode::VerifyCa => "certificate chain verified".to_string(),
+        _ => "certificate not verified".to_string(),
+    };
+    Ok(((), format!("{} handshake with {} completed; {}", version, address, checked)))
+}
+
+/// Sends Postgres's `SSLRequest`; the server answers `S` or `N`.
+async fn request_postgres_tls(stream: &mut TcpStream) -> std::io::Result<bool> {
+    stream.write_all(&[0, 0, 0, 8, 0x04, 0xd2, 0x16, 0x2f]).await?;
+    match stream.read_u8().await? {
+        b'S' => Ok(true),
+        b'N' => Ok(false),
+        other => Err(std::io::Error::other(format!("unexpected reply {:#04x}", other))),
+    }
+}
+
+/// Reads the MySQL greeting and, when the server supports TLS, sends the
+/// `SSLRequest` packet that precedes the handshake.
+async fn request_mysql_tls(stream: &mut TcpStream) -> std::io::Result<bool> {
+    const CLIENT_PROTOCOL_41: u32 = 0x0200;
+    const CLIENT_SSL: u32 = 0x0800;
+    const CLIENT_SECURE_CONNECTION: u32 = 0x8000;
+
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await?;
+    let mut greeting = vec![0u8; u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize];
+    stream.read_exact(&mut greeting).await?;
+    if greeting.first() == Some(&0xff) {
+        // An error packet: code, then the message, after a SQLSTATE marker.
+        let message = greeting.get(3..).unwrap_or_default();
+        let message = message.strip_prefix(b"#").and_then(|m| m.get(5..)).unwrap_or(message);
+        return Err(std::io::Error::other(String::from_utf8_lossy(message).into_owned()));
+    }
+    // Protocol version, NUL-terminated server version, connection id,
+    // 8 bytes of scramble and a filler byte come before the capabilities.
+    let version_end = greeting.iter().skip(1).position(|&b| b == 0).map(|i| i + 1)
+        .ok_or_else(|| std::io::Error::other("malformed greeting"))?;
+    let at = version_end + 1 + 4 + 8 + 1;
+    let capabilities = greeting.get(at..at + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]) as u32)
+        .ok_or_else(|| std::io::Error::other("malformed greeting"))?;
+    if capabilities & CLIENT_SSL == 0 {
+        return Ok(false);
+    }
+
+    let mut packet = vec![32, 0, 0, header[3].wrapping_add(1)];
+    packet.extend((CLIENT_PROTOCOL_41 | CLIENT_SSL | CLIENT_SECURE_CONNECTION).to_le_bytes());
+    packet.extend(16_777_216u32.to_le_bytes());
+    // utf8mb4_general_ci, then reserved bytes.
+    packet.push(45);
+    packet.extend([0u8; 23]);
+    stream.write_all(&packet).await?;
+    Ok(true)
+}
+
+/// Through a tunnel, a connection closed straight away usually means the
+/// SSH server could not reach the database.
+fn tunnel_hint(config: &ConnectionConfig, error: String) -> String {
+    match (&config.ssh, is_closed_connection(&error)) {
+        (Some(_), true) => format!("{} (the SSH server may not be able to reach {}:{})", error, config.host, config.port),
+        _ => error,
+    }
+}
+
+async fn connect(config: ConnectionConfig) -> Result<Driver, Box<dyn std::error::Error>> {
+    Ok(match config.r#type.as_str() {
+        "sqlite" | "postgresql" | "mysql" | "mariadb" => Driver::Sql(DatabaseConnection::new(config).await?),
+        "mongodb" => Driver::Mongo(MongoConnection::new(config).await?),
+        "redis" => Driver::Redis(RedisConnection::new(config).await?),
+        other => return Err(format!("Unsupported database type: {}", other).into()),
+    })
+}
+
+async fn access_detail(driver: &Driver, config: &ConnectionConfig) -> Result<String, String> {
+    Ok(match driver {
+        Driver::Sql(conn) => format!("{} tables visible in {}", conn.table_count().await.map_err(|e| e.to_string())?, config.database),
+        Driver::Mongo(conn) => format!("{} collections visible in {}", conn.collection_count().await.map_err(|e| e.to_string())?, config.database),
+        Driver::Redis(conn) => format!("{} keys in database {}", conn.key_count().await.map_err(|e| e.to_string())?, if config.database.is_empty() { "0" } else { &config.database }),
+    })
+}
+
+/// `host:port` pairs to check; Mongo seed lists name several hosts.
+fn host_list(hosts: &str, default_port: u16) -> Vec<(String, u16)> {
+    hosts.split(',').map(str::trim).filter(|h| !h.is_empty()).map(|host| {
+        match host.rsplit_once(':') {
+            Some((name, port)) if !name.contains(':') || name.ends_with(']') => match port.parse() {
+                Ok(port) => (name.trim_matches(['[', ']']).to_string(), port),
+                Err(_) => (host.to_string(), default_port),
+            },
+            _ => (host.trim_matches(['[', ']']).to_string(), default_port),
+        }
+    }).collect()
+}
+
+async fn resolve(hosts: &[(String, u16)]) -> Result<(Vec<(String, std::net::SocketAddr)>, String), String> {
+    if hosts.is_empty() {
+        return Err("No host configured".to_string());
+    }
+    let mut resolved = Vec::new();
+    let mut details = Vec::new();
+    for (host, port) in hosts {
+        let addresses: Vec<std::net::SocketAddr> = tokio::net::lookup_host((host.as_str(), *port))
+            .await
+            .map_err(|e| format!("Cannot resolve {}: {}", host, e))?
+            .collect();
+        let first = *addresses.first().ok_or_else(|| format!("{} has no addresses", host))?;
+        details.push(format!(
+            "{} -> {}", host, addresses.iter().map(|a| a.ip().to_string()).collect::<Vec<_>>().join(", ")
+        ));
+        resolved.push((host.clone(), first));
+    }
+    Ok((resolved, details.join("; ")))
+}
+
+async fn reach(addresses: Vec<(String, std::net::SocketAddr)>) -> Result<((), String), String> {
+    let mut details = Vec::new();
+    for (host, address) in addresses {
+        let started = Instant::now();
+        TcpStream::connect(address).await.map_err(|e| format!("Cannot reach {} ({}): {}", host, address, e))?;
+        details.push(format!("{} reachable in {} ms", address, started.elapsed().as_millis()));
+    }
+    Ok(((), details.join("; ")))
+}
+
+fn is_closed_connection(error: &str) -> bool {
+    let lower = error.to_lowercase();
+    ["connection reset", "unexpected eof", "unexpectedeof", "broken pipe", "connection closed"]
+        .iter()
+        .any(|word| lower.contains(word))
+}
+
+fn user(config: &ConnectionConfig) -> &str {
+    if config.username.is_empty() { "the default user" } else { &config.username }
+}
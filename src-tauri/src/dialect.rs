@@ -0,0 +1,96 @@
+//! SQL dialect helpers for code that generates SQL text. Dialects are keyed
+//! by the connection type strings used in `ConnectionConfig::type`.
+
+pub fn is_postgres(db_type: &str) -> bool {
+    db_type == "postgresql"
+}
+
+pub fn is_mysql(db_type: &str) -> bool {
+    matches!(db_type, "mysql" | "mariadb")
+}
+
+pub fn quote_identifier(db_type: &str, name: &str) -> String {
+    if is_mysql(db_type) {
+        format!("`{}`", name.replace('`', "``"))
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+/// Quotes a possibly schema-qualified table name (`schema.table`).
+pub fn quote_table(db_type: &str, table: &str) -> String {
+    table
+        .split('.')
+        .map(|part| quote_identifier(db_type, part))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Bind-parameter marker for the `n`th (1-based) parameter.
+pub fn placeholder(db_type: &str, n: usize) -> String {
+    if is_postgres(db_type) {
+        format!("${}", n)
+    } else {
+        "?".to_string()
+    }
+}
+
+pub fn quote_string(db_type: &str, value: &str) -> String {
+    let escaped = value.replace('\'', "''");
+    if is_mysql(db_type) {
+        format!("'{}'", escaped.replace('\\', "\\\\"))
+    } else {
+        format!("'{}'", escaped)
+    }
+}
+
+/// Renders a JSON result value as a SQL literal for generated scripts.
+pub fn literal(db_type: &str, value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => "NULL".to_string(),
+        serde_json::Value::Bool(b) => {
+            if is_postgres(db_type) {
+                if *b { "TRUE".to_string() } else { "FALSE".to_string() }
+            } else if *b {
+                "1".to_string()
+            } else {
+                "0".to_string()
+            }
+        }
+        serde_json::Value::Number(n) => n.to_string(),
+        serde_json::Value::String(s) => quote_string(db_type, s),
+        other => quote_string(db_type, &other.to_string()),
+    }
+}
+
+/// Row-value form used in keyset predicates: `"id"` or `("a", "b")`.
+pub fn column_tuple(db_type: &str, columns: &[String]) -> String {
+    let quoted: Vec<String> = columns.iter().map(|c| quote_identifier(db_type, c)).collect();
+    if quoted.len() == 1 {
+        quoted[0].clone()
+    } else {
+        format!("({})", quoted.join(", "))
+    }
+}
+
+pub fn literal_tuple(db_type: &str, values: &[serde_json::Value]) -> String {
+    let literals: Vec<String> = values.iter().map(|v| literal(db_type, v)).collect();
+    if literals.len() == 1 {
+        literals[0].clone()
+    } else {
+        format!("({})", literals.join(", "))
+    }
+}
+
+/// `"a" = 1 AND "b" = 'x'` for identifying a single row by key.
+pub fn key_predicate(db_type: &str, columns: &[String], values: &[serde_json::Value]) -> String {
+    columns
+        .iter()
+        .zip(values)
+        .map(|(column, value)| match value {
+            serde_json::Value::Null => format!("{} IS NULL", quote_identifier(db_type, column)),
+            _ => format!("{} = {}", quote_identifier(db_type, column), literal(db_type, value)),
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
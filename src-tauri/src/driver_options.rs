@@ -0,0 +1,196 @@
+//! Per-engine driver options (`ConnectionConfig.options`): which keys each
+//! engine accepts, their value types, and how they reach the driver — as a
+//! connection string parameter, a session statement, or a command run on
+//! each new connection.
+
+use serde::Serialize;
+use crate::dialect;
+use crate::types::ConnectionConfig;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(tag = "type", content = "choices", rename_all = "snake_case")]
+pub enum OptionKind {
+    Text,
+    Bool,
+    /// Non-negative integer.
+    Integer,
+    Choice(&'static [&'static str]),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Apply {
+    /// Connection string parameter of the same name.
+    Url,
+    /// Postgres run-time parameter, sent as `options[key]` (`-c key=value`).
+    PgRuntime,
+    /// MySQL `SET SESSION` statement.
+    MySqlSession(&'static str),
+    /// Applied by the Redis connection itself.
+    Redis,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct OptionSpec {
+    pub key: &'static str,
+    pub kind: OptionKind,
+    pub description: &'static str,
+    #[serde(skip)]
+    apply: Apply,
+}
+
+const fn spec(key: &'static str, kind: OptionKind, apply: Apply, description: &'static str) -> OptionSpec {
+    OptionSpec { key, kind, description, apply }
+}
+
+const POSTGRES: &[OptionSpec] = &[
+    spec("application_name", OptionKind::Text, Apply::Url, "Name shown in pg_stat_activity"),
+    spec("options", OptionKind::Text, Apply::Url, "Raw command-line options sent at startup, e.g. -c geqo=off"),
+    spec("search_path", OptionKind::Text, Apply::PgRuntime, "Schemas searched for unqualified names"),
+    spec("statement_timeout", OptionKind::Integer, Apply::PgRuntime, "Statement timeout in milliseconds; 0 disables it"),
+    spec("lock_timeout", OptionKind::Integer, Apply::PgRuntime, "Lock wait timeout in milliseconds; 0 disables it"),
+    spec("idle_in_transaction_session_timeout", OptionKind::Integer, Apply::PgRuntime, "Milliseconds an idle transaction may stay open"),
+    spec("timezone", OptionKind::Text, Apply::PgRuntime, "Session time zone, e.g. UTC"),
+];
+
+const MYSQL: &[OptionSpec] = &[
+    spec("charset", OptionKind::Text, Apply::Url, "Connection character set, e.g. utf8mb4"),
+    spec("collation", OptionKind::Text, Apply::Url, "Connection collation, e.g. utf8mb4_0900_ai_ci"),
+    spec("sql_mode", OptionKind::Text, Apply::MySqlSession("sql_mode"), "Session sql_mode, e.g. ANSI_QUOTES,STRICT_TRANS_TABLES"),
+    spec("time_zone", OptionKind::Text, Apply::MySqlSession("time_zone"), "Session time zone, e.g. +00:00 or Europe/Ljubljana"),
+];
+
+const MONGODB: &[OptionSpec] = &[
+    spec("authSource", OptionKind::Text, Apply::Url, "Database holding the user's credentials, e.g. admin"),
+    spec("authMechanism", OptionKind::Choice(&["SCRAM-SHA-1", "SCRAM-SHA-256", "MONGODB-X509", "PLAIN", "MONGODB-AWS"]), Apply::Url, "Authentication mechanism"),
+    spec("replicaSet", OptionKind::Text, Apply::Url, "Replica set name"),
+    spec("readPreference", OptionKind::Choice(&["primary", "primaryPreferred", "secondary", "secondaryPreferred", "nearest"]), Apply::Url, "Which members reads go to"),
+    spec("directConnection", OptionKind::Bool, Apply::Url, "Talk only to the given host instead of discovering the topology"),
+    spec("appName", OptionKind::Text, Apply::Url, "Name recorded in server logs and currentOp"),
+    spec("retryWrites", OptionKind::Bool, Apply::Url, "Retry writes once on transient errors"),
+    spec("w", OptionKind::Text, Apply::Url, "Write concern: a number or majority"),
+    spec("connectTimeoutMS", OptionKind::Integer, Apply::Url, "Connect timeout in milliseconds"),
+    spec("serverSelectionTimeoutMS", OptionKind::Integer, Apply::Url, "How long to wait for a suitable server, in milliseconds"),
+];
+
+const REDIS: &[OptionSpec] = &[
+    // The redis crate in use speaks RESP2 only; RESP3 is refused rather
+    // than silently ignored.
+    spec("protocol", OptionKind::Choice(&["resp2"]), Apply::Redis, "Wire protocol version"),
+    spec("client_name", OptionKind::Text, Apply::Redis, "Name set with CLIENT SETNAME, shown in CLIENT LIST"),
+];
+
+const SQLITE: &[OptionSpec] = &[
+    spec("cache", OptionKind::Choice(&["shared", "private"]), Apply::Url, "Page cache sharing between connections"),
+    spec("immutable", OptionKind::Bool, Apply::Url, "Treat the file as unchangeable; skips locking"),
+    spec("vfs", OptionKind::Text, Apply::Url, "SQLite VFS module name"),
+];
+
+pub fn specs(db_type: &str) -> &'static [OptionSpec] {
+    match db_type {
+        "postgresql" => POSTGRES,
+        "mysql" | "mariadb" => MYSQL,
+        "mongodb" => MONGODB,
+        "redis" => REDIS,
+        "sqlite" => SQLITE,
+        _ => &[],
+    }
+}
+
+/// The spec for `key`, also accepting the `options[key]` form Postgres
+/// connection strings use. Mongo keys are case-insensitive.
+fn find(db_type: &str, key: &str) -> Option<&'static OptionSpec> {
+    let key = key.strip_prefix("options[").and_then(|k| k.strip_suffix(']')).unwrap_or(key);
+    specs(db_type).iter().find(|spec| {
+        spec.key == key || (db_type == "mongodb" && spec.key.eq_ignore_ascii_case(key))
+    })
+}
+
+/// The key as listed in the engine's specs, for keys that parse under
+/// another spelling.
+pub fn canonical_key(db_type: &str, key: &str) -> String {
+    find(db_type, key).map_or_else(|| key.to_string(), |spec| spec.key.to_string())
+}
+
+/// Checks every option against the engine's specs.
+pub fn validate(config: &ConnectionConfig) -> Result<(), String> {
+    let db_type = config.r#type.as_str();
+    for (key, value) in config.options.iter().flatten() {
+        let Some(spec) = find(db_type, key) else {
+            let known: Vec<&str> = specs(db_type).iter().map(|spec| spec.key).collect();
+            return Err(format!(
+                "Unknown {} option {}; supported options are {}",
+                db_type, key, if known.is_empty() { "none".to_string() } else { known.join(", ") }
+            ));
+        };
+        let valid = match spec.kind {
+            OptionKind::Text => !value.trim().is_empty(),
+            OptionKind::Bool => matches!(value.as_str(), "true" | "false"),
+            OptionKind::Integer => value.parse::<u64>().is_ok(),
+            OptionKind::Choice(choices) => choices.iter().any(|c| c.eq_ignore_ascii_case(value)),
+        };
+        if !valid {
+            let expected = match spec.kind {
+                OptionKind::Text => "a non-empty value".to_string(),
+                OptionKind::Bool => "true or false".to_string(),
+                OptionKind::Integer => "a non-negative integer".to_string(),
+                OptionKind::Choice(choices) => format!("one of {}", choices.join(", ")),
+            };
+            return Err(format!("Option {} must be {}, not {:?}", spec.key, expected, value));
+        }
+    }
+    Ok(())
+}
+
+/// Options carried in the connection string, in the driver's spelling.
+pub fn url_params(config: &ConnectionConfig) -> Vec<(String, String)> {
+    let db_type = config.r#type.as_str();
+    config.options.iter().flatten().filter_map(|(key, value)| {
+        match find(db_type, key).map(|spec| spec.apply) {
+            Some(Apply::Url) | None => Some((canonical_key(db_type, key), value.clone())),
+            Some(Apply::PgRuntime) => Some((format!("options[{}]", canonical_key(db_type, key)), value.clone())),
+            Some(Apply::MySqlSession(_)) | Some(Apply::Redis) => None,
+        }
+    }).collect()
+}
+
+/// Options in a connection string meant for other clients (psql, the mysql
+/// shell, other drivers), which reject keys they do not know: Postgres
+/// run-time parameters are folded into one `options=-c key=value ...`, and
+/// options applied after connecting are left out.
+pub fn client_url_params(config: &ConnectionConfig) -> Vec<(String, String)> {
+    let db_type = config.r#type.as_str();
+    let mut params = Vec::new();
+    let mut runtime = Vec::new();
+    for (key, value) in config.options.iter().flatten() {
+        match find(db_type, key).map(|spec| spec.apply) {
+            Some(Apply::Url) | None => params.push((canonical_key(db_type, key), value.clone())),
+            Some(Apply::PgRuntime) => runtime.push(format!("-c {}={}", canonical_key(db_type, key), pg_option_escape(value))),
+            Some(Apply::MySqlSession(_)) | Some(Apply::Redis) => {}
+        }
+    }
+    if !runtime.is_empty() {
+        match params.iter_mut().find(|(key, _)| key == "options") {
+            Some((_, options)) => *options = format!("{} {}", options, runtime.join(" ")),
+            None => params.push(("options".to_string(), runtime.join(" "))),
+        }
+    }
+    params
+}
+
+/// libpq splits `options` on unescaped whitespace.
+fn pg_option_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(' ', "\\ ")
+}
+
+/// Statements to run on every new SQL connection.
+pub fn session_statements(config: &ConnectionConfig) -> Vec<String> {
+    let db_type = config.r#type.as_str();
+    config.options.iter().flatten().filter_map(|(key, value)| match find(db_type, key)?.apply {
+        Apply::MySqlSession(variable) => Some(format!("SET SESSION {} = {}", variable, dialect::quote_string(db_type, value))),
+        _ => None,
+    }).collect()
+}
+
+pub fn option<'a>(config: &'a ConnectionConfig, key: &str) -> Option<&'a str> {
+    config.options.as_ref()?.get(key).map(String::as_str)
+}
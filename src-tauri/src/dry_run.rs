@@ -0,0 +1,245 @@
+//! Dry runs: a statement or script executed inside a transaction that is
+//! always rolled back, reporting what each statement would have changed.
+//! Sequence and auto-increment counters still advance; nothing else persists.
+
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use sqlx::Executor;
+use crate::database::{self, DatabaseConnection};
+use crate::dialect;
+use crate::policy::{self, OperationCategory};
+use crate::statement::{self, TokenKind};
+use crate::undo;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DryRunRequest {
+    pub connection_id: String,
+    pub sql: String,
+    /// Rows of each statement's changes to return; 0 or omitted for counts only.
+    pub sample_rows: Option<usize>,
+    /// Typed confirmation for statements the safety policy asks to confirm.
+    pub confirmation: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DryRunStatement {
+    pub index: usize,
+    pub statement: String,
+    pub verb: String,
+    pub rows_affected: u64,
+    pub sample_columns: Vec<String>,
+    pub sample_rows: Vec<Vec<serde_json::Value>>,
+    /// `returning` (rows after the change) or `pre_select` (rows before it);
+    /// `None` when no sample could be taken.
+    pub sample_source: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DryRunResult {
+    pub statements: Vec<DryRunStatement>,
+    pub total_rows_affected: u64,
+    /// Statements after a failing one are not run.
+    pub completed: bool,
+    pub execution_time: u64,
+}
+
+/// Verbs that would end the surrounding transaction and make the rest of the
+/// script permanent.
+const TRANSACTION_VERBS: &[&str] = &["BEGIN", "START", "COMMIT", "ROLLBACK", "END", "RELEASE"];
+
+/// Storage engines whose changes a rollback undoes.
+const TRANSACTIONAL_ENGINES: &[&str] = &["InnoDB", "ndbcluster", "NDB", "TokuDB", "RocksDB"];
+
+pub async fn dry_run(conn: &DatabaseConnection, sql: &str, sample_rows: usize) -> Result<DryRunResult, String> {
+    let db_type = conn.db_type().to_string();
+    let start = std::time::Instant::now();
+
+    // Refuse up front anything the rollback could not undo.
+    let ranges = statement::split_statements(&db_type, sql);
+    let mut checked = Vec::with_capacity(ranges.len());
+    for (from, to) in ranges {
+        let text = &sql[from..to];
+        let Some(operation) = policy::classify(&db_type, text).pop() else { continue };
+        if TRANSACTION_VERBS.contains(&operation.verb.as_str()) {
+            return Err(format!("Dry run cannot include transaction control: {}", text));
+        }
+        if dialect::is_mysql(&db_type) && matches!(operation.category, OperationCategory::Schema | OperationCategory::Admin) {
+            return Err(format!("MySQL commits implicitly before {}, so it cannot be dry-run", operation.verb));
+        }
+        if dialect::is_mysql(&db_type) {
+            if let Some(what) = mysql_irreversible(&db_type, text, &operation.verb) {
+                return Err(format!("A rollback cannot undo {} on MySQL, so it cannot be dry-run", what));
+            }
+        }
+        if dialect::is_mysql(&db_type) && operation.category == OperationCategory::Write {
+            let table = written_table(&db_type, text).ok_or_else(|| {
+                format!("Cannot tell which table {} writes to, so its storage engine cannot be checked for a dry run", operation.verb)
+            })?;
+            check_transactional(conn, &table).await?;
+        }
+        checked.push((text, operation.verb));
+    }
+
+    let mut tx = conn.begin().await.map_err(|e| e.to_string())?;
+    let mut statements = Vec::with_capacity(checked.len());
+    let mut completed = true;
+    for (index, (text, verb)) in checked.into_iter().enumerate() {
+        let mut result = DryRunStatement {
+            index,
+            statement: text.to_string(),
+            verb: verb.clone(),
+            rows_affected: 0,
+            sample_columns: Vec::new(),
+            sample_rows: Vec::new(),
+            sample_source: None,
+            error: None,
+        };
+
+        if let Err(e) = run_statement(&mut tx, &db_type, text, &verb, sample_rows, &mut result).await {
+            log::warn!("Dry run statement {} failed: {}", index, e);
+            result.error = Some(e);
+            statements.push(result);
+            completed = false;
+            break;
+        }
+        statements.push(result);
+    }
+    tx.rollback().await.map_err(|e| e.to_string())?;
+
+    Ok(DryRunResult {
+        total_rows_affected: statements.iter().map(|s| s.rows_affected).sum(),
+        statements,
+        completed,
+        execution_time: start.elapsed().as_millis() as u64,
+    })
+}
+
+async fn run_statement(
+    tx: &mut sqlx::Transaction<'static, sqlx::Any>,
+    db_type: &str,
+    text: &str,
+    verb: &str,
+    sample_rows: usize,
+    result: &mut DryRunStatement,
+) -> Result<(), String> {
+    let changes_rows = matches!(verb, "INSERT" | "UPDATE" | "DELETE");
+    if sample_rows == 0 || !changes_rows {
+        result.rows_affected = (&mut **tx).execute(text).await.map_err(|e| e.to_string())?.rows_affected();
+        return Ok(());
+    }
+
+    if dialect::is_mysql(db_type) {
+        // No RETURNING: read the rows the same WHERE selects, before the change.
+        if let Some(target) = undo::parse_dml_target(db_type, text) {
+            let select = format!(
+                "SELECT * FROM {}{} LIMIT {}",
+                target.table_ref,
+                target.predicate.map(|p| format!(" WHERE {}", p)).unwrap_or_default(),
+                sample_rows
+            );
+            fetch_sample(tx, &select, sample_rows, result).await?;
+            result.sample_source = Some("pre_select".to_string());
+        }
+        result.rows_affected = (&mut **tx).execute(text).await.map_err(|e| e.to_string())?.rows_affected();
+        return Ok(());
+    }
+
+    let tokens = statement::tokenize(db_type, text);
+    let returning = if statement::find_top_level_keyword(text, &tokens, 0, "RETURNING").is_some() {
+        text.to_string()
+    } else {
+        format!("{} RETURNING *", text)
+    };
+    result.rows_affected = fetch_sample(tx, &returning, sample_rows, result).await?;
+    result.sample_source = Some("returning".to_string());
+    Ok(())
+}
+
+/// Keeps the first `limit` rows and returns how many there were in total.
+async fn fetch_sample(
+    tx: &mut sqlx::Transaction<'static, sqlx::Any>,
+    sql: &str,
+    limit: usize,
+    result: &mut DryRunStatement,
+) -> Result<u64, String> {
+    let mut rows = sqlx::query(sql).fetch(&mut **tx);
+    let mut count = 0u64;
+    while let Some(row) = rows.try_next().await.map_err(|e| e.to_string())? {
+        if result.sample_columns.is_empty() {
+            result.sample_columns = database::row_columns(&row).0;
+        }
+        if result.sample_rows.len() < limit {
+            result.sample_rows.push(database::row_values(&row));
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Target table of a single-table INSERT, REPLACE, UPDATE or DELETE.
+/// Statements whose effects escape the transaction regardless of engine:
+/// procedures and `DO` can commit or call anything, and `LOAD DATA` and
+/// `INTO OUTFILE`/`DUMPFILE` read or write files on the server.
+fn mysql_irreversible(db_type: &str, sql: &str, verb: &str) -> Option<&'static str> {
+    match verb {
+        "CALL" => return Some("a procedure call"),
+        "DO" => return Some("DO"),
+        "LOAD" => return Some("LOAD DATA"),
+        _ => {}
+    }
+    let tokens = statement::tokenize(db_type, sql);
+    tokens
+        .windows(2)
+        .any(|pair| pair[0].is_keyword(sql, "INTO") && (pair[1].is_keyword(sql, "OUTFILE") || pair[1].is_keyword(sql, "DUMPFILE")))
+        .then_some("writing a file with INTO OUTFILE or DUMPFILE")
+}
+
+fn written_table(db_type: &str, sql: &str) -> Option<String> {
+    if let Some(target) = undo::parse_dml_target(db_type, sql) {
+        return Some(target.table);
+    }
+    let tokens = statement::tokenize(db_type, sql);
+    let first = tokens.first()?;
+    if !first.is_keyword(sql, "INSERT") && !first.is_keyword(sql, "REPLACE") {
+        return None;
+    }
+    let name = tokens.iter().skip(1).find(|t| {
+        !["LOW_PRIORITY", "DELAYED", "HIGH_PRIORITY", "IGNORE", "INTO"].iter().any(|k| t.is_keyword(sql, k))
+    })?;
+    if !matches!(name.kind, TokenKind::Word | TokenKind::QuotedIdentifier) {
+        return None;
+    }
+    let mut table = name.text(sql).trim_matches('`').to_string();
+    let index = tokens.iter().position(|t| t == name)?;
+    if tokens.get(index + 1).is_some_and(|t| t.is_symbol(sql, ".")) {
+        let part = tokens.get(index + 2)?;
+        table = format!("{}.{}", table, part.text(sql).trim_matches('`'));
+    }
+    Some(table)
+}
+
+/// Refuses tables whose engine ignores rollbacks, such as MyISAM.
+async fn check_transactional(conn: &DatabaseConnection, table: &str) -> Result<(), String> {
+    let (schema, name) = match table.split_once('.') {
+        Some((schema, name)) => (dialect::quote_string("mysql", schema), name),
+        None => ("DATABASE()".to_string(), table),
+    };
+    let sql = format!(
+        "SELECT ENGINE FROM information_schema.TABLES WHERE TABLE_SCHEMA = {} AND TABLE_NAME = {}",
+        schema,
+        dialect::quote_string("mysql", name)
+    );
+    let engines = conn.execute_query(&sql).await.map_err(|e| e.to_string())?;
+    let engine = engines.rows.first()
+        .and_then(|row| row.first())
+        .and_then(|value| value.as_str())
+        .map(str::to_string);
+    match engine {
+        Some(engine) if !TRANSACTIONAL_ENGINES.iter().any(|e| e.eq_ignore_ascii_case(&engine)) => Err(format!(
+            "Table {} uses the {} engine, which does not roll back; a dry run would change it for real",
+            table, engine
+        )),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,862 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use futures_util::stream::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use crate::columnar::{ColumnarWriter, ParquetOptions};
+use crate::database::{self, DatabaseConnection};
+use crate::dialect;
+use crate::mongodb::{self as mongo, MongoConnection};
+
+const BATCH_ROWS: usize = 1000;
+const PROGRESS_EVERY_ROWS: u64 = 10_000;
+const XLSX_MAX_ROWS: u32 = 1_048_576;
+const XLSX_MAX_STRING: usize = 32_767;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Ndjson,
+    Sql,
+    Xlsx,
+    Markdown,
+    Html,
+    Parquet,
+    Arrow,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CsvOptions {
+    /// Single character, or `"\t"` / `"tab"` for TSV. Defaults to `,`.
+    pub delimiter: Option<String>,
+    /// `minimal` (default), `all`, `non_numeric` or `never`.
+    pub quote_style: Option<String>,
+    /// Any WHATWG encoding label, plus `utf-8-bom`, `utf-16le` and `utf-16be`.
+    pub encoding: Option<String>,
+    pub include_header: Option<bool>,
+    pub null_value: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SqlOptions {
+    /// Table named in the INSERT statements; defaults to the exported table.
+    pub table_name: Option<String>,
+    /// Rows per multi-row INSERT statement.
+    pub batch_size: Option<usize>,
+    /// Dialect used for quoting; defaults to the source connection's type.
+    pub dialect: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportRequest {
+    pub export_id: String,
+    pub connection_id: String,
+    /// Query to export. When absent, `table` is scanned in full.
+    pub query: Option<String>,
+    pub table: Option<String>,
+    pub path: String,
+    pub format: ExportFormat,
+    #[serde(default)]
+    pub csv: CsvOptions,
+    #[serde(default)]
+    pub sql: SqlOptions,
+    #[serde(default)]
+    pub parquet: ParquetOptions,
+    /// Typed confirmation for statements the safety policy asks to confirm.
+    pub confirmation: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportProgress {
+    pub export_id: String,
+    pub rows_written: u64,
+    pub finished: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportSummary {
+    pub export_id: String,
+    pub path: String,
+    pub rows_written: u64,
+    pub elapsed_ms: u64,
+    pub cancelled: bool,
+}
+
+/// Output side of an export. Writers run on a blocking thread and receive the
+/// header once, then rows in result order.
+pub trait RowWriter {
+    fn write_header(&mut self, columns: &[String], types: &[String]) -> Result<(), Box<dyn std::error::Error>>;
+    fn write_row(&mut self, row: &[serde_json::Value]) -> Result<(), Box<dyn std::error::Error>>;
+    fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+pub enum ExportMessage {
+    Header(Vec<String>, Vec<String>),
+    Rows(Vec<Vec<serde_json::Value>>),
+}
+
+/// Settings a writer needs beyond the format itself.
+pub struct WriterSettings {
+    pub csv: CsvOptions,
+    pub sql: SqlOptions,
+    pub parquet: ParquetOptions,
+    pub default_table: String,
+    pub default_dialect: String,
+}
+
+pub fn create_writer(
+    format: ExportFormat,
+    path: &Path,
+    settings: &WriterSettings,
+) -> Result<Box<dyn RowWriter>, Box<dyn std::error::Error>> {
+    match format {
+        ExportFormat::Xlsx => return Ok(Box::new(XlsxWriter::new(path))),
+        ExportFormat::Parquet => return Ok(Box::new(ColumnarWriter::parquet(path, settings.parquet.clone()))),
+        ExportFormat::Arrow => return Ok(Box::new(ColumnarWriter::arrow(path))),
+        _ => {}
+    }
+
+    let file = BufWriter::new(File::create(path)?);
+    let writer: Box<dyn RowWriter> = match format {
+        ExportFormat::Csv => Box::new(CsvWriter::new(file, &settings.csv)?),
+        ExportFormat::Json => Box::new(JsonWriter::new(file, false)),
+        ExportFormat::Ndjson => Box::new(JsonWriter::new(file, true)),
+        ExportFormat::Sql => Box::new(SqlInsertWriter::new(
+            file,
+            settings.sql.table_name.clone().unwrap_or_else(|| settings.default_table.clone()),
+            settings.sql.dialect.clone().unwrap_or_else(|| settings.default_dialect.clone()),
+            settings.sql.batch_size.unwrap_or(100).max(1),
+        )),
+        ExportFormat::Markdown => Box::new(MarkdownWriter::new(file)),
+        ExportFormat::Html => Box::new(HtmlWriter::new(file)),
+        ExportFormat::Xlsx | ExportFormat::Parquet | ExportFormat::Arrow => unreachable!(),
+    };
+    Ok(writer)
+}
+
+/// Spawns the blocking writer thread that drains `receiver` into a `RowWriter`.
+pub fn spawn_writer(
+    format: ExportFormat,
+    path: std::path::PathBuf,
+    settings: WriterSettings,
+    mut receiver: tokio::sync::mpsc::Receiver<ExportMessage>,
+) -> tokio::task::JoinHandle<Result<(), String>> {
+    tokio::task::spawn_blocking(move || {
+        let mut writer = create_writer(format, &path, &settings).map_err(|e| e.to_string())?;
+        while let Some(message) = receiver.blocking_recv() {
+            match message {
+                ExportMessage::Header(columns, types) => {
+                    writer.write_header(&columns, &types).map_err(|e| e.to_string())?
+                }
+                ExportMessage::Rows(rows) => {
+                    for row in &rows {
+                        writer.write_row(row).map_err(|e| e.to_string())?;
+                    }
+                }
+            }
+        }
+        writer.finish().map_err(|e| e.to_string())
+    })
+}
+
+/// Connection an export reads from. SQL sources run `query` or scan `table`;
+/// Mongo sources accept `collection.find({...})` or a bare collection name.
+pub enum ExportSource {
+    Sql(DatabaseConnection),
+    Mongo(MongoConnection),
+}
+
+impl ExportSource {
+    fn dialect(&self) -> &str {
+        match self {
+            ExportSource::Sql(conn) => conn.db_type(),
+            ExportSource::Mongo(_) => "mongodb",
+        }
+    }
+}
+
+/// Re-runs the export query with a streaming cursor and writes it to
+/// `request.path`. Rows are handed to a blocking writer thread in batches, so
+/// memory use does not depend on the result size. A cancelled or failed
+/// export removes its partial output file.
+pub async fn run_export<F>(
+    source: &ExportSource,
+    request: &ExportRequest,
+    cancel: Arc<AtomicBool>,
+    mut on_progress: F,
+) -> Result<ExportSummary, Box<dyn std::error::Error>>
+where
+    F: FnMut(u64),
+{
+    let start = std::time::Instant::now();
+    let statement = export_statement(source.dialect(), request)?;
+    let settings = WriterSettings {
+        csv: request.csv.clone(),
+        sql: request.sql.clone(),
+        parquet: request.parquet.clone(),
+        default_table: request.table.clone().unwrap_or_else(|| "export".to_string()),
+        default_dialect: source.dialect().to_string(),
+    };
+    let path = std::path::PathBuf::from(&request.path);
+
+    let (sender, receiver) = tokio::sync::mpsc::channel(8);
+    let writer = spawn_writer(request.format, path.clone(), settings, receiver);
+
+    // Errors are boxed trait objects and not `Send`, so flatten them to strings
+    // before awaiting the writer.
+    let streamed = match source {
+        ExportSource::Sql(conn) => stream_rows(conn, &statement, &sender, &cancel, &mut on_progress).await,
+        ExportSource::Mongo(conn) => stream_documents(conn, &statement, &sender, &cancel, &mut on_progress).await,
+    }
+    .map_err(|e| e.to_string());
+    drop(sender);
+    let written = writer.await?;
+
+    // A writer failure also breaks the channel, so report it ahead of the
+    // stream's resulting "writer stopped" error.
+    let rows_written = match (streamed, written) {
+        (Ok(rows), Ok(())) => rows,
+        (_, Err(e)) => {
+            let _ = std::fs::remove_file(&path);
+            return Err(Box::new(std::io::Error::other(e)));
+        }
+        (Err(e), Ok(())) => {
+            let _ = std::fs::remove_file(&path);
+            return Err(Box::new(std::io::Error::other(e)));
+        }
+    };
+
+    let cancelled = cancel.load(Ordering::Relaxed);
+    if cancelled {
+        let _ = std::fs::remove_file(&path);
+    }
+
+    Ok(ExportSummary {
+        export_id: request.export_id.clone(),
+        path: request.path.clone(),
+        rows_written,
+        elapsed_ms: start.elapsed().as_millis() as u64,
+        cancelled,
+    })
+}
+
+fn export_statement(db_type: &str, request: &ExportRequest) -> Result<String, Box<dyn std::error::Error>> {
+    match (&request.query, &request.table) {
+        (Some(query), _) => Ok(query.trim().trim_end_matches(';').to_string()),
+        (None, Some(table)) if db_type == "mongodb" => Ok(table.clone()),
+        (None, Some(table)) => Ok(format!("SELECT * FROM {}", dialect::quote_table(db_type, table))),
+        (None, None) => Err(Box::new(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Either a query or a table is required for export"
+        ))),
+    }
+}
+
+async fn stream_rows<F>(
+    conn: &DatabaseConnection,
+    sql: &str,
+    sender: &tokio::sync::mpsc::Sender<ExportMessage>,
+    cancel: &AtomicBool,
+    on_progress: &mut F,
+) -> Result<u64, Box<dyn std::error::Error>>
+where
+    F: FnMut(u64),
+{
+    let closed = || std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Export writer stopped");
+
+    let mut stream = conn.stream_query(sql);
+    let mut batch = Vec::with_capacity(BATCH_ROWS);
+    let mut header_sent = false;
+    let mut rows_written: u64 = 0;
+
+    while let Some(row) = stream.try_next().await? {
+        if cancel.load(Ordering::Relaxed) {
+            return Ok(rows_written);
+        }
+
+        if !header_sent {
+            let (columns, types) = database::row_columns(&row);
+            sender.send(ExportMessage::Header(columns, types)).await.map_err(|_| closed())?;
+            header_sent = true;
+        }
+
+        batch.push(database::row_values(&row));
+        rows_written += 1;
+
+        if batch.len() >= BATCH_ROWS {
+            let rows = std::mem::replace(&mut batch, Vec::with_capacity(BATCH_ROWS));
+            sender.send(ExportMessage::Rows(rows)).await.map_err(|_| closed())?;
+        }
+        if rows_written.is_multiple_of(PROGRESS_EVERY_ROWS) {
+            on_progress(rows_written);
+        }
+    }
+    drop(stream);
+
+    if !header_sent {
+        let columns = conn.describe_columns(sql).await?;
+        let types = vec![String::new(); columns.len()];
+        sender.send(ExportMessage::Header(columns, types)).await.map_err(|_| closed())?;
+    }
+    if !batch.is_empty() {
+        sender.send(ExportMessage::Rows(batch)).await.map_err(|_| closed())?;
+    }
+    on_progress(rows_written);
+
+    Ok(rows_written)
+}
+
+/// Mongo documents have no fixed shape, so the first batch of documents is
+/// buffered to derive the column set (union of their fields, `_id` first).
+/// Fields that only appear after that batch are not exported.
+async fn stream_documents<F>(
+    conn: &MongoConnection,
+    statement: &str,
+    sender: &tokio::sync::mpsc::Sender<ExportMessage>,
+    cancel: &AtomicBool,
+    on_progress: &mut F,
+) -> Result<u64, Box<dyn std::error::Error>>
+where
+    F: FnMut(u64),
+{
+    let closed = || std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Export writer stopped");
+
+    let mut cursor = conn.find_cursor(statement).await?;
+    let mut columns: Vec<String> = Vec::new();
+    let mut types: Vec<String> = Vec::new();
+    let mut sample: Vec<serde_json::Map<String, serde_json::Value>> = Vec::new();
+    let mut batch = Vec::with_capacity(BATCH_ROWS);
+    let mut header_sent = false;
+    let mut rows_written: u64 = 0;
+
+    let shape = |document: &serde_json::Map<String, serde_json::Value>, columns: &[String]| -> Vec<serde_json::Value> {
+        columns.iter().map(|c| document.get(c).cloned().unwrap_or(serde_json::Value::Null)).collect()
+    };
+
+    loop {
+        let next = cursor.try_next().await?;
+        if cancel.load(Ordering::Relaxed) {
+            return Ok(rows_written);
+        }
+
+        if !header_sent && (next.is_none() || sample.len() >= BATCH_ROWS) {
+            for document in &sample {
+                for (key, value) in document {
+                    if !columns.contains(key) && !value.is_null() {
+                        columns.push(key.clone());
+                        types.push(mongo::mongo_type_to_string(value));
+                    }
+                }
+            }
+            if let Some(id_index) = columns.iter().position(|c| c == "_id") {
+                let id = columns.remove(id_index);
+                let id_type = types.remove(id_index);
+                columns.insert(0, id);
+                types.insert(0, id_type);
+            }
+            sender.send(ExportMessage::Header(columns.clone(), types.clone())).await.map_err(|_| closed())?;
+            header_sent = true;
+
+            let rows: Vec<Vec<serde_json::Value>> = sample.drain(..).map(|d| shape(&d, &columns)).collect();
+            if !rows.is_empty() {
+                sender.send(ExportMessage::Rows(rows)).await.map_err(|_| closed())?;
+            }
+        }
+
+        let Some(document) = next else { break };
+        let serde_json::Value::Object(document) = mongo::convert_bson_to_json(&document) else {
+            continue;
+        };
+        rows_written += 1;
+
+        if header_sent {
+            batch.push(shape(&document, &columns));
+            if batch.len() >= BATCH_ROWS {
+                let rows = std::mem::replace(&mut batch, Vec::with_capacity(BATCH_ROWS));
+                sender.send(ExportMessage::Rows(rows)).await.map_err(|_| closed())?;
+            }
+        } else {
+            sample.push(document);
+        }
+        if rows_written.is_multiple_of(PROGRESS_EVERY_ROWS) {
+            on_progress(rows_written);
+        }
+    }
+
+    if !batch.is_empty() {
+        sender.send(ExportMessage::Rows(batch)).await.map_err(|_| closed())?;
+    }
+    on_progress(rows_written);
+
+    Ok(rows_written)
+}
+
+fn text_value(value: &serde_json::Value, null_value: &str) -> String {
+    match value {
+        serde_json::Value::Null => null_value.to_string(),
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Re-encodes the UTF-8 produced by the writers into the requested output
+/// encoding. Characters the target encoding cannot represent are written as
+/// numeric character references, which is encoding_rs' standard behaviour.
+enum TextEncoder<W: Write> {
+    Utf8(W),
+    Utf16 { inner: W, big_endian: bool, pending: Vec<u8> },
+    Legacy { inner: W, encoder: encoding_rs::Encoder, pending: Vec<u8> },
+}
+
+impl<W: Write> TextEncoder<W> {
+    fn new(mut inner: W, label: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        let label = label.unwrap_or("utf-8").trim().to_ascii_lowercase();
+        match label.as_str() {
+            "utf-8" | "utf8" => Ok(TextEncoder::Utf8(inner)),
+            "utf-8-bom" | "utf8-bom" => {
+                inner.write_all(&[0xEF, 0xBB, 0xBF])?;
+                Ok(TextEncoder::Utf8(inner))
+            }
+            "utf-16" | "utf-16le" => {
+                inner.write_all(&[0xFF, 0xFE])?;
+                Ok(TextEncoder::Utf16 { inner, big_endian: false, pending: Vec::new() })
+            }
+            "utf-16be" => {
+                inner.write_all(&[0xFE, 0xFF])?;
+                Ok(TextEncoder::Utf16 { inner, big_endian: true, pending: Vec::new() })
+            }
+            _ => {
+                let encoding = encoding_rs::Encoding::for_label(label.as_bytes()).ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Unknown encoding: {}", label))
+                })?;
+                Ok(TextEncoder::Legacy { inner, encoder: encoding.new_encoder(), pending: Vec::new() })
+            }
+        }
+    }
+
+    /// Splits off the longest complete UTF-8 prefix of `pending`, keeping a
+    /// multi-byte sequence that straddles two writes for the next call.
+    fn take_complete(pending: &mut Vec<u8>) -> std::io::Result<String> {
+        let valid = match std::str::from_utf8(pending) {
+            Ok(_) => pending.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid UTF-8 in export output")),
+        };
+        let rest = pending.split_off(valid);
+        let complete = std::mem::replace(pending, rest);
+        Ok(String::from_utf8(complete).unwrap_or_default())
+    }
+
+    fn encode_legacy(encoder: &mut encoding_rs::Encoder, text: &str, last: bool) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(text.len() + 16);
+        let mut remaining = text;
+        loop {
+            let capacity = encoder.max_buffer_length_from_utf8_if_no_unmappables(remaining.len()).unwrap_or(remaining.len() * 4) + 16;
+            bytes.reserve(capacity);
+            let (result, read, _) = encoder.encode_from_utf8_to_vec(remaining, &mut bytes, last);
+            remaining = &remaining[read..];
+            if result == encoding_rs::CoderResult::InputEmpty {
+                break;
+            }
+        }
+        bytes
+    }
+
+    /// Ends the output: stateful encodings such as ISO-2022-JP switch back to
+    /// ASCII here, and a UTF-8 sequence cut off by the last write is an error.
+    fn finish(&mut self) -> std::io::Result<()> {
+        match self {
+            TextEncoder::Utf8(_) => {}
+            TextEncoder::Utf16 { pending, .. } | TextEncoder::Legacy { pending, .. } if !pending.is_empty() => {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid UTF-8 in export output"));
+            }
+            TextEncoder::Utf16 { .. } => {}
+            TextEncoder::Legacy { inner, encoder, .. } => inner.write_all(&Self::encode_legacy(encoder, "", true))?,
+        }
+        self.flush()
+    }
+}
+
+impl<W: Write> Write for TextEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            TextEncoder::Utf8(inner) => inner.write(buf),
+            TextEncoder::Utf16 { inner, big_endian, pending } => {
+                pending.extend_from_slice(buf);
+                let text = Self::take_complete(pending)?;
+                let mut bytes = Vec::with_capacity(text.len() * 2);
+                for unit in text.encode_utf16() {
+                    let pair = if *big_endian { unit.to_be_bytes() } else { unit.to_le_bytes() };
+                    bytes.extend_from_slice(&pair);
+                }
+                inner.write_all(&bytes)?;
+                Ok(buf.len())
+            }
+            TextEncoder::Legacy { inner, encoder, pending } => {
+                pending.extend_from_slice(buf);
+                let text = Self::take_complete(pending)?;
+                inner.write_all(&Self::encode_legacy(encoder, &text, false))?;
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            TextEncoder::Utf8(inner) => inner.flush(),
+            TextEncoder::Utf16 { inner, .. } => inner.flush(),
+            TextEncoder::Legacy { inner, .. } => inner.flush(),
+        }
+    }
+}
+
+struct CsvWriter {
+    /// Taken by `finish` to end the encoder's output.
+    writer: Option<csv::Writer<TextEncoder<BufWriter<File>>>>,
+    include_header: bool,
+    null_value: String,
+}
+
+impl CsvWriter {
+    fn new(file: BufWriter<File>, options: &CsvOptions) -> Result<Self, Box<dyn std::error::Error>> {
+        let delimiter = match options.delimiter.as_deref() {
+            None | Some("") => b',',
+            Some("\\t") | Some("\t") | Some("tab") => b'\t',
+            Some(d) if d.len() == 1 => d.as_bytes()[0],
+            Some(d) => return Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("CSV delimiter must be a single ASCII character, got {:?}", d)
+            ))),
+        };
+        let quote_style = match options.quote_style.as_deref().unwrap_or("minimal") {
+            "minimal" => csv::QuoteStyle::Necessary,
+            "all" => csv::QuoteStyle::Always,
+            "non_numeric" => csv::QuoteStyle::NonNumeric,
+            "never" => csv::QuoteStyle::Never,
+            other => return Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Unknown CSV quote style: {}", other)
+            ))),
+        };
+
+        let output = TextEncoder::new(file, options.encoding.as_deref())?;
+        let writer = csv::WriterBuilder::new()
+            .delimiter(delimiter)
+            .quote_style(quote_style)
+            .from_writer(output);
+
+        Ok(Self {
+            writer: Some(writer),
+            include_header: options.include_header.unwrap_or(true),
+            null_value: options.null_value.clone().unwrap_or_default(),
+        })
+    }
+}
+
+impl RowWriter for CsvWriter {
+    fn write_header(&mut self, columns: &[String], _types: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        if self.include_header {
+            let writer = self.writer.as_mut().ok_or("CSV export is already finished")?;
+            writer.write_record(columns)?;
+        }
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[serde_json::Value]) -> Result<(), Box<dyn std::error::Error>> {
+        let writer = self.writer.as_mut().ok_or("CSV export is already finished")?;
+        writer.write_record(row.iter().map(|v| text_value(v, &self.null_value)))?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(writer) = self.writer.take() {
+            writer.into_inner().map_err(|e| e.into_error())?.finish()?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes JSON objects with keys in column order (a `serde_json::Map` would
+/// sort them). `lines` selects NDJSON instead of a single array.
+struct JsonWriter {
+    out: BufWriter<File>,
+    lines: bool,
+    columns: Vec<String>,
+    rows_written: u64,
+}
+
+impl JsonWriter {
+    fn new(out: BufWriter<File>, lines: bool) -> Self {
+        Self { out, lines, columns: Vec::new(), rows_written: 0 }
+    }
+}
+
+impl RowWriter for JsonWriter {
+    fn write_header(&mut self, columns: &[String], _types: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        self.columns = columns.iter().map(serde_json::to_string).collect::<Result<_, _>>()?;
+        if !self.lines {
+            self.out.write_all(b"[")?;
+        }
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[serde_json::Value]) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.lines {
+            self.out.write_all(if self.rows_written == 0 { b"\n  " } else { b",\n  " })?;
+        }
+        self.out.write_all(b"{")?;
+        for (i, (column, value)) in self.columns.iter().zip(row).enumerate() {
+            if i > 0 {
+                self.out.write_all(b",")?;
+            }
+            self.out.write_all(column.as_bytes())?;
+            self.out.write_all(b":")?;
+            serde_json::to_writer(&mut self.out, value)?;
+        }
+        self.out.write_all(b"}")?;
+        if self.lines {
+            self.out.write_all(b"\n")?;
+        }
+        self.rows_written += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.lines {
+            self.out.write_all(if self.rows_written == 0 { b"]\n" } else { b"\n]\n" })?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+struct SqlInsertWriter {
+    out: BufWriter<File>,
+    table: String,
+    dialect: String,
+    batch_size: usize,
+    column_list: String,
+    pending: Vec<String>,
+}
+
+impl SqlInsertWriter {
+    fn new(out: BufWriter<File>, table: String, dialect: String, batch_size: usize) -> Self {
+        Self { out, table, dialect, batch_size, column_list: String::new(), pending: Vec::new() }
+    }
+
+    fn flush_batch(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        writeln!(
+            self.out,
+            "INSERT INTO {} ({}) VALUES\n  {};",
+            dialect::quote_table(&self.dialect, &self.table),
+            self.column_list,
+            self.pending.join(",\n  ")
+        )?;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+impl RowWriter for SqlInsertWriter {
+    fn write_header(&mut self, columns: &[String], _types: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        self.column_list = columns
+            .iter()
+            .map(|c| dialect::quote_identifier(&self.dialect, c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[serde_json::Value]) -> Result<(), Box<dyn std::error::Error>> {
+        let values: Vec<String> = row.iter().map(|v| dialect::literal(&self.dialect, v)).collect();
+        self.pending.push(format!("({})", values.join(", ")));
+        if self.pending.len() >= self.batch_size {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.flush_batch()?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Real .xlsx output using rust_xlsxwriter's constant-memory worksheets.
+/// Results longer than Excel's row limit continue on additional sheets.
+struct XlsxWriter {
+    path: std::path::PathBuf,
+    workbook: rust_xlsxwriter::Workbook,
+    header_format: rust_xlsxwriter::Format,
+    columns: Vec<String>,
+    sheet_index: usize,
+    next_row: u32,
+}
+
+impl XlsxWriter {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            workbook: rust_xlsxwriter::Workbook::new(),
+            header_format: rust_xlsxwriter::Format::new().set_bold(),
+            columns: Vec::new(),
+            sheet_index: 0,
+            next_row: 0,
+        }
+    }
+
+    fn start_sheet(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let sheet_number = self.workbook.worksheets().len() + 1;
+        let worksheet = self.workbook.add_worksheet_with_constant_memory();
+        worksheet.set_name(format!("Export {}", sheet_number))?;
+        for (col, name) in self.columns.iter().enumerate() {
+            worksheet.write_string_with_format(0, col as u16, name, &self.header_format)?;
+        }
+        self.sheet_index = sheet_number - 1;
+        self.next_row = 1;
+        Ok(())
+    }
+}
+
+impl RowWriter for XlsxWriter {
+    fn write_header(&mut self, columns: &[String], _types: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        self.columns = columns.to_vec();
+        self.start_sheet()
+    }
+
+    fn write_row(&mut self, row: &[serde_json::Value]) -> Result<(), Box<dyn std::error::Error>> {
+        if self.next_row >= XLSX_MAX_ROWS {
+            self.start_sheet()?;
+        }
+        let worksheet = self.workbook.worksheet_from_index(self.sheet_index)?;
+        for (col, value) in row.iter().enumerate() {
+            let col = col as u16;
+            match value {
+                serde_json::Value::Null => {}
+                serde_json::Value::Bool(b) => {
+                    worksheet.write_boolean(self.next_row, col, *b)?;
+                }
+                serde_json::Value::Number(n) => match n.as_f64() {
+                    Some(f) => {
+                        worksheet.write_number(self.next_row, col, f)?;
+                    }
+                    None => {
+                        worksheet.write_string(self.next_row, col, n.to_string())?;
+                    }
+                },
+                other => {
+                    let mut text = text_value(other, "");
+                    if text.len() > XLSX_MAX_STRING {
+                        let mut end = XLSX_MAX_STRING;
+                        while !text.is_char_boundary(end) {
+                            end -= 1;
+                        }
+                        text.truncate(end);
+                    }
+                    worksheet.write_string(self.next_row, col, text)?;
+                }
+            }
+        }
+        self.next_row += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.workbook.worksheets().is_empty() {
+            self.start_sheet()?;
+        }
+        self.workbook.save(&self.path)?;
+        Ok(())
+    }
+}
+
+struct MarkdownWriter {
+    out: BufWriter<File>,
+}
+
+impl MarkdownWriter {
+    fn new(out: BufWriter<File>) -> Self {
+        Self { out }
+    }
+
+    fn cell(value: &str) -> String {
+        value.replace('\\', "\\\\").replace('|', "\\|").replace("\r\n", "<br>").replace('\n', "<br>")
+    }
+}
+
+impl RowWriter for MarkdownWriter {
+    fn write_header(&mut self, columns: &[String], _types: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let header: Vec<String> = columns.iter().map(|c| Self::cell(c)).collect();
+        let rule: Vec<&str> = columns.iter().map(|_| "---").collect();
+        writeln!(self.out, "| {} |", header.join(" | "))?;
+        writeln!(self.out, "| {} |", rule.join(" | "))?;
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[serde_json::Value]) -> Result<(), Box<dyn std::error::Error>> {
+        let cells: Vec<String> = row.iter().map(|v| Self::cell(&text_value(v, "NULL"))).collect();
+        writeln!(self.out, "| {} |", cells.join(" | "))?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+struct HtmlWriter {
+    out: BufWriter<File>,
+}
+
+impl HtmlWriter {
+    fn new(out: BufWriter<File>) -> Self {
+        Self { out }
+    }
+
+    fn escape(value: &str) -> String {
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+}
+
+impl RowWriter for HtmlWriter {
+    fn write_header(&mut self, columns: &[String], _types: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Export</title></head>\n<body>\n<table>")?;
+        write!(self.out, "<thead><tr>")?;
+        for column in columns {
+            write!(self.out, "<th>{}</th>", Self::escape(column))?;
+        }
+        writeln!(self.out, "</tr></thead>\n<tbody>")?;
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[serde_json::Value]) -> Result<(), Box<dyn std::error::Error>> {
+        write!(self.out, "<tr>")?;
+        for value in row {
+            match value {
+                serde_json::Value::Null => write!(self.out, "<td class=\"null\">NULL</td>")?,
+                other => write!(self.out, "<td>{}</td>", Self::escape(&text_value(other, "")))?,
+            }
+        }
+        writeln!(self.out, "</tr>")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        writeln!(self.out, "</tbody>\n</table>\n</body>\n</html>")?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+//! Connection health: the state a monitored connection is in and when to
+//! check it next. The monitor task itself lives with the connection map in
+//! `main.rs`; this module only decides transitions and delays.
+
+use std::time::Duration;
+use serde::Serialize;
+use crate::types::HealthCheckConfig;
+
+/// How soon a degraded connection is checked again.
+const DEGRADED_RECHECK: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthState {
+    Connected,
+    /// A check failed, but not enough of them to reconnect.
+    Degraded,
+    /// Checks keep failing; reconnecting with backoff.
+    Lost,
+    /// A lost connection was replaced by a new one.
+    Reconnected,
+}
+
+/// Emitted as `connection-health` whenever the state changes.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthEvent {
+    pub connection_id: String,
+    pub state: HealthState,
+    /// Round trip of the check that caused the change.
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+    /// Reconnection attempts made so far.
+    pub attempts: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthSettings {
+    pub interval: Duration,
+    pub timeout: Duration,
+    pub failures_before_lost: u32,
+    pub max_backoff: Duration,
+}
+
+impl HealthSettings {
+    /// `None` when checks are turned off for the connection.
+    pub fn from_config(config: Option<&HealthCheckConfig>) -> Option<Self> {
+        let config = config.cloned().unwrap_or_default();
+        if !config.enabled.unwrap_or(true) {
+            return None;
+        }
+        Some(Self {
+            interval: Duration::from_secs(config.interval_secs.unwrap_or(30).max(1)),
+            timeout: Duration::from_secs(config.timeout_secs.unwrap_or(5).max(1)),
+            failures_before_lost: config.failures_before_lost.unwrap_or(3).max(1),
+            max_backoff: Duration::from_secs(config.max_backoff_secs.unwrap_or(60).max(1)),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct HealthTracker {
+    settings: HealthSettings,
+    state: HealthState,
+    failures: u32,
+    attempts: u32,
+}
+
+impl HealthTracker {
+    pub fn new(settings: HealthSettings) -> Self {
+        Self { settings, state: HealthState::Connected, failures: 0, attempts: 0 }
+    }
+
+    pub fn settings(&self) -> &HealthSettings {
+        &self.settings
+    }
+
+    pub fn state(&self) -> HealthState {
+        self.state
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Records a check on the live connection, or a reconnection attempt
+    /// once lost. Returns the new state when it changed.
+    pub fn record(&mut self, ok: bool) -> Option<HealthState> {
+        let next = match (self.state, ok) {
+            (HealthState::Lost, true) => HealthState::Reconnected,
+            (HealthState::Lost, false) => {
+                self.attempts += 1;
+                HealthState::Lost
+            }
+            (HealthState::Degraded, true) => HealthState::Connected,
+            (state, true) => state,
+            (_, false) => {
+                self.failures += 1;
+                if self.failures >= self.settings.failures_before_lost {
+                    HealthState::Lost
+                } else {
+                    HealthState::Degraded
+                }
+            }
+        };
+        if ok {
+            self.failures = 0;
+        }
+        if next == self.state {
+            return None;
+        }
+        if next == HealthState::Lost {
+            self.attempts = 0;
+        }
+        self.state = next;
+        Some(next)
+    }
+
+    /// Wait before the next check or reconnection attempt. Reconnection
+    /// backs off exponentially from one second up to `max_backoff`.
+    pub fn next_delay(&self) -> Duration {
+        match self.state {
+            HealthState::Connected | HealthState::Reconnected => self.settings.interval,
+            HealthState::Degraded => self.settings.interval.min(DEGRADED_RECHECK),
+            HealthState::Lost => Duration::from_secs(1u64 << self.attempts.min(16)).min(self.settings.max_backoff),
+        }
+    }
+}
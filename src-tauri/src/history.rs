@@ -0,0 +1,234 @@
+//! Query history kept in a local SQLite database with an FTS5 index over the
+//! statement text, so it outlives the webview and is shared by every window.
+
+use std::path::Path;
+use std::sync::Mutex;
+use rusqlite::{params_from_iter, Connection};
+use serde::{Deserialize, Serialize};
+
+/// Bumped with `PRAGMA user_version` when the schema changes.
+const SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS history (
+        id INTEGER PRIMARY KEY,
+        connection_id TEXT NOT NULL,
+        connection_name TEXT,
+        db_type TEXT,
+        source TEXT NOT NULL,
+        sql TEXT NOT NULL,
+        parameters TEXT,
+        started_at TEXT NOT NULL,
+        duration_ms INTEGER NOT NULL,
+        row_count INTEGER,
+        affected_rows INTEGER,
+        success INTEGER NOT NULL,
+        error TEXT,
+        tab_id TEXT
+    );
+    CREATE INDEX IF NOT EXISTS history_started ON history (started_at);
+    CREATE INDEX IF NOT EXISTS history_connection ON history (connection_id, started_at);
+    CREATE VIRTUAL TABLE IF NOT EXISTS history_fts USING fts5 (
+        sql, error, content = 'history', content_rowid = 'id'
+    );
+    CREATE TRIGGER IF NOT EXISTS history_fts_insert AFTER INSERT ON history BEGIN
+        INSERT INTO history_fts (rowid, sql, error) VALUES (new.id, new.sql, new.error);
+    END;
+    CREATE TRIGGER IF NOT EXISTS history_fts_delete AFTER DELETE ON history BEGIN
+        INSERT INTO history_fts (history_fts, rowid, sql, error) VALUES ('delete', old.id, old.sql, old.error);
+    END;
+";
+
+/// Timestamps are stored in one fixed UTC format so that string comparison
+/// orders them chronologically.
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3fZ";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub connection_id: String,
+    pub connection_name: Option<String>,
+    pub db_type: Option<String>,
+    /// `query` or `ddl`.
+    pub source: String,
+    pub sql: String,
+    /// Bound parameters, for executions that used them.
+    pub parameters: Option<Vec<serde_json::Value>>,
+    pub started_at: String,
+    pub duration_ms: u64,
+    pub row_count: Option<u64>,
+    pub affected_rows: Option<u64>,
+    pub success: bool,
+    pub error: Option<String>,
+    /// Editor tab the statement was run from, as reported by the client.
+    pub tab_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryStatus {
+    Success,
+    Error,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HistoryQuery {
+    /// Full-text terms matched against the SQL and error text; each term is
+    /// a prefix match and all must be present.
+    pub text: Option<String>,
+    pub connection_id: Option<String>,
+    /// RFC 3339, inclusive.
+    pub since: Option<String>,
+    /// RFC 3339, exclusive.
+    pub until: Option<String>,
+    pub status: Option<HistoryStatus>,
+    pub tab_id: Option<String>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+pub struct HistoryStore {
+    conn: Mutex<Connection>,
+}
+
+impl HistoryStore {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let conn = Connection::open(path)?;
+        // Several windows write through this process; WAL keeps readers
+        // from blocking the writer.
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.execute_batch(SCHEMA)?;
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version < SCHEMA_VERSION {
+            conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        }
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Connection>, Box<dyn std::error::Error>> {
+        self.conn.lock().map_err(|_| "History database lock poisoned".into())
+    }
+
+    /// Stores an entry; its `id` is ignored and the new one returned.
+    pub fn record(&self, entry: &HistoryEntry) -> Result<i64, Box<dyn std::error::Error>> {
+        let conn = self.lock()?;
+        let parameters = entry.parameters.as_ref().map(serde_json::to_string).transpose()?;
+        conn.execute(
+            "INSERT INTO history (connection_id, connection_name, db_type, source, sql, parameters, \
+             started_at, duration_ms, row_count, affected_rows, success, error, tab_id) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            rusqlite::params![
+                entry.connection_id,
+                entry.connection_name,
+                entry.db_type,
+                entry.source,
+                entry.sql,
+                parameters,
+                normalize_time(&entry.started_at)?,
+                entry.duration_ms as i64,
+                entry.row_count.map(|n| n as i64),
+                entry.affected_rows.map(|n| n as i64),
+                entry.success,
+                entry.error,
+                entry.tab_id,
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Matching entries, newest first.
+    pub fn search(&self, query: &HistoryQuery) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+        let mut sql = String::from(
+            "SELECT h.id, h.connection_id, h.connection_name, h.db_type, h.source, h.sql, h.parameters, \
+             h.started_at, h.duration_ms, h.row_count, h.affected_rows, h.success, h.error, h.tab_id \
+             FROM history h WHERE 1 = 1",
+        );
+        let mut params: Vec<rusqlite::types::Value> = Vec::new();
+
+        if let Some(text) = query.text.as_deref().and_then(match_expression) {
+            sql.push_str(" AND h.id IN (SELECT rowid FROM history_fts WHERE history_fts MATCH ?)");
+            params.push(text.into());
+        }
+        if let Some(connection_id) = &query.connection_id {
+            sql.push_str(" AND h.connection_id = ?");
+            params.push(connection_id.clone().into());
+        }
+        if let Some(since) = &query.since {
+            sql.push_str(" AND h.started_at >= ?");
+            params.push(normalize_time(since)?.into());
+        }
+        if let Some(until) = &query.until {
+            sql.push_str(" AND h.started_at < ?");
+            params.push(normalize_time(until)?.into());
+        }
+        if let Some(status) = query.status {
+            sql.push_str(" AND h.success = ?");
+            params.push(i64::from(status == HistoryStatus::Success).into());
+        }
+        if let Some(tab_id) = &query.tab_id {
+            sql.push_str(" AND h.tab_id = ?");
+            params.push(tab_id.clone().into());
+        }
+        sql.push_str(" ORDER BY h.started_at DESC, h.id DESC LIMIT ? OFFSET ?");
+        params.push((query.limit.unwrap_or(200) as i64).into());
+        params.push((query.offset.unwrap_or(0) as i64).into());
+
+        let conn = self.lock()?;
+        let mut statement = conn.prepare(&sql)?;
+        let rows = statement.query_map(params_from_iter(params), |row| {
+            let parameters: Option<String> = row.get(6)?;
+            Ok(HistoryEntry {
+                id: row.get(0)?,
+                connection_id: row.get(1)?,
+                connection_name: row.get(2)?,
+                db_type: row.get(3)?,
+                source: row.get(4)?,
+                sql: row.get(5)?,
+                parameters: parameters.and_then(|p| serde_json::from_str(&p).ok()),
+                started_at: row.get(7)?,
+                duration_ms: row.get::<_, i64>(8)? as u64,
+                row_count: row.get::<_, Option<i64>>(9)?.map(|n| n as u64),
+                affected_rows: row.get::<_, Option<i64>>(10)?.map(|n| n as u64),
+                success: row.get(11)?,
+                error: row.get(12)?,
+                tab_id: row.get(13)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Deletes entries older than `before`, or all of them; returns the count.
+    pub fn clear(&self, before: Option<&str>) -> Result<usize, Box<dyn std::error::Error>> {
+        let conn = self.lock()?;
+        Ok(match before {
+            Some(before) => conn.execute("DELETE FROM history WHERE started_at < ?1", [normalize_time(before)?])?,
+            None => conn.execute("DELETE FROM history", [])?,
+        })
+    }
+}
+
+pub fn now() -> String {
+    chrono::Utc::now().format(TIME_FORMAT).to_string()
+}
+
+fn normalize_time(value: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let time = chrono::DateTime::parse_from_rfc3339(value)
+        .map_err(|e| format!("Invalid timestamp {}: {}", value, e))?;
+    Ok(time.with_timezone(&chrono::Utc).format(TIME_FORMAT).to_string())
+}
+
+/// Turns free text into an FTS5 query. Words are split the way the FTS
+/// tokenizer splits them (so `users.id` finds `users` and `id`) and each
+/// becomes a quoted prefix match; operators typed by the user cannot produce
+/// a syntax error.
+fn match_expression(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("\"{}\"*", term))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
@@ -0,0 +1,480 @@
+//! Consequences of dropping or truncating a table: the objects that depend
+//! on it, the rows that would go, and whether the plain statement would be
+//! refused without `CASCADE`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::database::DatabaseConnection;
+use crate::dialect;
+use crate::statement::{self, TokenKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImpactAction {
+    Drop,
+    Truncate,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImpactRequest {
+    pub connection_id: String,
+    /// Possibly schema-qualified.
+    pub table: String,
+    pub action: ImpactAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependentObject {
+    /// `view`, `materialized_view`, `foreign_key`, `trigger`, `function`,
+    /// `procedure` or `sequence`.
+    pub kind: String,
+    pub name: String,
+    /// Table owning the object, for foreign keys the referencing table.
+    pub table: Option<String>,
+    pub detail: Option<String>,
+    /// Rows of the referencing table, for foreign keys.
+    pub row_count: Option<u64>,
+    pub row_count_estimated: bool,
+    /// Whether this dependency makes the plain statement fail.
+    pub blocking: bool,
+    /// Found by searching source text rather than tracked by the server, so
+    /// it may be a false positive.
+    pub heuristic: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImpactReport {
+    pub table: String,
+    pub action: ImpactAction,
+    pub row_count: Option<u64>,
+    /// Server statistics used instead of `COUNT(*)` on large tables.
+    pub row_count_estimated: bool,
+    pub dependents: Vec<DependentObject>,
+    pub requires_cascade: bool,
+    /// Whether the engine accepts `CASCADE` for this statement at all.
+    pub cascade_supported: bool,
+    pub notes: Vec<String>,
+}
+
+/// Above this estimate tables are not counted exactly.
+const EXACT_COUNT_LIMIT: f64 = 1_000_000.0;
+
+pub async fn analyze_impact(conn: &DatabaseConnection, table: &str, action: ImpactAction) -> Result<ImpactReport, String> {
+    let mut report = ImpactReport {
+        table: table.to_string(),
+        action,
+        row_count: None,
+        row_count_estimated: false,
+        dependents: Vec::new(),
+        requires_cascade: false,
+        cascade_supported: dialect::is_postgres(conn.db_type()),
+        notes: Vec::new(),
+    };
+
+    match conn.db_type() {
+        "postgresql" => analyze_postgres(conn, &mut report).await?,
+        "mysql" | "mariadb" => analyze_mysql(conn, &mut report).await?,
+        "sqlite" => analyze_sqlite(conn, &mut report).await?,
+        other => return Err(format!("Impact analysis is not supported for {}", other)),
+    }
+
+    (report.row_count, report.row_count_estimated) = count_rows(conn, table, &dialect::quote_table(conn.db_type(), table)).await?;
+    for dependent in report.dependents.iter_mut().filter(|d| d.kind == "foreign_key") {
+        if let Some(referencing) = &dependent.table {
+            // Postgres reports regclass text, which is already quoted.
+            let quoted = if dialect::is_postgres(conn.db_type()) {
+                referencing.clone()
+            } else {
+                dialect::quote_table(conn.db_type(), referencing)
+            };
+            (dependent.row_count, dependent.row_count_estimated) = count_rows(conn, referencing, &quoted).await?;
+        }
+    }
+    report.requires_cascade = report.dependents.iter().any(|d| d.blocking);
+    Ok(report)
+}
+
+async fn query(conn: &DatabaseConnection, sql: &str) -> Result<Vec<Vec<Value>>, String> {
+    Ok(conn.execute_query(sql).await.map_err(|e| e.to_string())?.rows)
+}
+
+fn text(row: &[Value], index: usize) -> Option<String> {
+    match row.get(index)? {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+fn number(value: Option<&Value>) -> Option<u64> {
+    match value? {
+        Value::Number(n) => n.as_u64().or_else(|| n.as_f64().map(|f| f.max(0.0) as u64)),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Rows in a table, and whether the number is an estimate: the server's
+/// statistics (`pg_class.reltuples`, `information_schema.TABLES.TABLE_ROWS`)
+/// are read first and only tables estimated below `EXACT_COUNT_LIMIT` are
+/// counted exactly.
+async fn count_rows(conn: &DatabaseConnection, table: &str, quoted_table: &str) -> Result<(Option<u64>, bool), String> {
+    let db_type = conn.db_type();
+    let estimate_sql = match db_type {
+        "postgresql" => Some(format!(
+            "SELECT reltuples::float8::text FROM pg_class WHERE oid = {}::regclass",
+            dialect::quote_string(db_type, quoted_table)
+        )),
+        "mysql" | "mariadb" => {
+            let (schema, name) = split_table(table);
+            Some(format!(
+                "SELECT TABLE_ROWS FROM information_schema.TABLES WHERE TABLE_SCHEMA = {} AND TABLE_NAME = {}",
+                schema.map(|s| dialect::quote_string(db_type, s)).unwrap_or_else(|| "DATABASE()".to_string()),
+                dialect::quote_string(db_type, name)
+            ))
+        }
+        _ => None,
+    };
+    if let Some(sql) = estimate_sql {
+        let rows = query(conn, &sql).await?;
+        if let Some(estimate) = rows.first().and_then(|row| text(row, 0)).and_then(|s| s.parse::<f64>().ok()) {
+            if estimate > EXACT_COUNT_LIMIT {
+                return Ok((Some(estimate as u64), true));
+            }
+        }
+    }
+
+    let sql = format!("SELECT COUNT(*) FROM {}", quoted_table);
+    let rows = query(conn, &sql).await?;
+    Ok((number(rows.first().and_then(|row| row.first())), false))
+}
+
+fn split_table(table: &str) -> (Option<&str>, &str) {
+    match table.split_once('.') {
+        Some((schema, name)) => (Some(schema), name),
+        None => (None, table),
+    }
+}
+
+async fn analyze_postgres(conn: &DatabaseConnection, report: &mut ImpactReport) -> Result<(), String> {
+    let db_type = conn.db_type();
+    let regclass = format!("{}::regclass", dialect::quote_string(db_type, &dialect::quote_table(db_type, &report.table)));
+    let drop = report.action == ImpactAction::Drop;
+
+    // Views and materialized views reach the table through their rewrite rules.
+    let views = query(conn, &format!(
+        "SELECT DISTINCT c.relkind::text, c.oid::regclass::text \
+         FROM pg_depend d \
+         JOIN pg_rewrite r ON r.oid = d.objid \
+         JOIN pg_class c ON c.oid = r.ev_class \
+         WHERE d.classid = 'pg_rewrite'::regclass AND d.refobjid = {0} AND c.oid <> {0}",
+        regclass
+    )).await?;
+    for row in &views {
+        report.dependents.push(DependentObject {
+            kind: if text(row, 0).as_deref() == Some("m") { "materialized_view" } else { "view" }.to_string(),
+            name: text(row, 1).unwrap_or_default(),
+            table: None,
+            detail: None,
+            row_count: None,
+            row_count_estimated: false,
+            blocking: drop,
+            heuristic: false,
+        });
+    }
+
+    let foreign_keys = query(conn, &format!(
+        "SELECT conname::text, conrelid::regclass::text, pg_get_constraintdef(oid), (conrelid = confrelid)::text \
+         FROM pg_constraint WHERE contype = 'f' AND confrelid = {}",
+        regclass
+    )).await?;
+    for row in &foreign_keys {
+        let self_reference = text(row, 3).as_deref() == Some("true");
+        let referencing = text(row, 1).unwrap_or_default();
+        report.dependents.push(DependentObject {
+            kind: "foreign_key".to_string(),
+            name: text(row, 0).unwrap_or_default(),
+            detail: Some(match (drop, self_reference) {
+                (_, true) => format!("{} (self-reference)", text(row, 2).unwrap_or_default()),
+                (true, false) => format!("{}; CASCADE drops the constraint on {}", text(row, 2).unwrap_or_default(), referencing),
+                (false, false) => format!("{}; CASCADE also truncates {}", text(row, 2).unwrap_or_default(), referencing),
+            }),
+            table: Some(referencing),
+            row_count: None,
+            row_count_estimated: false,
+            blocking: !self_reference,
+            heuristic: false,
+        });
+    }
+
+    let triggers = query(conn, &format!(
+        "SELECT tgname::text, pg_get_triggerdef(oid) FROM pg_trigger WHERE tgrelid = {} AND NOT tgisinternal",
+        regclass
+    )).await?;
+    for row in &triggers {
+        report.dependents.push(DependentObject {
+            kind: "trigger".to_string(),
+            name: text(row, 0).unwrap_or_default(),
+            table: Some(report.table.clone()),
+            detail: text(row, 1),
+            row_count: None,
+            row_count_estimated: false,
+            blocking: false,
+            heuristic: false,
+        });
+    }
+    if !drop && !triggers.is_empty() {
+        report.notes.push("TRUNCATE fires ON TRUNCATE triggers only; row-level DELETE triggers do not run.".to_string());
+    }
+
+    // Tracked dependencies: SQL-standard function bodies and functions taking
+    // or returning the table's row type.
+    let tracked = query(conn, &format!(
+        "SELECT DISTINCT p.oid::regprocedure::text, p.prokind::text \
+         FROM pg_depend d JOIN pg_proc p ON p.oid = d.objid \
+         WHERE d.classid = 'pg_proc'::regclass \
+           AND d.refobjid IN ({0}, (SELECT reltype FROM pg_class WHERE oid = {0}))",
+        regclass
+    )).await?;
+    let mut seen = Vec::new();
+    for row in &tracked {
+        let name = text(row, 0).unwrap_or_default();
+        seen.push(name.clone());
+        report.dependents.push(routine(&name, text(row, 1).as_deref() == Some("p"), drop, false));
+    }
+
+    // Other function bodies are opaque to the server; search their source.
+    let (_, name) = split_table(&report.table);
+    let mentioned = query(conn, &format!(
+        "SELECT p.oid::regprocedure::text, p.prokind::text \
+         FROM pg_proc p JOIN pg_namespace n ON n.oid = p.pronamespace \
+         WHERE n.nspname NOT IN ('pg_catalog', 'information_schema') AND p.prosrc ILIKE {}",
+        dialect::quote_string(db_type, &format!("%{}%", like_escape(name)))
+    )).await?;
+    for row in &mentioned {
+        let name = text(row, 0).unwrap_or_default();
+        if !seen.contains(&name) {
+            report.dependents.push(routine(&name, text(row, 1).as_deref() == Some("p"), false, true));
+        }
+    }
+
+    if drop {
+        let sequences = query(conn, &format!(
+            "SELECT c.oid::regclass::text FROM pg_depend d JOIN pg_class c ON c.oid = d.objid \
+             WHERE d.classid = 'pg_class'::regclass AND d.refobjid = {} AND d.deptype IN ('a', 'i') AND c.relkind = 'S'",
+            regclass
+        )).await?;
+        for row in &sequences {
+            report.dependents.push(DependentObject {
+                kind: "sequence".to_string(),
+                name: text(row, 0).unwrap_or_default(),
+                table: Some(report.table.clone()),
+                detail: Some("Owned by a column; dropped with the table".to_string()),
+                row_count: None,
+                row_count_estimated: false,
+                blocking: false,
+                heuristic: false,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn routine(name: &str, procedure: bool, blocking: bool, heuristic: bool) -> DependentObject {
+    DependentObject {
+        kind: if procedure { "procedure" } else { "function" }.to_string(),
+        name: name.to_string(),
+        table: None,
+        detail: heuristic.then(|| "Body mentions the table name".to_string()),
+        row_count: None,
+        row_count_estimated: false,
+        blocking,
+        heuristic,
+    }
+}
+
+fn like_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+async fn analyze_mysql(conn: &DatabaseConnection, report: &mut ImpactReport) -> Result<(), String> {
+    let db_type = conn.db_type();
+    let (schema, name) = split_table(&report.table);
+    let schema_sql = schema.map(|s| dialect::quote_string(db_type, s)).unwrap_or_else(|| "DATABASE()".to_string());
+    let name_sql = dialect::quote_string(db_type, name);
+    let drop = report.action == ImpactAction::Drop;
+
+    // VIEW_TABLE_USAGE exists from MySQL 8.0.13; older servers and MariaDB
+    // only have the view text to search.
+    let tracked = query(conn, &format!(
+        "SELECT VIEW_SCHEMA, VIEW_NAME FROM information_schema.VIEW_TABLE_USAGE \
+         WHERE TABLE_SCHEMA = {} AND TABLE_NAME = {}",
+        schema_sql, name_sql
+    )).await;
+    let (views, heuristic) = match tracked {
+        Ok(rows) => (rows, false),
+        Err(_) => (query(conn, &format!(
+            "SELECT TABLE_SCHEMA, TABLE_NAME FROM information_schema.VIEWS \
+             WHERE VIEW_DEFINITION LIKE {}",
+            dialect::quote_string(db_type, &format!("%`{}`%", like_escape(name)))
+        )).await?, true),
+    };
+    for row in &views {
+        report.dependents.push(DependentObject {
+            kind: "view".to_string(),
+            name: format!("{}.{}", text(row, 0).unwrap_or_default(), text(row, 1).unwrap_or_default()),
+            table: None,
+            detail: drop.then(|| "Becomes invalid; MySQL does not prevent the drop".to_string()),
+            row_count: None,
+            row_count_estimated: false,
+            blocking: false,
+            heuristic,
+        });
+    }
+
+    let foreign_keys = query(conn, &format!(
+        "SELECT CONSTRAINT_NAME, CONSTRAINT_SCHEMA, TABLE_NAME, DELETE_RULE \
+         FROM information_schema.REFERENTIAL_CONSTRAINTS \
+         WHERE UNIQUE_CONSTRAINT_SCHEMA = {} AND REFERENCED_TABLE_NAME = {}",
+        schema_sql, name_sql
+    )).await?;
+    for row in &foreign_keys {
+        let referencing = text(row, 2).unwrap_or_default();
+        let self_reference = referencing == name;
+        report.dependents.push(DependentObject {
+            kind: "foreign_key".to_string(),
+            name: text(row, 0).unwrap_or_default(),
+            table: Some(format!("{}.{}", text(row, 1).unwrap_or_default(), referencing)),
+            detail: Some(format!("ON DELETE {}", text(row, 3).unwrap_or_default())),
+            row_count: None,
+            row_count_estimated: false,
+            // InnoDB refuses to truncate any referenced table, self-references
+            // included; DROP only fails for references from other tables.
+            blocking: !drop || !self_reference,
+            heuristic: false,
+        });
+    }
+    if !foreign_keys.is_empty() {
+        report.notes.push("MySQL has no CASCADE here; drop the referencing foreign keys first or disable foreign_key_checks.".to_string());
+    }
+
+    let triggers = query(conn, &format!(
+        "SELECT TRIGGER_NAME, ACTION_TIMING, EVENT_MANIPULATION FROM information_schema.TRIGGERS \
+         WHERE EVENT_OBJECT_SCHEMA = {} AND EVENT_OBJECT_TABLE = {}",
+        schema_sql, name_sql
+    )).await?;
+    for row in &triggers {
+        report.dependents.push(DependentObject {
+            kind: "trigger".to_string(),
+            name: text(row, 0).unwrap_or_default(),
+            table: Some(report.table.clone()),
+            detail: Some(format!("{} {}", text(row, 1).unwrap_or_default(), text(row, 2).unwrap_or_default())),
+            row_count: None,
+            row_count_estimated: false,
+            blocking: false,
+            heuristic: false,
+        });
+    }
+    if !drop && !triggers.is_empty() {
+        report.notes.push("TRUNCATE does not fire DELETE triggers.".to_string());
+    }
+
+    let routines = query(conn, &format!(
+        "SELECT ROUTINE_SCHEMA, ROUTINE_NAME, ROUTINE_TYPE FROM information_schema.ROUTINES \
+         WHERE ROUTINE_SCHEMA = {} AND ROUTINE_DEFINITION LIKE {}",
+        schema_sql,
+        dialect::quote_string(db_type, &format!("%{}%", like_escape(name)))
+    )).await?;
+    for row in &routines {
+        let name = format!("{}.{}", text(row, 0).unwrap_or_default(), text(row, 1).unwrap_or_default());
+        report.dependents.push(routine(&name, text(row, 2).as_deref() == Some("PROCEDURE"), false, true));
+    }
+    Ok(())
+}
+
+/// SQLite keeps no dependency catalog: views and triggers are found by
+/// tokenizing their stored SQL, foreign keys through `PRAGMA foreign_key_list`.
+async fn analyze_sqlite(conn: &DatabaseConnection, report: &mut ImpactReport) -> Result<(), String> {
+    let db_type = conn.db_type();
+    let (_, name) = split_table(&report.table);
+    let drop = report.action == ImpactAction::Drop;
+    report.notes.push("SQLite has no TRUNCATE; the table is emptied with DELETE FROM.".to_string());
+
+    let objects = query(conn, "SELECT type, name, tbl_name, sql FROM sqlite_master WHERE type IN ('view', 'trigger', 'table')").await?;
+    let enforced = number(query(conn, "PRAGMA foreign_keys").await?.first().and_then(|row| row.first())) == Some(1);
+
+    for row in &objects {
+        let kind = text(row, 0).unwrap_or_default();
+        let object = text(row, 1).unwrap_or_default();
+        let owner = text(row, 2).unwrap_or_default();
+        let sql = text(row, 3).unwrap_or_default();
+
+        match kind.as_str() {
+            "view" if mentions_table(db_type, &sql, name) => report.dependents.push(DependentObject {
+                kind: "view".to_string(),
+                name: object,
+                table: None,
+                detail: drop.then(|| "Becomes invalid; SQLite does not prevent the drop".to_string()),
+                row_count: None,
+                row_count_estimated: false,
+                blocking: false,
+                heuristic: false,
+            }),
+            "trigger" if owner.eq_ignore_ascii_case(name) || mentions_table(db_type, &sql, name) => {
+                let own = owner.eq_ignore_ascii_case(name);
+                report.dependents.push(DependentObject {
+                    kind: "trigger".to_string(),
+                    name: object,
+                    table: Some(owner),
+                    detail: Some(if own && drop {
+                        "Dropped with the table".to_string()
+                    } else if own {
+                        "DELETE triggers fire for every row".to_string()
+                    } else {
+                        "Body references the table".to_string()
+                    }),
+                    row_count: None,
+                    row_count_estimated: false,
+                    blocking: false,
+                    heuristic: false,
+                });
+            }
+            "table" if !owner.eq_ignore_ascii_case(name) => {
+                let pragma = format!("PRAGMA foreign_key_list({})", dialect::quote_identifier(db_type, &owner));
+                for fk in query(conn, &pragma).await? {
+                    if !text(&fk, 2).is_some_and(|t| t.eq_ignore_ascii_case(name)) {
+                        continue;
+                    }
+                    report.dependents.push(DependentObject {
+                        kind: "foreign_key".to_string(),
+                        name: format!("{}({})", owner, text(&fk, 3).unwrap_or_default()),
+                        table: Some(owner.clone()),
+                        detail: Some(format!("ON DELETE {}", text(&fk, 6).unwrap_or_default())),
+                        row_count: None,
+                        row_count_estimated: false,
+                        // Only enforced with PRAGMA foreign_keys = ON, and
+                        // then only when referenced rows exist.
+                        blocking: enforced,
+                        heuristic: false,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    if !enforced && report.dependents.iter().any(|d| d.kind == "foreign_key") {
+        report.notes.push("Foreign keys are not enforced on this connection (PRAGMA foreign_keys is off).".to_string());
+    }
+    Ok(())
+}
+
+fn mentions_table(db_type: &str, sql: &str, table: &str) -> bool {
+    statement::tokenize(db_type, sql).iter().any(|token| {
+        let text = token.text(sql);
+        match token.kind {
+            TokenKind::Word => text.eq_ignore_ascii_case(table),
+            TokenKind::QuotedIdentifier if text.len() >= 2 => text[1..text.len() - 1].eq_ignore_ascii_case(table),
+            _ => false,
+        }
+    })
+}
@@ -0,0 +1,1316 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::de::{Deserializer as _, Error as _, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::Executor;
+use crate::database::{self, DatabaseConnection};
+use crate::dialect;
+use crate::driver_options;
+use crate::mongodb::MongoConnection;
+use crate::types::TlsMode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    Csv,
+    Tsv,
+    Json,
+    Ndjson,
+}
+
+impl ImportFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "csv" | "txt" => Some(ImportFormat::Csv),
+            "tsv" | "tab" => Some(ImportFormat::Tsv),
+            "json" => Some(ImportFormat::Json),
+            "ndjson" | "jsonl" => Some(ImportFormat::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportFileOptions {
+    /// Inferred from the file extension when not given.
+    #[serde(default)]
+    pub format: Option<ImportFormat>,
+    #[serde(default)]
+    pub delimiter: Option<char>,
+    #[serde(default = "default_quote")]
+    pub quote: char,
+    #[serde(default = "default_has_header")]
+    pub has_header: bool,
+    /// Source strings that are loaded as NULL.
+    #[serde(default = "default_null_markers")]
+    pub null_markers: Vec<String>,
+}
+
+fn default_quote() -> char {
+    '"'
+}
+
+fn default_has_header() -> bool {
+    true
+}
+
+fn default_null_markers() -> Vec<String> {
+    vec![String::new(), "\\N".to_string()]
+}
+
+impl Default for ImportFileOptions {
+    fn default() -> Self {
+        Self {
+            format: None,
+            delimiter: None,
+            quote: default_quote(),
+            has_header: default_has_header(),
+            null_markers: default_null_markers(),
+        }
+    }
+}
+
+impl ImportFileOptions {
+    fn resolve_format(&self, path: &Path) -> Result<ImportFormat, String> {
+        self.format
+            .or_else(|| ImportFormat::from_path(path))
+            .ok_or_else(|| format!("Cannot tell the file format of {}; choose one explicitly", path.display()))
+    }
+}
+
+/// Column types the preview infers and a mapping coerces source values to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Boolean,
+    Integer,
+    Float,
+    Date,
+    Timestamp,
+    Text,
+    Json,
+}
+
+impl ColumnType {
+    fn label(self) -> &'static str {
+        match self {
+            ColumnType::Boolean => "boolean",
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::Date => "date",
+            ColumnType::Timestamp => "timestamp",
+            ColumnType::Text => "text",
+            ColumnType::Json => "json",
+        }
+    }
+
+    /// Narrowest type that holds values of both `self` and `other`.
+    fn widen(self, other: ColumnType) -> ColumnType {
+        use ColumnType::*;
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Integer, Float) | (Float, Integer) => Float,
+            (Date, Timestamp) | (Timestamp, Date) => Timestamp,
+            _ => Text,
+        }
+    }
+}
+
+pub fn sql_type_for(db_type: &str, column_type: ColumnType) -> String {
+    let sqlite = !dialect::is_postgres(db_type) && !dialect::is_mysql(db_type);
+    match column_type {
+        ColumnType::Boolean if sqlite => "INTEGER",
+        ColumnType::Boolean => "BOOLEAN",
+        ColumnType::Integer if sqlite => "INTEGER",
+        ColumnType::Integer => "BIGINT",
+        ColumnType::Float if dialect::is_postgres(db_type) => "DOUBLE PRECISION",
+        ColumnType::Float if dialect::is_mysql(db_type) => "DOUBLE",
+        ColumnType::Float => "REAL",
+        ColumnType::Date if sqlite => "TEXT",
+        ColumnType::Date => "DATE",
+        ColumnType::Timestamp if dialect::is_postgres(db_type) => "TIMESTAMP",
+        ColumnType::Timestamp if dialect::is_mysql(db_type) => "DATETIME(6)",
+        ColumnType::Timestamp => "TEXT",
+        ColumnType::Json if dialect::is_postgres(db_type) => "JSONB",
+        ColumnType::Json if dialect::is_mysql(db_type) => "JSON",
+        ColumnType::Json | ColumnType::Text => "TEXT",
+    }
+    .to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviewColumn {
+    pub name: String,
+    pub inferred_type: ColumnType,
+    pub sql_type: String,
+    /// Whether a null (or null marker) was seen in the scanned rows.
+    pub nullable: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportPreview {
+    pub format: ImportFormat,
+    pub columns: Vec<PreviewColumn>,
+    pub sample_rows: Vec<Vec<Value>>,
+    pub rows_scanned: u64,
+    pub invalid_rows: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnMapping {
+    /// Header name for CSV/TSV (`column_N` without a header), key for JSON.
+    pub source: String,
+    pub target: String,
+    #[serde(rename = "type")]
+    pub column_type: ColumnType,
+    /// Column type used when creating the table; derived from `type` if unset.
+    #[serde(default)]
+    pub sql_type: Option<String>,
+    /// Overrides the file-level null markers for this column.
+    #[serde(default)]
+    pub null_markers: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMethod {
+    /// Batched multi-row INSERT statements.
+    #[default]
+    Insert,
+    /// The engine's bulk path: `COPY FROM STDIN` on Postgres, `LOAD DATA LOCAL
+    /// INFILE` on MySQL, a single transaction on SQLite.
+    Native,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportRequest {
+    pub import_id: String,
+    pub connection_id: String,
+    pub path: String,
+    #[serde(default)]
+    pub options: ImportFileOptions,
+    /// Target table, or collection for MongoDB.
+    pub table: String,
+    /// Columns to load. Empty means every source column, with inferred types.
+    #[serde(default)]
+    pub mappings: Vec<ColumnMapping>,
+    pub create_table: Option<bool>,
+    #[serde(default)]
+    pub method: ImportMethod,
+    pub batch_size: Option<usize>,
+    /// Keep going past rows that fail to convert or insert. Bulk paths
+    /// (`COPY`, `LOAD DATA`) are all-or-nothing on the server side.
+    pub continue_on_error: Option<bool>,
+    pub max_error_rows: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportErrorRow {
+    pub line: u64,
+    pub column: Option<String>,
+    pub error: String,
+    pub record: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportProgress {
+    pub import_id: String,
+    pub rows_read: u64,
+    pub rows_imported: u64,
+    pub rows_failed: u64,
+    pub finished: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub import_id: String,
+    pub table: String,
+    /// Loader actually used: insert, copy, load_data, transaction or insert_many.
+    pub loader: String,
+    pub created_table: bool,
+    pub rows_read: u64,
+    pub rows_imported: u64,
+    pub rows_failed: u64,
+    pub errors: Vec<ImportErrorRow>,
+    pub errors_truncated: bool,
+    /// Warnings the server raised while loading, such as truncated values or
+    /// duplicate keys `LOAD DATA` skipped; at most `MAX_LOAD_WARNINGS`.
+    pub warnings: Vec<String>,
+    pub warning_count: u64,
+    pub cancelled: bool,
+    pub elapsed_ms: u64,
+}
+
+const PREVIEW_SAMPLE_ROWS: usize = 50;
+
+/// Scans up to `scan_rows` records to infer column types and returns the first
+/// rows as they will be read, with null markers applied.
+pub fn preview_file(
+    path: &Path,
+    options: &ImportFileOptions,
+    db_type: &str,
+    scan_rows: usize,
+) -> Result<ImportPreview, Box<dyn std::error::Error>> {
+    let format = options.resolve_format(path)?;
+    let mut columns = SourceColumns::new(format, options, path)?;
+    let mut inferred: Vec<Option<ColumnType>> = Vec::new();
+    let mut nullable: Vec<bool> = Vec::new();
+    let mut sample_rows = Vec::new();
+    let mut rows_scanned = 0u64;
+    let mut invalid_rows = 0u64;
+
+    read_records(path, format, options, |record| {
+        if let SourceValues::Invalid { .. } = record.values {
+            invalid_rows += 1;
+            return true;
+        }
+        rows_scanned += 1;
+        columns.observe(&record.values);
+        inferred.resize(columns.names.len(), None);
+        nullable.resize(columns.names.len(), false);
+
+        let mut row = Vec::with_capacity(columns.names.len());
+        for (index, field) in columns.fields().iter().enumerate() {
+            let value = apply_null_markers(record.values.get(field), &options.null_markers);
+            match infer_type(&value) {
+                Some(value_type) => {
+                    inferred[index] = Some(inferred[index].map_or(value_type, |t| t.widen(value_type)));
+                }
+                None => nullable[index] = true,
+            }
+            if sample_rows.len() < PREVIEW_SAMPLE_ROWS {
+                row.push(value);
+            }
+        }
+        if sample_rows.len() < PREVIEW_SAMPLE_ROWS {
+            sample_rows.push(row);
+        }
+        (rows_scanned as usize) < scan_rows
+    })?;
+
+    // JSON keys first seen late leave earlier sample rows short.
+    for row in &mut sample_rows {
+        row.resize(columns.names.len(), Value::Null);
+    }
+
+    let columns = columns
+        .names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let inferred_type = inferred.get(index).copied().flatten().unwrap_or(ColumnType::Text);
+            PreviewColumn {
+                name: name.clone(),
+                inferred_type,
+                sql_type: sql_type_for(db_type, inferred_type),
+                nullable: nullable.get(index).copied().unwrap_or(true),
+            }
+        })
+        .collect();
+
+    Ok(ImportPreview {
+        format,
+        columns,
+        sample_rows,
+        rows_scanned,
+        invalid_rows,
+    })
+}
+
+pub enum ImportTarget {
+    Sql(DatabaseConnection),
+    Mongo(MongoConnection),
+}
+
+impl ImportTarget {
+    fn db_type(&self) -> &str {
+        match self {
+            ImportTarget::Sql(conn) => conn.db_type(),
+            ImportTarget::Mongo(_) => "mongodb",
+        }
+    }
+}
+
+/// Loads a CSV/TSV/JSON/NDJSON file into a table or collection. The file is
+/// parsed on a blocking thread and handed over in batches; each row is coerced
+/// through the column mappings before it reaches the loader. Rows that fail to
+/// convert or insert are collected into the summary when `continue_on_error`
+/// is set, otherwise the first one aborts the import.
+pub async fn run_import<F>(
+    target: &ImportTarget,
+    request: &ImportRequest,
+    cancel: Arc<AtomicBool>,
+    mut on_progress: F,
+) -> Result<ImportSummary, Box<dyn std::error::Error>>
+where
+    F: FnMut(&ImportProgress),
+{
+    let start = std::time::Instant::now();
+    let path = PathBuf::from(&request.path);
+    let options = &request.options;
+    let format = options.resolve_format(&path)?;
+    let db_type = target.db_type().to_string();
+
+    let mappings = if request.mappings.is_empty() {
+        preview_file(&path, options, &db_type, 1000)?
+            .columns
+            .into_iter()
+            .map(|column| ColumnMapping {
+                source: column.name.clone(),
+                target: column.name,
+                column_type: column.inferred_type,
+                sql_type: Some(column.sql_type),
+                null_markers: None,
+            })
+            .collect()
+    } else {
+        request.mappings.clone()
+    };
+    if mappings.is_empty() {
+        return Err("No columns to import".into());
+    }
+    let fields = resolve_fields(&path, format, options, &mappings)?;
+
+    let created_table = request.create_table.unwrap_or(false) && matches!(target, ImportTarget::Sql(_));
+    if created_table {
+        if let ImportTarget::Sql(conn) = target {
+            let sql = create_table_sql(&db_type, &request.table, &mappings);
+            conn.execute_ddl(&sql).await.map_err(|e| e.to_string())?;
+        }
+    }
+
+    let continue_on_error = request.continue_on_error.unwrap_or(false);
+    let max_error_rows = request.max_error_rows.unwrap_or(1000);
+    let batch_size = request.batch_size.unwrap_or(500).max(1);
+    let columns: Vec<String> = mappings.iter().map(|m| m.target.clone()).collect();
+
+    let mut loader = Loader::open(target, request.method, &request.table, columns).await?;
+    let mut receiver = spawn_reader(path, format, options.clone(), batch_size);
+
+    let mut summary = ImportSummary {
+        import_id: request.import_id.clone(),
+        table: request.table.clone(),
+        loader: loader.name().to_string(),
+        created_table,
+        rows_read: 0,
+        rows_imported: 0,
+        rows_failed: 0,
+        errors: Vec::new(),
+        errors_truncated: false,
+        warnings: Vec::new(),
+        warning_count: 0,
+        cancelled: false,
+        elapsed_ms: 0,
+    };
+
+    let outcome: Result<(), String> = async {
+        while let Some(batch) = receiver.recv().await {
+            if cancel.load(Ordering::Relaxed) {
+                summary.cancelled = true;
+                break;
+            }
+            let batch = batch?;
+            summary.rows_read += batch.len() as u64;
+
+            let mut rows = Vec::with_capacity(batch.len());
+            let mut failures = Vec::new();
+            for record in batch {
+                match coerce_record(&record, &mappings, &fields, &options.null_markers) {
+                    Ok(values) => rows.push(PreparedRow { line: record.line, values, record }),
+                    Err(failure) => failures.push(*failure),
+                }
+            }
+            if !failures.is_empty() && !continue_on_error {
+                return Err(describe_failure(&failures[0]));
+            }
+
+            let (imported, load_failures) = loader.write(rows, continue_on_error).await?;
+            summary.rows_imported += imported;
+            failures.extend(load_failures);
+            if !failures.is_empty() && !continue_on_error {
+                return Err(describe_failure(&failures[0]));
+            }
+
+            summary.rows_failed += failures.len() as u64;
+            for failure in failures {
+                if summary.errors.len() < max_error_rows {
+                    summary.errors.push(failure);
+                } else {
+                    summary.errors_truncated = true;
+                }
+            }
+
+            on_progress(&ImportProgress {
+                import_id: request.import_id.clone(),
+                rows_read: summary.rows_read,
+                rows_imported: summary.rows_imported,
+                rows_failed: summary.rows_failed,
+                finished: false,
+            });
+        }
+        Ok(())
+    }
+    .await;
+    drop(receiver);
+
+    match outcome {
+        Ok(()) if !summary.cancelled => {
+            if let Some(loaded) = loader.finish().await? {
+                // Rows the server dropped, e.g. duplicates `LOAD DATA` skips.
+                summary.rows_failed += summary.rows_imported.saturating_sub(loaded.rows);
+                summary.rows_imported = loaded.rows;
+                summary.warnings = loaded.warnings;
+                summary.warning_count = loaded.warning_count;
+            }
+        }
+        Ok(()) => loader.abort("import cancelled").await,
+        Err(error) => {
+            loader.abort(&error).await;
+            return Err(error.into());
+        }
+    }
+
+    summary.elapsed_ms = start.elapsed().as_millis() as u64;
+    Ok(summary)
+}
+
+fn describe_failure(failure: &ImportErrorRow) -> String {
+    match &failure.column {
+        Some(column) => format!("Line {}, column {}: {}", failure.line, column, failure.error),
+        None => format!("Line {}: {}", failure.line, failure.error),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Reading
+// ---------------------------------------------------------------------------
+
+/// One parsed source record. `line` is the 1-based line in the file for
+/// CSV/TSV/NDJSON and the 1-based element index for JSON arrays.
+struct SourceRecord {
+    line: u64,
+    values: SourceValues,
+}
+
+enum SourceValues {
+    Fields(Vec<String>),
+    Object(serde_json::Map<String, Value>),
+    Invalid { raw: String, error: String },
+}
+
+#[derive(Clone)]
+enum SourceField {
+    Index(usize),
+    Key(String),
+}
+
+impl SourceValues {
+    fn get(&self, field: &SourceField) -> Value {
+        match (self, field) {
+            (SourceValues::Fields(fields), SourceField::Index(i)) => {
+                fields.get(*i).map(|s| Value::String(s.clone())).unwrap_or(Value::Null)
+            }
+            (SourceValues::Object(map), SourceField::Key(key)) => map.get(key).cloned().unwrap_or(Value::Null),
+            _ => Value::Null,
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        match self {
+            SourceValues::Fields(fields) => Value::Array(fields.iter().cloned().map(Value::String).collect()),
+            SourceValues::Object(map) => Value::Object(map.clone()),
+            SourceValues::Invalid { raw, .. } => Value::String(raw.clone()),
+        }
+    }
+}
+
+/// Column names discovered while scanning: the header (or `column_N`) for
+/// delimited files, keys in order of first appearance for JSON.
+struct SourceColumns {
+    keyed: bool,
+    names: Vec<String>,
+    seen: HashSet<String>,
+}
+
+impl SourceColumns {
+    fn new(format: ImportFormat, options: &ImportFileOptions, path: &Path) -> Result<Self, String> {
+        let keyed = matches!(format, ImportFormat::Json | ImportFormat::Ndjson);
+        let names = if !keyed && options.has_header {
+            csv_header(path, format, options)?
+        } else {
+            Vec::new()
+        };
+        let seen = names.iter().cloned().collect();
+        Ok(Self { keyed, names, seen })
+    }
+
+    fn observe(&mut self, values: &SourceValues) {
+        match values {
+            SourceValues::Object(map) => {
+                for key in map.keys() {
+                    if self.seen.insert(key.clone()) {
+                        self.names.push(key.clone());
+                    }
+                }
+            }
+            SourceValues::Fields(fields) => {
+                while self.names.len() < fields.len() {
+                    self.names.push(format!("column_{}", self.names.len() + 1));
+                }
+            }
+            SourceValues::Invalid { .. } => {}
+        }
+    }
+
+    fn fields(&self) -> Vec<SourceField> {
+        if self.keyed {
+            self.names.iter().cloned().map(SourceField::Key).collect()
+        } else {
+            (0..self.names.len()).map(SourceField::Index).collect()
+        }
+    }
+}
+
+fn resolve_fields(
+    path: &Path,
+    format: ImportFormat,
+    options: &ImportFileOptions,
+    mappings: &[ColumnMapping],
+) -> Result<Vec<SourceField>, String> {
+    if matches!(format, ImportFormat::Json | ImportFormat::Ndjson) {
+        return Ok(mappings.iter().map(|m| SourceField::Key(m.source.clone())).collect());
+    }
+
+    let header = if options.has_header { csv_header(path, format, options)? } else { Vec::new() };
+    mappings
+        .iter()
+        .map(|mapping| {
+            if let Some(index) = header.iter().position(|name| name == &mapping.source) {
+                return Ok(SourceField::Index(index));
+            }
+            mapping
+                .source
+                .strip_prefix("column_")
+                .and_then(|n| n.parse::<usize>().ok())
+                .filter(|n| *n >= 1)
+                .map(|n| SourceField::Index(n - 1))
+                .ok_or_else(|| format!("Source column '{}' not found in file", mapping.source))
+        })
+        .collect()
+}
+
+fn csv_reader(path: &Path, format: ImportFormat, options: &ImportFileOptions) -> Result<csv::Reader<File>, String> {
+    let delimiter = options.delimiter.unwrap_or(if format == ImportFormat::Tsv { '\t' } else { ',' });
+    if !delimiter.is_ascii() || !options.quote.is_ascii() {
+        return Err("Delimiter and quote must be single-byte characters".to_string());
+    }
+    csv::ReaderBuilder::new()
+        .delimiter(delimiter as u8)
+        .quote(options.quote as u8)
+        .has_headers(options.has_header)
+        .flexible(true)
+        .from_path(path)
+        .map_err(|e| e.to_string())
+}
+
+fn csv_header(path: &Path, format: ImportFormat, options: &ImportFileOptions) -> Result<Vec<String>, String> {
+    let mut reader = csv_reader(path, format, options)?;
+    let header = reader.headers().map_err(|e| e.to_string())?;
+    Ok(header.iter().map(|name| name.trim().to_string()).collect())
+}
+
+/// Parses `path` and calls `emit` for every record until it returns `false`.
+/// Malformed records are emitted as `SourceValues::Invalid`; only I/O errors
+/// and structural problems (e.g. a JSON file that is not an array) fail.
+fn read_records<F>(path: &Path, format: ImportFormat, options: &ImportFileOptions, mut emit: F) -> Result<(), String>
+where
+    F: FnMut(SourceRecord) -> bool,
+{
+    match format {
+        ImportFormat::Csv | ImportFormat::Tsv => {
+            let mut reader = csv_reader(path, format, options)?;
+            for result in reader.records() {
+                let record = match result {
+                    Ok(record) => SourceRecord {
+                        line: record.position().map(|p| p.line()).unwrap_or(0),
+                        values: SourceValues::Fields(record.iter().map(String::from).collect()),
+                    },
+                    Err(e) if e.is_io_error() => return Err(e.to_string()),
+                    Err(e) => SourceRecord {
+                        line: e.position().map(|p| p.line()).unwrap_or(0),
+                        values: SourceValues::Invalid { raw: String::new(), error: e.to_string() },
+                    },
+                };
+                if !emit(record) {
+                    break;
+                }
+            }
+            Ok(())
+        }
+        ImportFormat::Ndjson => {
+            let reader = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+            for (index, line) in reader.lines().enumerate() {
+                let line = line.map_err(|e| e.to_string())?;
+                let text = line.trim().trim_start_matches('\u{feff}');
+                if text.is_empty() {
+                    continue;
+                }
+                let values = match serde_json::from_str::<Value>(text) {
+                    Ok(Value::Object(map)) => SourceValues::Object(map),
+                    Ok(_) => SourceValues::Invalid { raw: line.clone(), error: "not a JSON object".to_string() },
+                    Err(e) => SourceValues::Invalid { raw: line.clone(), error: e.to_string() },
+                };
+                if !emit(SourceRecord { line: index as u64 + 1, values }) {
+                    break;
+                }
+            }
+            Ok(())
+        }
+        ImportFormat::Json => {
+            let mut reader = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+            if reader.fill_buf().map_err(|e| e.to_string())?.starts_with("\u{feff}".as_bytes()) {
+                reader.consume(3);
+            }
+            let mut deserializer = serde_json::Deserializer::from_reader(reader);
+            match deserializer.deserialize_seq(RecordVisitor { emit: &mut emit }) {
+                Ok(()) => Ok(()),
+                Err(e) if e.to_string().starts_with(STOPPED) => Ok(()),
+                Err(e) => Err(format!("Invalid JSON array: {}", e)),
+            }
+        }
+    }
+}
+
+const STOPPED: &str = "reading stopped";
+
+/// Streams the elements of a top-level JSON array without loading the whole
+/// document.
+struct RecordVisitor<'f, F> {
+    emit: &'f mut F,
+}
+
+impl<'de, F> Visitor<'de> for RecordVisitor<'_, F>
+where
+    F: FnMut(SourceRecord) -> bool,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an array of JSON objects")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let mut index = 0u64;
+        while let Some(element) = seq.next_element::<Value>()? {
+            index += 1;
+            let values = match element {
+                Value::Object(map) => SourceValues::Object(map),
+                other => SourceValues::Invalid { raw: other.to_string(), error: "not a JSON object".to_string() },
+            };
+            if !(self.emit)(SourceRecord { line: index, values }) {
+                return Err(A::Error::custom(STOPPED));
+            }
+        }
+        Ok(())
+    }
+}
+
+type RecordBatchReceiver = tokio::sync::mpsc::Receiver<Result<Vec<SourceRecord>, String>>;
+
+fn spawn_reader(path: PathBuf, format: ImportFormat, options: ImportFileOptions, batch_size: usize) -> RecordBatchReceiver {
+    let (sender, receiver) = tokio::sync::mpsc::channel(4);
+
+    tokio::task::spawn_blocking(move || {
+        let mut batch = Vec::with_capacity(batch_size);
+        let result = read_records(&path, format, &options, |record| {
+            batch.push(record);
+            if batch.len() < batch_size {
+                return true;
+            }
+            let full = std::mem::replace(&mut batch, Vec::with_capacity(batch_size));
+            sender.blocking_send(Ok(full)).is_ok()
+        });
+        let last = match result {
+            Ok(()) if batch.is_empty() => return,
+            Ok(()) => Ok(batch),
+            Err(e) => Err(e),
+        };
+        let _ = sender.blocking_send(last);
+    });
+
+    receiver
+}
+
+// ---------------------------------------------------------------------------
+// Type inference and coercion
+// ---------------------------------------------------------------------------
+
+fn apply_null_markers(value: Value, null_markers: &[String]) -> Value {
+    match &value {
+        Value::String(s) if null_markers.iter().any(|marker| marker == s) => Value::Null,
+        _ => value,
+    }
+}
+
+fn infer_type(value: &Value) -> Option<ColumnType> {
+    match value {
+        Value::Null => None,
+        Value::Bool(_) => Some(ColumnType::Boolean),
+        Value::Number(n) if n.is_i64() || n.is_u64() => Some(ColumnType::Integer),
+        Value::Number(_) => Some(ColumnType::Float),
+        Value::Array(_) | Value::Object(_) => Some(ColumnType::Json),
+        Value::String(s) => Some(infer_text_type(s.trim())),
+    }
+}
+
+fn infer_text_type(text: &str) -> ColumnType {
+    if text.eq_ignore_ascii_case("true") || text.eq_ignore_ascii_case("false") {
+        ColumnType::Boolean
+    } else if text.parse::<i64>().is_ok() {
+        ColumnType::Integer
+    } else if text.parse::<f64>().map(|f| f.is_finite()).unwrap_or(false) {
+        ColumnType::Float
+    } else if parse_date(text).is_some() {
+        ColumnType::Date
+    } else if parse_timestamp(text).is_some() {
+        ColumnType::Timestamp
+    } else {
+        ColumnType::Text
+    }
+}
+
+fn parse_date(text: &str) -> Option<NaiveDate> {
+    ["%Y-%m-%d", "%Y/%m/%d"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(text, format).ok())
+}
+
+fn parse_timestamp(text: &str) -> Option<NaiveDateTime> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(text) {
+        return Some(timestamp.naive_utc());
+    }
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M", "%Y/%m/%d %H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+}
+
+/// Converts a source value to the JSON form `dialect::literal` renders for the
+/// target type. Dates and timestamps are normalised to ISO text.
+fn coerce_value(value: Value, column_type: ColumnType) -> Result<Value, String> {
+    let fail = |value: &Value| format!("cannot convert {} to {}", value, column_type.label());
+    match (column_type, value) {
+        (_, Value::Null) => Ok(Value::Null),
+        (ColumnType::Boolean, Value::Bool(b)) => Ok(Value::Bool(b)),
+        (ColumnType::Boolean, Value::Number(n)) => match n.as_i64() {
+            Some(0) => Ok(Value::Bool(false)),
+            Some(1) => Ok(Value::Bool(true)),
+            _ => Err(fail(&Value::Number(n))),
+        },
+        (ColumnType::Boolean, Value::String(s)) => match s.trim().to_ascii_lowercase().as_str() {
+            "true" | "t" | "yes" | "y" | "1" => Ok(Value::Bool(true)),
+            "false" | "f" | "no" | "n" | "0" => Ok(Value::Bool(false)),
+            _ => Err(fail(&Value::String(s))),
+        },
+        (ColumnType::Integer, Value::Number(n)) => {
+            if n.is_i64() || n.is_u64() {
+                Ok(Value::Number(n))
+            } else {
+                match n.as_f64() {
+                    Some(f) if f.fract() == 0.0 && f.abs() < 9.0e15 => Ok(Value::from(f as i64)),
+                    _ => Err(fail(&Value::Number(n))),
+                }
+            }
+        }
+        (ColumnType::Integer, Value::String(s)) => s
+            .trim()
+            .parse::<i64>()
+            .map(Value::from)
+            .map_err(|_| fail(&Value::String(s))),
+        (ColumnType::Integer, Value::Bool(b)) => Ok(Value::from(b as i64)),
+        (ColumnType::Float, Value::Number(n)) => Ok(Value::Number(n)),
+        (ColumnType::Float, Value::String(s)) => s
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| fail(&Value::String(s))),
+        (ColumnType::Date, Value::String(s)) => {
+            let text = s.trim();
+            parse_date(text)
+                .or_else(|| parse_timestamp(text).map(|t| t.date()))
+                .map(|d| Value::String(d.format("%Y-%m-%d").to_string()))
+                .ok_or_else(|| fail(&Value::String(s.clone())))
+        }
+        (ColumnType::Timestamp, Value::String(s)) => {
+            let text = s.trim();
+            parse_timestamp(text)
+                .or_else(|| parse_date(text).and_then(|d| d.and_hms_opt(0, 0, 0)))
+                .map(|t| Value::String(t.format("%Y-%m-%d %H:%M:%S%.f").to_string()))
+                .ok_or_else(|| fail(&Value::String(s.clone())))
+        }
+        (ColumnType::Json, Value::String(s)) => match serde_json::from_str::<Value>(&s) {
+            Ok(parsed @ (Value::Object(_) | Value::Array(_))) => Ok(parsed),
+            Ok(_) => Ok(Value::String(s)),
+            Err(_) => Err(fail(&Value::String(s))),
+        },
+        (ColumnType::Json, other @ (Value::Object(_) | Value::Array(_))) => Ok(other),
+        (ColumnType::Json, other) => Ok(Value::String(other.to_string())),
+        (ColumnType::Text, Value::String(s)) => Ok(Value::String(s)),
+        (ColumnType::Text, other) => Ok(Value::String(other.to_string())),
+        (_, other) => Err(fail(&other)),
+    }
+}
+
+fn coerce_record(
+    record: &SourceRecord,
+    mappings: &[ColumnMapping],
+    fields: &[SourceField],
+    null_markers: &[String],
+) -> Result<Vec<Value>, Box<ImportErrorRow>> {
+    if let SourceValues::Invalid { error, .. } = &record.values {
+        return Err(Box::new(ImportErrorRow {
+            line: record.line,
+            column: None,
+            error: error.clone(),
+            record: record.values.to_json(),
+        }));
+    }
+
+    mappings
+        .iter()
+        .zip(fields)
+        .map(|(mapping, field)| {
+            let markers = mapping.null_markers.as_deref().unwrap_or(null_markers);
+            let value = apply_null_markers(record.values.get(field), markers);
+            coerce_value(value, mapping.column_type).map_err(|error| {
+                Box::new(ImportErrorRow {
+                    line: record.line,
+                    column: Some(mapping.source.clone()),
+                    error,
+                    record: record.values.to_json(),
+                })
+            })
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Loading
+// ---------------------------------------------------------------------------
+
+struct PreparedRow {
+    line: u64,
+    values: Vec<Value>,
+    record: SourceRecord,
+}
+
+impl PreparedRow {
+    fn failure(&self, error: String) -> ImportErrorRow {
+        ImportErrorRow {
+            line: self.line,
+            column: None,
+            error,
+            record: self.record.values.to_json(),
+        }
+    }
+}
+
+/// Chunks of bulk-load text; an `Err` chunk aborts the load.
+type BulkReceiver = tokio::sync::mpsc::Receiver<Result<Vec<u8>, String>>;
+
+/// Warning messages kept in the summary of a `LOAD DATA` import.
+const MAX_LOAD_WARNINGS: usize = 100;
+
+/// What the server reports after a bulk load.
+struct BulkLoaded {
+    rows: u64,
+    warnings: Vec<String>,
+    warning_count: u64,
+}
+
+enum LoaderKind {
+    Insert(DatabaseConnection),
+    Transaction(sqlx::Transaction<'static, sqlx::Any>),
+    /// `COPY` or `LOAD DATA` running on its own connection and task, fed
+    /// through a channel.
+    Bulk {
+        name: &'static str,
+        sender: tokio::sync::mpsc::Sender<Result<Vec<u8>, String>>,
+        handle: tokio::task::JoinHandle<Result<BulkLoaded, String>>,
+    },
+    InsertMany(MongoConnection),
+}
+
+struct Loader {
+    db_type: String,
+    table: String,
+    columns: Vec<String>,
+    kind: LoaderKind,
+}
+
+impl Loader {
+    async fn open(target: &ImportTarget, method: ImportMethod, table: &str, columns: Vec<String>) -> Result<Self, String> {
+        let db_type = target.db_type().to_string();
+        let kind = match target {
+            ImportTarget::Mongo(conn) => LoaderKind::InsertMany(conn.clone()),
+            ImportTarget::Sql(conn) if method == ImportMethod::Insert => LoaderKind::Insert(conn.clone()),
+            ImportTarget::Sql(conn) if dialect::is_postgres(&db_type) => {
+                let statement = format!(
+                    "COPY {} ({}) FROM STDIN",
+                    dialect::quote_table(&db_type, table),
+                    column_list(&db_type, &columns)
+                );
+                let (sender, receiver) = tokio::sync::mpsc::channel(4);
+                let url = conn.url().to_string();
+                let session_sql = database::session_sql(conn.config());
+                let handle = tokio::spawn(async move { copy_from_stdin(&url, &session_sql, &statement, receiver).await });
+                LoaderKind::Bulk { name: "copy", sender, handle }
+            }
+            ImportTarget::Sql(conn) if dialect::is_mysql(&db_type) => {
+                let statement = format!(
+                    "LOAD DATA LOCAL INFILE 'import' INTO TABLE {} CHARACTER SET utf8mb4 \
+                     FIELDS TERMINATED BY '\\t' ESCAPED BY '\\\\' LINES TERMINATED BY '\\n' ({})",
+                    dialect::quote_table(&db_type, table),
+                    column_list(&db_type, &columns)
+                );
+                let opts = load_data_opts(conn)?;
+                let (sender, receiver) = tokio::sync::mpsc::channel(4);
+                let handle = tokio::task::spawn_blocking(move || load_data_local(opts, &statement, receiver));
+                LoaderKind::Bulk { name: "load_data", sender, handle }
+            }
+            ImportTarget::Sql(conn) => LoaderKind::Transaction(conn.begin().await.map_err(|e| e.to_string())?),
+        };
+
+        Ok(Self {
+            db_type,
+            table: table.to_string(),
+            columns,
+            kind,
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        match self.kind {
+            LoaderKind::Insert(_) => "insert",
+            LoaderKind::Transaction(_) => "transaction",
+            LoaderKind::Bulk { name, .. } => name,
+            LoaderKind::InsertMany(_) => "insert_many",
+        }
+    }
+
+    /// Loads one batch and returns the number of rows written plus rows that
+    /// failed. A failed batch is retried row by row when `continue_on_error`
+    /// is set so the report points at the offending rows.
+    async fn write(&mut self, rows: Vec<PreparedRow>, continue_on_error: bool) -> Result<(u64, Vec<ImportErrorRow>), String> {
+        if rows.is_empty() {
+            return Ok((0, Vec::new()));
+        }
+
+        match &mut self.kind {
+            LoaderKind::Insert(conn) => {
+                let sql = insert_sql(&self.db_type, &self.table, &self.columns, &rows);
+                match conn.execute_statement(&sql).await.map_err(|e| e.to_string()) {
+                    Ok(_) => Ok((rows.len() as u64, Vec::new())),
+                    Err(e) if !continue_on_error => Err(format!("Batch starting at line {}: {}", rows[0].line, e)),
+                    Err(_) => {
+                        let mut imported = 0;
+                        let mut failures = Vec::new();
+                        for row in &rows {
+                            let sql = insert_sql(&self.db_type, &self.table, &self.columns, std::slice::from_ref(row));
+                            match conn.execute_statement(&sql).await.map_err(|e| e.to_string()) {
+                                Ok(_) => imported += 1,
+                                Err(e) => failures.push(row.failure(e)),
+                            }
+                        }
+                        Ok((imported, failures))
+                    }
+                }
+            }
+            LoaderKind::Transaction(tx) => {
+                let sql = insert_sql(&self.db_type, &self.table, &self.columns, &rows);
+                match (&mut **tx).execute(sql.as_str()).await.map_err(|e| e.to_string()) {
+                    Ok(_) => Ok((rows.len() as u64, Vec::new())),
+                    Err(e) if !continue_on_error => Err(format!("Batch starting at line {}: {}", rows[0].line, e)),
+                    Err(_) => {
+                        let mut imported = 0;
+                        let mut failures = Vec::new();
+                        for row in &rows {
+                            let sql = insert_sql(&self.db_type, &self.table, &self.columns, std::slice::from_ref(row));
+                            match (&mut **tx).execute(sql.as_str()).await.map_err(|e| e.to_string()) {
+                                Ok(_) => imported += 1,
+                                Err(e) => failures.push(row.failure(e)),
+                            }
+                        }
+                        Ok((imported, failures))
+                    }
+                }
+            }
+            LoaderKind::Bulk { sender, handle, .. } => {
+                if sender.send(Ok(text_rows(&self.db_type, &rows))).await.is_err() {
+                    // The load task ended early; surface its error.
+                    return Err(match handle.await {
+                        Ok(Err(e)) => e,
+                        Ok(Ok(_)) => "Bulk load finished before all rows were sent".to_string(),
+                        Err(e) => e.to_string(),
+                    });
+                }
+                Ok((rows.len() as u64, Vec::new()))
+            }
+            LoaderKind::InsertMany(conn) => {
+                let documents = rows.iter().map(|row| document(&self.columns, &row.values)).collect();
+                match conn.insert_documents(&self.table, documents).await.map_err(|e| e.to_string()) {
+                    Ok(inserted) => Ok((inserted, Vec::new())),
+                    Err(e) if !continue_on_error => Err(format!("Batch starting at line {}: {}", rows[0].line, e)),
+                    Err(_) => {
+                        let mut imported = 0;
+                        let mut failures = Vec::new();
+                        for row in &rows {
+                            let documents = vec![document(&self.columns, &row.values)];
+                            match conn.insert_documents(&self.table, documents).await.map_err(|e| e.to_string()) {
+                                Ok(inserted) => imported += inserted,
+                                Err(e) => failures.push(row.failure(e)),
+                            }
+                        }
+                        Ok((imported, failures))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Commits the load. Returns the server's counts for bulk paths.
+    async fn finish(self) -> Result<Option<BulkLoaded>, String> {
+        match self.kind {
+            LoaderKind::Insert(_) | LoaderKind::InsertMany(_) => Ok(None),
+            LoaderKind::Transaction(tx) => {
+                tx.commit().await.map_err(|e| e.to_string())?;
+                Ok(None)
+            }
+            LoaderKind::Bulk { sender, handle, .. } => {
+                drop(sender);
+                handle.await.map_err(|e| e.to_string())?.map(Some)
+            }
+        }
+    }
+
+    /// Discards whatever the loader has not committed. Rows already written by
+    /// the INSERT loaders stay in place.
+    async fn abort(self, reason: &str) {
+        match self.kind {
+            LoaderKind::Insert(_) | LoaderKind::InsertMany(_) => {}
+            LoaderKind::Transaction(tx) => {
+                let _ = tx.rollback().await;
+            }
+            LoaderKind::Bulk { sender, handle, .. } => {
+                let _ = sender.send(Err(reason.to_string())).await;
+                drop(sender);
+                let _ = handle.await;
+            }
+        }
+    }
+}
+
+/// Runs `COPY ... FROM STDIN` on a dedicated Postgres connection, since the
+/// Any pool cannot hand out a `PgConnection`. The pool's session statements
+/// run first so read-only mode and init SQL apply here too.
+async fn copy_from_stdin(url: &str, session_sql: &[String], statement: &str, mut receiver: BulkReceiver) -> Result<BulkLoaded, String> {
+    use sqlx::Connection;
+
+    let mut conn = sqlx::PgConnection::connect(url).await.map_err(|e| e.to_string())?;
+    for sql in session_sql {
+        conn.execute(sql.as_str()).await.map_err(|e| e.to_string())?;
+    }
+    let mut copy = conn.copy_in_raw(statement).await.map_err(|e| e.to_string())?;
+    while let Some(chunk) = receiver.recv().await {
+        match chunk {
+            Ok(data) => {
+                if let Err(e) = copy.send(data).await {
+                    return Err(e.to_string());
+                }
+            }
+            Err(reason) => {
+                let _ = copy.abort(reason.clone()).await;
+                return Err(reason);
+            }
+        }
+    }
+    let rows = copy.finish().await.map_err(|e| e.to_string())?;
+    Ok(BulkLoaded { rows, warnings: Vec::new(), warning_count: 0 })
+}
+
+/// Options for the native MySQL client, built from the connection config
+/// rather than the sqlx URL, whose parameters the client rejects. Sessions
+/// get the same charset, session statements, init SQL and read-only setting
+/// as the pool.
+fn load_data_opts(conn: &DatabaseConnection) -> Result<mysql::OptsBuilder, String> {
+    let config = conn.config();
+    let tls = config.tls_config();
+    if conn.has_server_name_relay() && tls.mode == TlsMode::VerifyFull {
+        return Err(format!(
+            "LOAD DATA cannot check the TLS server name {}; import with the insert method instead",
+            tls.server_name.as_deref().unwrap_or_default()
+        ));
+    }
+
+    let mut init = Vec::new();
+    if let Some(charset) = driver_options::option(config, "charset") {
+        let collation = driver_options::option(config, "collation")
+            .map(|collation| format!(" COLLATE {}", dialect::quote_string("mysql", collation)))
+            .unwrap_or_default();
+        init.push(format!("SET NAMES {}{}", dialect::quote_string("mysql", charset), collation));
+    }
+    init.extend(database::session_sql(config));
+
+    let (host, port) = conn.connect_target();
+    let mut opts = mysql::OptsBuilder::new()
+        .ip_or_hostname(Some(host))
+        .tcp_port(port)
+        .prefer_socket(false)
+        .user(Some(&config.username))
+        .pass(config.password.as_ref())
+        .db_name(Some(&config.database).filter(|db| !db.is_empty()))
+        .init(init);
+    if tls.mode != TlsMode::Disable {
+        let path = |p: &Option<String>| p.as_deref().filter(|p| !p.is_empty()).map(PathBuf::from);
+        let identity = path(&tls.client_cert_path).map(|cert| {
+            let key = path(&tls.client_key_path).unwrap_or_else(|| cert.clone());
+            mysql::ClientIdentity::new(cert, key)
+        });
+        opts = opts.ssl_opts(mysql::SslOpts::default()
+            .with_root_cert_path(path(&tls.ca_cert_path))
+            .with_client_identity(identity)
+            .with_danger_accept_invalid_certs(matches!(tls.mode, TlsMode::Prefer | TlsMode::Require))
+            .with_danger_skip_domain_validation(tls.mode != TlsMode::VerifyFull));
+    }
+    Ok(opts)
+}
+
+/// Runs `LOAD DATA LOCAL INFILE` through the native MySQL client, streaming
+/// the file contents from `receiver`. An `Err` chunk fails the handler so the
+/// server rolls the statement back. `LOCAL` implies `IGNORE`: duplicates are
+/// skipped and bad values truncated with only a warning, so the warnings are
+/// read back for the summary.
+fn load_data_local(
+    opts: mysql::OptsBuilder,
+    statement: &str,
+    receiver: BulkReceiver,
+) -> Result<BulkLoaded, String> {
+    use mysql::prelude::Queryable;
+
+    let mut receiver = Some(receiver);
+    let handler = mysql::LocalInfileHandler::new(move |_, stream| {
+        let Some(mut receiver) = receiver.take() else {
+            return Ok(());
+        };
+        while let Some(chunk) = receiver.blocking_recv() {
+            match chunk {
+                Ok(data) => stream.write_all(&data)?,
+                Err(reason) => return Err(std::io::Error::new(std::io::ErrorKind::Interrupted, reason)),
+            }
+        }
+        stream.flush()
+    });
+
+    let mut conn = mysql::Conn::new(opts.local_infile_handler(Some(handler))).map_err(|e| e.to_string())?;
+    conn.query_drop(statement).map_err(|e| match e.to_string() {
+        message if message.contains("1148") || message.contains("3948") => {
+            format!("{} (the server must allow local_infile)", message)
+        }
+        message => message,
+    })?;
+    let rows = conn.affected_rows();
+
+    let warning_count: u64 = conn.query_first("SELECT @@warning_count").map_err(|e| e.to_string())?.unwrap_or(0);
+    let warnings = if warning_count > 0 {
+        let listed: Vec<(String, u32, String)> = conn
+            .query(format!("SHOW WARNINGS LIMIT {}", MAX_LOAD_WARNINGS))
+            .map_err(|e| e.to_string())?;
+        listed.into_iter().map(|(level, code, message)| format!("{} {}: {}", level, code, message)).collect()
+    } else {
+        Vec::new()
+    };
+    Ok(BulkLoaded { rows, warnings, warning_count })
+}
+
+fn column_list(db_type: &str, columns: &[String]) -> String {
+    columns.iter().map(|c| dialect::quote_identifier(db_type, c)).collect::<Vec<_>>().join(", ")
+}
+
+fn create_table_sql(db_type: &str, table: &str, mappings: &[ColumnMapping]) -> String {
+    let definitions: Vec<String> = mappings
+        .iter()
+        .map(|m| {
+            let sql_type = m.sql_type.clone().unwrap_or_else(|| sql_type_for(db_type, m.column_type));
+            format!("{} {}", dialect::quote_identifier(db_type, &m.target), sql_type)
+        })
+        .collect();
+    format!("CREATE TABLE {} ({})", dialect::quote_table(db_type, table), definitions.join(", "))
+}
+
+fn insert_sql(db_type: &str, table: &str, columns: &[String], rows: &[PreparedRow]) -> String {
+    let values: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let literals: Vec<String> = row.values.iter().map(|v| dialect::literal(db_type, v)).collect();
+            format!("({})", literals.join(", "))
+        })
+        .collect();
+    format!(
+        "INSERT INTO {} ({}) VALUES {}",
+        dialect::quote_table(db_type, table),
+        column_list(db_type, columns),
+        values.join(", ")
+    )
+}
+
+/// Tab-separated text with `\N` for NULL and backslash escapes, the default
+/// input format of both Postgres `COPY` and MySQL `LOAD DATA`.
+fn text_rows(db_type: &str, rows: &[PreparedRow]) -> Vec<u8> {
+    let mut out = String::new();
+    for row in rows {
+        for (index, value) in row.values.iter().enumerate() {
+            if index > 0 {
+                out.push('\t');
+            }
+            match value {
+                Value::Null => out.push_str("\\N"),
+                Value::Bool(b) if dialect::is_postgres(db_type) => out.push(if *b { 't' } else { 'f' }),
+                Value::Bool(b) => out.push(if *b { '1' } else { '0' }),
+                Value::Number(n) => out.push_str(&n.to_string()),
+                Value::String(s) => push_escaped(&mut out, s),
+                other => push_escaped(&mut out, &other.to_string()),
+            }
+        }
+        out.push('\n');
+    }
+    out.into_bytes()
+}
+
+fn push_escaped(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn document(columns: &[String], values: &[Value]) -> serde_json::Map<String, Value> {
+    columns
+        .iter()
+        .cloned()
+        .zip(values.iter().cloned())
+        .filter(|(_, value)| !value.is_null())
+        .collect()
+}
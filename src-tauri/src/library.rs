@@ -0,0 +1,399 @@
+//! Saved-query library: named scripts stored as plain `.sql` files under a
+//! user-chosen directory, with their metadata in a comment front-matter block
+//! so every file stays valid SQL and the folder can be kept in git.
+//!
+//! ```sql
+//! -- ---
+//! -- name: Active users
+//! -- description: Users seen in the last 30 days
+//! -- tags: reporting, users
+//! -- connection: prod-analytics
+//! -- favorite: true
+//! -- ---
+//! SELECT * FROM users WHERE last_seen > now() - interval '30 days';
+//! ```
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use serde::{Deserialize, Serialize};
+
+const FRONT_MATTER_FENCE: &str = "-- ---";
+
+/// Editors save with a burst of writes, renames and chmods; wait this long
+/// after the last one before reporting a change.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryEntry {
+    /// Path relative to the library root, with `/` separators.
+    pub id: String,
+    /// Containing folder relative to the root; empty at the top level.
+    pub folder: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    /// Id of the connection the script is usually run against.
+    pub connection: Option<String>,
+    pub favorite: bool,
+    pub modified_at: Option<String>,
+    pub sql: String,
+}
+
+/// Fields of a new or edited entry. On update, `None` keeps the current value.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LibraryEntryInput {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub connection: Option<String>,
+    pub favorite: Option<bool>,
+    pub sql: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct LibrarySettings {
+    root: Option<PathBuf>,
+}
+
+type ChangeHandler = Arc<dyn Fn() + Send + Sync>;
+
+pub struct SavedQueryLibrary {
+    settings_path: PathBuf,
+    root: Mutex<Option<PathBuf>>,
+    on_change: Mutex<Option<ChangeHandler>>,
+    /// Filesystem watch on the current root; replaced when the root changes.
+    watcher: Mutex<Option<Debouncer<RecommendedWatcher>>>,
+}
+
+impl SavedQueryLibrary {
+    /// Loads the configured root from `settings_path`, if one was chosen.
+    pub fn open(settings_path: PathBuf) -> Self {
+        let root = std::fs::read_to_string(&settings_path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<LibrarySettings>(&contents).ok())
+            .and_then(|settings| settings.root);
+        Self { settings_path, root: Mutex::new(root), on_change: Mutex::new(None), watcher: Mutex::new(None) }
+    }
+
+    pub fn root(&self) -> Option<PathBuf> {
+        self.root.lock().ok().and_then(|root| root.clone())
+    }
+
+    pub fn set_root(&self, root: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(&root)?;
+        let root = root.canonicalize()?;
+        if let Some(dir) = self.settings_path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let settings = LibrarySettings { root: Some(root.clone()) };
+        std::fs::write(&self.settings_path, serde_json::to_string_pretty(&settings)?)?;
+        *self.root.lock().map_err(|_| "Library lock poisoned")? = Some(root);
+        if let Err(e) = self.rewatch() {
+            log::warn!("Failed to watch the saved-query directory: {}", e);
+        }
+        Ok(())
+    }
+
+    /// Calls `on_change` (on the watcher's thread) after files under the root
+    /// are added, removed or modified, including by other programs. Follows
+    /// the root when `set_root` moves it.
+    pub fn watch(&self, on_change: impl Fn() + Send + Sync + 'static) -> Result<(), Box<dyn std::error::Error>> {
+        *self.on_change.lock().map_err(|_| "Library lock poisoned")? = Some(Arc::new(on_change));
+        self.rewatch()
+    }
+
+    fn rewatch(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut watcher = self.watcher.lock().map_err(|_| "Library lock poisoned")?;
+        *watcher = None;
+        let handler = self.on_change.lock().map_err(|_| "Library lock poisoned")?.clone();
+        let (Some(root), Some(handler)) = (self.root(), handler) else {
+            return Ok(());
+        };
+
+        let watched = root.clone();
+        let mut debouncer = new_debouncer(WATCH_DEBOUNCE, move |result: DebounceEventResult| match result {
+            // Hidden directories such as `.git` churn without touching scripts.
+            Ok(events) if events.iter().any(|event| !is_hidden(&watched, &event.path)) => handler(),
+            Ok(_) => {}
+            Err(e) => log::warn!("Saved-query watcher error: {}", e),
+        })?;
+        debouncer.watcher().watch(&root, RecursiveMode::Recursive)?;
+        *watcher = Some(debouncer);
+        Ok(())
+    }
+
+    fn require_root(&self) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        self.root().ok_or_else(|| "No saved-query directory has been chosen".into())
+    }
+
+    /// All entries, ordered by folder and name. Unreadable files are skipped.
+    pub fn list(&self) -> Result<Vec<LibraryEntry>, Box<dyn std::error::Error>> {
+        let root = self.require_root()?;
+        let mut entries = Vec::new();
+        for path in sql_files(&root)? {
+            match read_entry(&root, &path) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => log::warn!("Skipping unreadable library file {:?}: {}", path, e),
+            }
+        }
+        entries.sort_by(|a, b| (&a.folder, a.name.to_lowercase()).cmp(&(&b.folder, b.name.to_lowercase())));
+        Ok(entries)
+    }
+
+    pub fn read(&self, id: &str) -> Result<LibraryEntry, Box<dyn std::error::Error>> {
+        let root = self.require_root()?;
+        read_entry(&root, &resolve(&root, id)?)
+    }
+
+    /// Writes a new file named after the entry in `folder`, which is created
+    /// if needed.
+    pub fn create(&self, folder: &str, input: &LibraryEntryInput) -> Result<LibraryEntry, Box<dyn std::error::Error>> {
+        let root = self.require_root()?;
+        let name = input.name.clone().filter(|n| !n.trim().is_empty()).ok_or("A saved query needs a name")?;
+        let dir = resolve_folder(&root, folder)?;
+        std::fs::create_dir_all(&dir)?;
+        let path = unused_path(&dir, &file_stem(&name));
+
+        let entry = LibraryEntry {
+            id: String::new(),
+            folder: String::new(),
+            name,
+            description: input.description.clone(),
+            tags: input.tags.clone().unwrap_or_default(),
+            connection: input.connection.clone(),
+            favorite: input.favorite.unwrap_or(false),
+            modified_at: None,
+            sql: input.sql.clone().unwrap_or_default(),
+        };
+        std::fs::write(&path, render(&entry))?;
+        read_entry(&root, &path)
+    }
+
+    /// Rewrites an entry in place; the file keeps its name even if the
+    /// entry's `name` changes (use `rename` for that).
+    pub fn update(&self, id: &str, input: &LibraryEntryInput) -> Result<LibraryEntry, Box<dyn std::error::Error>> {
+        let root = self.require_root()?;
+        let path = resolve(&root, id)?;
+        let mut entry = read_entry(&root, &path)?;
+        if let Some(name) = &input.name {
+            entry.name = name.clone();
+        }
+        if input.description.is_some() {
+            entry.description = input.description.clone().filter(|d| !d.is_empty());
+        }
+        if let Some(tags) = &input.tags {
+            entry.tags = tags.clone();
+        }
+        if input.connection.is_some() {
+            entry.connection = input.connection.clone().filter(|c| !c.is_empty());
+        }
+        if let Some(favorite) = input.favorite {
+            entry.favorite = favorite;
+        }
+        if let Some(sql) = &input.sql {
+            entry.sql = sql.clone();
+        }
+        std::fs::write(&path, render(&entry))?;
+        read_entry(&root, &path)
+    }
+
+    /// Renames the entry and its file.
+    pub fn rename(&self, id: &str, name: &str) -> Result<LibraryEntry, Box<dyn std::error::Error>> {
+        let root = self.require_root()?;
+        let path = resolve(&root, id)?;
+        let mut entry = read_entry(&root, &path)?;
+        entry.name = name.to_string();
+
+        let dir = path.parent().ok_or("Entry has no folder")?;
+        let stem = file_stem(name);
+        let target = if path.file_stem().and_then(|s| s.to_str()) == Some(stem.as_str()) {
+            path.clone()
+        } else {
+            unused_path(dir, &stem)
+        };
+        std::fs::write(&path, render(&entry))?;
+        if target != path {
+            std::fs::rename(&path, &target)?;
+        }
+        read_entry(&root, &target)
+    }
+
+    /// Moves the entry into `folder` (relative to the root, empty for the top
+    /// level), creating it if needed.
+    pub fn move_to(&self, id: &str, folder: &str) -> Result<LibraryEntry, Box<dyn std::error::Error>> {
+        let root = self.require_root()?;
+        let path = resolve(&root, id)?;
+        let dir = resolve_folder(&root, folder)?;
+        if path.parent() == Some(dir.as_path()) {
+            return read_entry(&root, &path);
+        }
+        std::fs::create_dir_all(&dir)?;
+        let stem = path.file_stem().and_then(|s| s.to_str()).ok_or("Invalid file name")?;
+        let target = unused_path(&dir, stem);
+        std::fs::rename(&path, &target)?;
+        read_entry(&root, &target)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let root = self.require_root()?;
+        std::fs::remove_file(resolve(&root, id)?)?;
+        Ok(())
+    }
+}
+
+/// Whether `path` lies in a hidden file or directory below `root`, which
+/// `sql_files` skips.
+fn is_hidden(root: &Path, path: &Path) -> bool {
+    path.strip_prefix(root)
+        .map(|relative| relative.components().any(|c| c.as_os_str().to_string_lossy().starts_with('.')))
+        .unwrap_or(false)
+}
+
+/// `.sql` files under `root`, skipping hidden directories such as `.git`.
+fn sql_files(root: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let hidden = path.file_name().and_then(|n| n.to_str()).map(|n| n.starts_with('.')).unwrap_or(true);
+            if hidden {
+                continue;
+            }
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().map(|ext| ext == "sql").unwrap_or(false) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Joins a relative path onto the root, refusing anything that could escape it.
+fn resolve_folder(root: &Path, relative: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let relative = Path::new(relative);
+    if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(format!("Invalid library path: {}", relative.display()).into());
+    }
+    Ok(root.join(relative))
+}
+
+fn resolve(root: &Path, id: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if id.is_empty() || !id.ends_with(".sql") {
+        return Err(format!("Invalid library entry: {}", id).into());
+    }
+    resolve_folder(root, id)
+}
+
+fn relative_id(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// File name for an entry: the name with path separators and other
+/// characters that are awkward in file names replaced.
+fn file_stem(name: &str) -> String {
+    let stem: String = name.trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.') { c } else { '_' })
+        .collect();
+    let stem = stem.trim_matches('.').trim();
+    if stem.is_empty() { "query".to_string() } else { stem.to_string() }
+}
+
+fn unused_path(dir: &Path, stem: &str) -> PathBuf {
+    let mut path = dir.join(format!("{}.sql", stem));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{} ({}).sql", stem, n));
+        n += 1;
+    }
+    path
+}
+
+fn read_entry(root: &Path, path: &Path) -> Result<LibraryEntry, Box<dyn std::error::Error>> {
+    let contents = std::fs::read_to_string(path)?;
+    let id = relative_id(root, path);
+    let folder = id.rsplit_once('/').map(|(folder, _)| folder.to_string()).unwrap_or_default();
+    let modified_at = std::fs::metadata(path)?
+        .modified()
+        .ok()
+        .map(|time| chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339());
+
+    let mut entry = LibraryEntry {
+        name: path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default(),
+        id,
+        folder,
+        description: None,
+        tags: Vec::new(),
+        connection: None,
+        favorite: false,
+        modified_at,
+        sql: contents.clone(),
+    };
+
+    // Files without front matter are plain scripts named after the file.
+    let mut lines = contents.split_inclusive('\n');
+    let Some(first) = lines.next().filter(|line| line.trim_end() == FRONT_MATTER_FENCE) else {
+        return Ok(entry);
+    };
+    let mut consumed = first.len();
+    let mut closed = false;
+    for line in lines {
+        consumed += line.len();
+        let line = line.trim_end();
+        if line == FRONT_MATTER_FENCE {
+            closed = true;
+            break;
+        }
+        let Some((key, value)) = line.trim_start_matches("--").split_once(':') else { continue };
+        let value = value.trim();
+        match key.trim() {
+            "name" if !value.is_empty() => entry.name = value.to_string(),
+            "description" if !value.is_empty() => entry.description = Some(value.to_string()),
+            "tags" => entry.tags = value.trim_matches(['[', ']'])
+                .split(',')
+                .map(|t| t.trim().trim_matches(['"', '\'']).to_string())
+                .filter(|t| !t.is_empty())
+                .collect(),
+            "connection" if !value.is_empty() => entry.connection = Some(value.to_string()),
+            "favorite" => entry.favorite = value.eq_ignore_ascii_case("true"),
+            _ => {}
+        }
+    }
+    if closed {
+        entry.sql = contents[consumed..].to_string();
+    }
+    Ok(entry)
+}
+
+fn render(entry: &LibraryEntry) -> String {
+    // Values are kept on one line; newlines would end the comment.
+    let one_line = |value: &str| value.replace(['\r', '\n'], " ");
+    let mut out = format!("{}\n-- name: {}\n", FRONT_MATTER_FENCE, one_line(&entry.name));
+    if let Some(description) = &entry.description {
+        out.push_str(&format!("-- description: {}\n", one_line(description)));
+    }
+    if !entry.tags.is_empty() {
+        out.push_str(&format!("-- tags: {}\n", one_line(&entry.tags.join(", "))));
+    }
+    if let Some(connection) = &entry.connection {
+        out.push_str(&format!("-- connection: {}\n", one_line(connection)));
+    }
+    if entry.favorite {
+        out.push_str("-- favorite: true\n");
+    }
+    out.push_str(FRONT_MATTER_FENCE);
+    out.push('\n');
+    out.push_str(&entry.sql);
+    out
+}
@@ -0,0 +1,281 @@
+//! Lock waits on a SQL server, arranged as blocking trees: each root is a
+//! session that blocks others without waiting itself, with the sessions it
+//! blocks (and the ones they block in turn) below it.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use serde::Serialize;
+use serde_json::Value;
+use crate::database::DatabaseConnection;
+use crate::sessions::{self, ServerSession};
+
+/// The lock a blocked session is waiting for.
+#[derive(Debug, Clone, Serialize)]
+pub struct LockWait {
+    pub lock_type: Option<String>,
+    /// Mode requested by the waiting session.
+    pub mode: Option<String>,
+    /// Table, index or transaction the lock is on.
+    pub relation: Option<String>,
+    /// Modes the blocking session holds on the same object.
+    pub held_mode: Option<String>,
+    pub waited_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockingNode {
+    pub session: ServerSession,
+    /// What this session waits for from its parent; `None` for roots.
+    pub waiting_for: Option<LockWait>,
+    pub blocked: Vec<BlockingNode>,
+    /// Distinct sessions below this one, directly or not. A session waiting
+    /// behind several blockers appears under each of them but counts once.
+    pub blocked_total: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockingReport {
+    /// Root blockers, the one holding up the most sessions first.
+    pub roots: Vec<BlockingNode>,
+    /// Sessions waiting on a lock held by another session.
+    pub waiting_sessions: usize,
+}
+
+/// One "blocked waits for blocker" pair.
+#[derive(Debug, Clone)]
+struct Edge {
+    blocked: String,
+    blocker: String,
+    wait: LockWait,
+}
+
+// A session can wait behind several others (e.g. queued after a lock
+// request that is itself waiting), so every blocking pid is an edge.
+// `pg_locks.waitstart` (PG14+) is read through `to_jsonb` so older servers
+// still answer; without it the wait is timed from the last state change.
+const POSTGRES_WAITS: &str = "\
+SELECT a.pid::bigint, b.blocker::bigint, l.locktype::text, l.mode::text, \
+       COALESCE(l.relation::regclass::text, 'transaction ' || l.transactionid::text, l.locktype::text), \
+       (SELECT string_agg(DISTINCT h.mode, ',') FROM pg_locks h \
+        WHERE h.pid = b.blocker AND h.granted AND h.locktype = l.locktype \
+          AND h.database IS NOT DISTINCT FROM l.database \
+          AND h.relation IS NOT DISTINCT FROM l.relation \
+          AND h.transactionid IS NOT DISTINCT FROM l.transactionid), \
+       (EXTRACT(EPOCH FROM now() - COALESCE(l.waitstart, a.state_change, a.query_start)) * 1000)::bigint \
+FROM pg_stat_activity a \
+CROSS JOIN LATERAL unnest(pg_blocking_pids(a.pid)) AS b(blocker) \
+LEFT JOIN LATERAL (SELECT locktype, mode, database, relation, transactionid, \
+                          (to_jsonb(p) ->> 'waitstart')::timestamptz AS waitstart \
+                   FROM pg_locks p WHERE pid = a.pid AND NOT granted LIMIT 1) l ON true";
+
+// MySQL 8.0+.
+const MYSQL_WAITS: &str = "\
+SELECT CAST(rt.PROCESSLIST_ID AS SIGNED), CAST(bt.PROCESSLIST_ID AS SIGNED), rl.LOCK_TYPE, rl.LOCK_MODE, \
+       CONCAT_WS('.', rl.OBJECT_SCHEMA, rl.OBJECT_NAME, rl.INDEX_NAME), bl.LOCK_MODE, \
+       CAST(TIMESTAMPDIFF(MICROSECOND, trx.trx_wait_started, NOW(6)) / 1000 AS SIGNED) \
+FROM performance_schema.data_lock_waits w \
+JOIN performance_schema.data_locks rl ON rl.ENGINE_LOCK_ID = w.REQUESTING_ENGINE_LOCK_ID \
+JOIN performance_schema.data_locks bl ON bl.ENGINE_LOCK_ID = w.BLOCKING_ENGINE_LOCK_ID \
+JOIN performance_schema.threads rt ON rt.THREAD_ID = w.REQUESTING_THREAD_ID \
+JOIN performance_schema.threads bt ON bt.THREAD_ID = w.BLOCKING_THREAD_ID \
+LEFT JOIN information_schema.INNODB_TRX trx ON trx.trx_id = w.REQUESTING_ENGINE_TRANSACTION_ID";
+
+// MySQL 5.7 and MariaDB.
+const INNODB_WAITS: &str = "\
+SELECT CAST(r.trx_mysql_thread_id AS SIGNED), CAST(b.trx_mysql_thread_id AS SIGNED), rl.lock_type, rl.lock_mode, \
+       CONCAT_WS('.', rl.lock_table, rl.lock_index), bl.lock_mode, \
+       CAST(TIMESTAMPDIFF(MICROSECOND, r.trx_wait_started, NOW(6)) / 1000 AS SIGNED) \
+FROM information_schema.INNODB_LOCK_WAITS w \
+JOIN information_schema.INNODB_TRX r ON r.trx_id = w.requesting_trx_id \
+JOIN information_schema.INNODB_TRX b ON b.trx_id = w.blocking_trx_id \
+JOIN information_schema.INNODB_LOCKS rl ON rl.lock_id = w.requested_lock_id \
+JOIN information_schema.INNODB_LOCKS bl ON bl.lock_id = w.blocking_lock_id";
+
+pub async fn analyze(conn: &DatabaseConnection) -> Result<BlockingReport, String> {
+    let rows = match conn.db_type() {
+        "postgresql" => query(conn, POSTGRES_WAITS).await?,
+        "mysql" | "mariadb" => match query(conn, MYSQL_WAITS).await {
+            Ok(rows) => rows,
+            Err(e) => {
+                log::debug!("performance_schema lock waits unavailable ({}), using InnoDB tables", e);
+                query(conn, INNODB_WAITS).await?
+            }
+        },
+        other => return Err(format!("Lock analysis is not available for {}", other)),
+    };
+    let edges: Vec<Edge> = rows.iter().filter_map(|row| {
+        Some(Edge {
+            blocked: text(row, 0)?,
+            blocker: text(row, 1)?,
+            wait: LockWait {
+                lock_type: text(row, 2),
+                mode: text(row, 3),
+                relation: text(row, 4).filter(|relation| !relation.is_empty()),
+                held_mode: text(row, 5),
+                waited_ms: row.get(6).and_then(Value::as_i64).map(|ms| ms.max(0) as u64),
+            },
+        })
+    }).collect();
+    if edges.is_empty() {
+        return Ok(BlockingReport { roots: Vec::new(), waiting_sessions: 0 });
+    }
+    let sessions = sessions::list_sql(conn).await?;
+    Ok(build_report(edges, sessions))
+}
+
+async fn query(conn: &DatabaseConnection, sql: &str) -> Result<Vec<Vec<Value>>, String> {
+    conn.execute_query(sql).await.map(|result| result.rows).map_err(|e| e.to_string())
+}
+
+fn build_report(edges: Vec<Edge>, sessions: Vec<ServerSession>) -> BlockingReport {
+    let sessions: HashMap<String, ServerSession> = sessions.into_iter().map(|s| (s.id.clone(), s)).collect();
+    let mut children: HashMap<&str, Vec<&Edge>> = HashMap::new();
+    for edge in &edges {
+        children.entry(edge.blocker.as_str()).or_default().push(edge);
+    }
+    let waiting: HashSet<&str> = edges.iter().map(|e| e.blocked.as_str()).collect();
+    let blockers: BTreeSet<&str> = edges.iter().map(|e| e.blocker.as_str()).collect();
+
+    let mut reached = HashSet::new();
+    let mut roots: Vec<BlockingNode> = blockers.iter()
+        .filter(|id| !waiting.contains(*id))
+        .map(|id| node(id, None, &children, &sessions, &mut Vec::new(), &mut reached))
+        .collect();
+    // Sessions that only wait on each other (a deadlock the server has not
+    // broken yet) have no root; start one at the lowest id of each cycle.
+    while let Some(id) = blockers.iter().find(|id| !reached.contains(**id)) {
+        roots.push(node(id, None, &children, &sessions, &mut Vec::new(), &mut reached));
+    }
+    roots.sort_by(|a, b| b.blocked_total.cmp(&a.blocked_total).then_with(|| a.session.id.cmp(&b.session.id)));
+    BlockingReport { roots, waiting_sessions: waiting.len() }
+}
+
+fn node<'a>(
+    id: &'a str,
+    waiting_for: Option<LockWait>,
+    children: &HashMap<&'a str, Vec<&'a Edge>>,
+    sessions: &HashMap<String, ServerSession>,
+    path: &mut Vec<&'a str>,
+    reached: &mut HashSet<&'a str>,
+) -> BlockingNode {
+    reached.insert(id);
+    path.push(id);
+    let mut blocked = Vec::new();
+    for edge in children.get(id).into_iter().flatten() {
+        if !path.contains(&edge.blocked.as_str()) {
+            blocked.push(node(&edge.blocked, Some(edge.wait.clone()), children, sessions, path, reached));
+        }
+    }
+    path.pop();
+    let mut below = HashSet::new();
+    collect_ids(&blocked, &mut below);
+    BlockingNode {
+        // A session can end between the two queries; keep its place.
+        session: sessions.get(id).cloned().unwrap_or_else(|| ServerSession {
+            id: id.to_string(),
+            user: None,
+            database: None,
+            client_address: None,
+            application: None,
+            state: None,
+            statement: None,
+            duration_ms: None,
+            wait_event: None,
+            is_current: false,
+        }),
+        waiting_for,
+        blocked_total: below.len(),
+        blocked,
+    }
+}
+
+fn collect_ids<'a>(nodes: &'a [BlockingNode], ids: &mut HashSet<&'a str>) {
+    for node in nodes {
+        if ids.insert(node.session.id.as_str()) {
+            collect_ids(&node.blocked, ids);
+        }
+    }
+}
+
+fn text(row: &[Value], index: usize) -> Option<String> {
+    match row.get(index)? {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(id: &str) -> ServerSession {
+        ServerSession {
+            id: id.to_string(),
+            user: None,
+            database: None,
+            client_address: None,
+            application: None,
+            state: None,
+            statement: None,
+            duration_ms: None,
+            wait_event: None,
+            is_current: false,
+        }
+    }
+
+    /// Builds a report from `(blocked, blocker)` pairs.
+    fn report(pairs: &[(&str, &str)]) -> BlockingReport {
+        let edges = pairs.iter().map(|(blocked, blocker)| Edge {
+            blocked: blocked.to_string(),
+            blocker: blocker.to_string(),
+            wait: LockWait { lock_type: None, mode: None, relation: None, held_mode: None, waited_ms: None },
+        }).collect();
+        let mut ids: Vec<&str> = pairs.iter().flat_map(|(a, b)| [*a, *b]).collect();
+        ids.sort();
+        ids.dedup();
+        build_report(edges, ids.into_iter().map(session).collect())
+    }
+
+    fn ids(nodes: &[BlockingNode]) -> Vec<&str> {
+        nodes.iter().map(|n| n.session.id.as_str()).collect()
+    }
+
+    #[test]
+    fn chain_has_one_root() {
+        let report = report(&[("2", "1"), ("3", "2")]);
+        assert_eq!(ids(&report.roots), ["1"]);
+        assert_eq!(report.roots[0].blocked_total, 2);
+        assert_eq!(ids(&report.roots[0].blocked[0].blocked), ["3"]);
+        assert_eq!(report.waiting_sessions, 2);
+    }
+
+    #[test]
+    fn fan_in_counts_each_waiter_once() {
+        // 4 waits behind both 2 and 3, which both wait behind 1.
+        let report = report(&[("2", "1"), ("3", "1"), ("4", "2"), ("4", "3")]);
+        assert_eq!(ids(&report.roots), ["1"]);
+        let root = &report.roots[0];
+        assert_eq!(root.blocked_total, 3);
+        assert!(root.blocked.iter().all(|child| ids(&child.blocked) == ["4"] && child.blocked_total == 1));
+        assert_eq!(report.waiting_sessions, 3);
+    }
+
+    #[test]
+    fn waiter_behind_two_roots() {
+        let report = report(&[("3", "1"), ("3", "2")]);
+        assert_eq!(ids(&report.roots), ["1", "2"]);
+        assert!(report.roots.iter().all(|root| root.blocked_total == 1));
+        assert_eq!(report.waiting_sessions, 1);
+    }
+
+    #[test]
+    fn cycle_starts_at_lowest_id() {
+        let report = report(&[("1", "2"), ("2", "1"), ("3", "2")]);
+        assert_eq!(ids(&report.roots), ["1"]);
+        let root = &report.roots[0];
+        assert_eq!(root.blocked_total, 2);
+        assert_eq!(ids(&root.blocked), ["2"]);
+        assert_eq!(ids(&root.blocked[0].blocked), ["3"]);
+        assert_eq!(report.waiting_sessions, 3);
+    }
+}
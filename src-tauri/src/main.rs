@@ -1,28 +1,146 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::collections::HashMap;
 use tokio::sync::Mutex;
-use tauri::AppHandle;
+use tauri::{AppHandle, Emitter, Manager};
 
+mod audit;
+mod columnar;
+mod data_diff;
 mod database;
+mod diagnostics;
+mod dialect;
+mod driver_options;
+mod dry_run;
+mod dsn;
+mod export;
+mod health;
+mod history;
+mod impact;
+mod import;
+mod library;
+mod locks;
+mod logging;
 mod mongodb;
+mod policy;
 mod redis;
+mod row_edit;
+mod schema_diff;
+mod sessions;
+mod snapshot;
+mod ssh;
+mod statement;
+mod tls;
+mod tls_relay;
 mod types;
+mod undo;
 
-use types::{QueryRow, TableInfo, ConnectionConfig as AppConnectionConfig};
+use audit::{AuditExportRequest, AuditLog, AuditRecord, AuditVerification};
+use types::{QueryLimits, QueryRow, TableInfo, ConnectionConfig as AppConnectionConfig};
 use database::DatabaseConnection;
 use mongodb::MongoConnection;
 use redis::RedisConnection;
+use columnar::{ColumnarImportRequest, ColumnarImportSummary, ColumnarPreview};
+use data_diff::{DataDiffRequest, DataDiffResult};
+use dry_run::{DryRunRequest, DryRunResult};
+use export::{ExportProgress, ExportRequest, ExportSource, ExportSummary};
+use history::{HistoryEntry, HistoryQuery, HistoryStore};
+use impact::{ImpactReport, ImpactRequest};
+use import::{ImportFileOptions, ImportPreview, ImportProgress, ImportRequest, ImportSummary, ImportTarget};
+use policy::{PolicyDecision, PolicyStore, SafetyPolicy};
+use library::{LibraryEntry, LibraryEntryInput, SavedQueryLibrary};
+use locks::BlockingReport;
+use logging::{LogConfig, LogTail};
+use row_edit::{RowChangePlan, RowChangeRequest, RowChangeResult};
+use schema_diff::{SchemaContents, SchemaDiff, SchemaSource};
+use sessions::ServerSession;
+use snapshot::{SchemaSnapshot, SnapshotStore, SnapshotSummary};
+use undo::{JournalEntry, JournalQuery, JournalStore, JournalSummary};
 
+#[derive(Clone)]
 enum DbConnection {
     Sql(DatabaseConnection),
     Mongo(MongoConnection),
     Redis(RedisConnection),
 }
 
+impl DbConnection {
+    fn config(&self) -> &AppConnectionConfig {
+        match self {
+            DbConnection::Sql(c) => c.config(),
+            DbConnection::Mongo(c) => c.config(),
+            DbConnection::Redis(c) => c.config(),
+        }
+    }
+    
+    /// Refuses data and schema changes on connections marked read-only.
+    fn ensure_writable(&self) -> Result<(), String> {
+        let config = self.config();
+        if config.is_read_only() {
+            return Err(format!("Connection {} is read-only", config.name));
+        }
+        Ok(())
+    }
+    
+    /// On read-only connections, refuses statements the classifier does not
+    /// recognise as reads, so a script cannot switch the session back to
+    /// read-write before writing.
+    fn ensure_readable(&self, text: &str) -> Result<(), String> {
+        let config = self.config();
+        if !config.is_read_only() {
+            return Ok(());
+        }
+        match policy::read_only_violation(&config.r#type, text) {
+            Some(operation) => Err(format!("Connection {} is read-only; {} is not allowed", config.name, operation.verb)),
+            None => Ok(()),
+        }
+    }
+    
+    /// Cheapest round trip each driver offers.
+    async fn ping(&self) -> Result<(), String> {
+        match self {
+            DbConnection::Sql(c) => c.ping().await,
+            DbConnection::Mongo(c) => c.ping().await,
+            DbConnection::Redis(c) => c.ping().await,
+        }
+        .map_err(|e| e.to_string())
+    }
+    
+    async fn fetch_schema(&self) -> Result<Vec<TableInfo>, String> {
+        match self {
+            DbConnection::Sql(c) => c.get_schema().await
+                .map_err(|e| e.to_string()),
+            DbConnection::Mongo(c) => c.get_collections().await
+                .map_err(|e| e.to_string()),
+            DbConnection::Redis(c) => c.get_info().await
+                .map_err(|e| e.to_string()),
+        }
+    }
+    
+    /// Tables of every schema plus indexes, constraints, views, triggers and
+    /// routines, for snapshots and schema comparison.
+    async fn fetch_schema_contents(&self) -> Result<SchemaContents, String> {
+        match self {
+            DbConnection::Sql(c) => Ok(SchemaContents {
+                tables: c.get_all_tables().await.map_err(|e| e.to_string())?,
+                objects: Some(c.get_schema_objects().await.map_err(|e| e.to_string())?),
+            }),
+            _ => Ok(SchemaContents {
+                tables: self.fetch_schema().await?,
+                objects: Some(Vec::new()),
+            }),
+        }
+    }
+}
+
 struct AppState {
     connections: Arc<Mutex<HashMap<String, DbConnection>>>,
+    exports: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+    imports: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+    /// Health monitor task per connected connection.
+    monitors: Arc<Mutex<HashMap<String, tauri::async_runtime::JoinHandle<()>>>>,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -34,6 +152,87 @@ pub struct QueryResult {
     pub execution_time: u64,
     pub affected_rows: usize,
     pub error: Option<String>,
+    /// Undo journal entry, when the statement was captured.
+    pub change_id: Option<String>,
+    /// Rows stopped at the query's row cap; more were available.
+    pub truncated: bool,
+}
+
+/// Journals the inverse of a captured change and returns its change id.
+fn record_undo(
+    app: &AppHandle,
+    config: &AppConnectionConfig,
+    table: &str,
+    source: &str,
+    statement: Option<String>,
+    rows_affected: u64,
+    inverse: Vec<row_edit::RowChange>,
+) -> Result<String, String> {
+    let inverse_script = if config.r#type == "mongodb" {
+        row_edit::plan_document_changes(table, &inverse)?
+            .0
+            .statements
+            .into_iter()
+            .map(|s| s.preview)
+            .collect()
+    } else {
+        undo::describe_inverse(&config.r#type, table, &inverse)
+    };
+    
+    let entry = JournalEntry {
+        format_version: undo::JOURNAL_FORMAT_VERSION,
+        id: undo::new_change_id(),
+        connection_id: config.id.clone(),
+        connection_name: config.name.clone(),
+        db_type: config.r#type.clone(),
+        table: table.to_string(),
+        captured_at: chrono::Utc::now().to_rfc3339(),
+        source: source.to_string(),
+        statement,
+        rows_affected,
+        inverse,
+        inverse_script,
+        undone_at: None,
+    };
+    
+    let store = JournalStore::new(app_data_subdir(app, "undo")?);
+    store.save(&entry).map_err(|e| e.to_string())?;
+    Ok(entry.id)
+}
+
+fn policy_store(app: &AppHandle) -> Result<PolicyStore, String> {
+    Ok(PolicyStore::new(app_data_subdir(app, "policy")?))
+}
+
+/// Evaluates the safety policy for `text` on a connection and refuses it
+/// unless the matching rules are satisfied by `confirmation`. Read-only
+/// connections refuse anything but reads first. Every command that runs
+/// SQL the user typed goes through here before touching the database.
+async fn enforce_policy(
+    app: &AppHandle,
+    state: &AppState,
+    connection_id: &str,
+    text: &str,
+    confirmation: Option<&str>,
+) -> Result<(), String> {
+    let config = {
+        let connections = state.connections.lock().await;
+        let conn = connections.get(connection_id).ok_or("Not connected")?;
+        conn.ensure_readable(text)?;
+        conn.config().clone()
+    };
+    let rules = policy_store(app)?.effective_rules(connection_id).map_err(|e| e.to_string())?;
+    let decision = policy::evaluate(&rules, &config, text);
+    decision.check_confirmation(confirmation).map_err(|e| {
+        log::warn!("Safety policy refused statement on {}: {}", connection_id, e);
+        e
+    })
+}
+
+fn app_data_subdir(app: &AppHandle, name: &str) -> Result<std::path::PathBuf, String> {
+    app.path().app_data_dir()
+        .map(|dir| dir.join(name))
+        .map_err(|e| format!("Cannot resolve app data directory: {}", e))
 }
 
 #[tauri::command]
@@ -50,7 +249,7 @@ async fn save_connection(
     connection: AppConnectionConfig,
 ) -> Result<(), String> {
     // For now, just log the connection - actual persistence is handled in frontend
-    println!("[DEBUG] Connection saved: {}", connection.name);
+    log::info!("Connection saved: {}", connection.name);
     Ok(())
 }
 
@@ -60,11 +259,12 @@ async fn delete_connection(
     id: String,
 ) -> Result<(), String> {
     // Remove from runtime state
+    stop_health_monitor(&state, &id).await;
     let mut connections = state.connections.lock().await;
     connections.remove(&id);
     
     // Also handle in frontend
-    println!("[DEBUG] Connection deleted: {}", id);
+    log::info!("Connection deleted: {}", id);
     Ok(())
 }
 
@@ -79,33 +279,276 @@ async fn get_connection(
 
 #[tauri::command]
 async fn connect_database(
-    _app: AppHandle,
+    app: AppHandle,
     state: tauri::State<'_, AppState>,
     connection: AppConnectionConfig,
 ) -> Result<(), String> {
+    let conn = open_connection(&connection).await?;
+    
+    let mut connections = state.connections.lock().await;
+    connections.insert(connection.id.clone(), conn);
+    drop(connections);
+    start_health_monitor(&app, &state, &connection).await;
+    
+    Ok(())
+}
+
+async fn open_connection(connection: &AppConnectionConfig) -> Result<DbConnection, String> {
     let conn = match connection.r#type.as_str() {
         "sqlite" | "postgresql" | "mysql" | "mariadb" => {
             let conn = DatabaseConnection::new(connection.clone()).await
-                .map_err(|e| e.to_string())?;
+                .map_err(|e| connect_error(connection, e.as_ref()))?;
             DbConnection::Sql(conn)
         }
         "mongodb" => {
             let conn = MongoConnection::new(connection.clone()).await
-                .map_err(|e| e.to_string())?;
+                .map_err(|e| connect_error(connection, e.as_ref()))?;
             DbConnection::Mongo(conn)
         }
         "redis" => {
             let conn = RedisConnection::new(connection.clone()).await
-                .map_err(|e| e.to_string())?;
+                .map_err(|e| connect_error(connection, e.as_ref()))?;
             DbConnection::Redis(conn)
         }
         _ => return Err(format!("Unsupported database type: {}", connection.r#type)),
     };
-    
-    let mut connections = state.connections.lock().await;
-    connections.insert(connection.id.clone(), conn);
-    
-    Ok(())
+    Ok(conn)
+}
+
+/// Replaces any running monitor for the connection with a new one.
+async fn start_health_monitor(app: &AppHandle, state: &AppState, connection: &AppConnectionConfig) {
+    let mut monitors = state.monitors.lock().await;
+    if let Some(previous) = monitors.remove(&connection.id) {
+        previous.abort();
+    }
+    if let Some(settings) = health::HealthSettings::from_config(connection.health_check.as_ref()) {
+        monitors.insert(connection.id.clone(), spawn_health_monitor(app.clone(), connection.clone(), settings));
+    }
+}
+
+async fn stop_health_monitor(state: &AppState, id: &str) {
+    if let Some(monitor) = state.monitors.lock().await.remove(id) {
+        monitor.abort();
+    }
+}
+
+/// Upper bound for one reconnection attempt, SSH tunnel included.
+const RECONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Pings the connection on an interval and emits `connection-health` on
+/// state changes. A lost connection is reopened with backoff and swapped in
+/// under the same id, so commands pick up the new one transparently.
+fn spawn_health_monitor(
+    app: AppHandle,
+    connection: AppConnectionConfig,
+    settings: health::HealthSettings,
+) -> tauri::async_runtime::JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut tracker = health::HealthTracker::new(settings);
+        loop {
+            tokio::time::sleep(tracker.next_delay()).await;
+            let state = app.state::<AppState>();
+            let started = std::time::Instant::now();
+            let timeout = tracker.settings().timeout;
+            let result = if tracker.state() == health::HealthState::Lost {
+                match tokio::time::timeout(RECONNECT_TIMEOUT, open_connection(&connection)).await {
+                    Ok(Ok(conn)) => {
+                        let mut connections = state.connections.lock().await;
+                        if !connections.contains_key(&connection.id) {
+                            return;
+                        }
+                        connections.insert(connection.id.clone(), conn);
+                        Ok(())
+                    }
+                    Ok(Err(e)) => Err(e),
+                    Err(_) => Err("Reconnecting timed out".to_string()),
+                }
+            } else {
+                // Ping a clone so other commands are not held up by a slow check.
+                let conn = match state.connections.lock().await.get(&connection.id) {
+                    Some(conn) => conn.clone(),
+                    None => return,
+                };
+                match tokio::time::timeout(timeout, conn.ping()).await {
+                    Ok(result) => result.map_err(|e| logging::redact(&e, false)),
+                    Err(_) => Err(format!("Health check timed out after {} seconds", timeout.as_secs())),
+                }
+            };
+            let latency_ms = started.elapsed().as_millis() as u64;
+
+            let Some(new_state) = tracker.record(result.is_ok()) else {
+                if let Err(e) = &result {
+                    log::debug!("Health check for {} failed: {}", connection.name, e);
+                }
+                continue;
+            };
+            match &result {
+                Ok(()) => log::info!("Connection {} is {:?}", connection.name, new_state),
+                Err(e) => log::warn!("Connection {} is {:?}: {}", connection.name, new_state, e),
+            }
+            let _ = app.emit("connection-health", health::HealthEvent {
+                connection_id: connection.id.clone(),
+                state: new_state,
+                latency_ms: result.is_ok().then_some(latency_ms),
+                error: result.err(),
+                attempts: tracker.attempts(),
+            });
+        }
+    })
+}
+
+/// Runs staged connection checks without registering the connection.
+#[tauri::command]
+async fn test_connection(connection: AppConnectionConfig) -> Result<diagnostics::ConnectionDiagnostics, String> {
+    Ok(diagnostics::test_connection(&connection).await)
+}
+
+fn connect_error(connection: &AppConnectionConfig, error: &dyn std::error::Error) -> String {
+    let message = logging::redact_error(&error.to_string(), connection.password.as_deref());
+    log::warn!("Connecting to {} failed: {}", connection.name, message);
+    message
+}
+
+/// Parses a connection string into a config for the connection dialog; the
+/// id is left empty for the caller to assign.
+#[tauri::command]
+async fn parse_connection_string(dsn: String) -> Result<AppConnectionConfig, String> {
+    dsn::parse(&dsn).map_err(|e| e.to_string())
+}
+
+/// Connection string for a config, with the password masked unless
+/// `include_password` is set.
+#[tauri::command]
+async fn connection_string(
+    connection: AppConnectionConfig,
+    include_password: Option<bool>,
+) -> Result<String, String> {
+    Ok(dsn::to_url(&connection, !include_password.unwrap_or(false)))
+}
+
+/// Driver options accepted for a database type.
+#[tauri::command]
+async fn list_driver_options(db_type: String) -> Result<Vec<driver_options::OptionSpec>, String> {
+    Ok(driver_options::specs(&db_type).to_vec())
+}
+
+/// Client-side pool figures for a SQL connection.
+#[tauri::command]
+async fn pool_stats(
+    state: tauri::State<'_, AppState>,
+    id: String,
+) -> Result<database::PoolStats, String> {
+    let connections = state.connections.lock().await;
+    match connections.get(&id) {
+        Some(DbConnection::Sql(conn)) => Ok(conn.pool_stats()),
+        Some(_) => Err("Pool statistics are only available for SQL connections".to_string()),
+        None => Err("Connection not found".to_string()),
+    }
+}
+
+/// Sessions on the server behind a connection, not only this app's.
+#[tauri::command]
+async fn list_sessions(
+    state: tauri::State<'_, AppState>,
+    connection_id: String,
+) -> Result<Vec<ServerSession>, String> {
+    let conn = connected(&state, &connection_id).await?;
+    server_sessions(&conn).await
+}
+
+async fn server_sessions(conn: &DbConnection) -> Result<Vec<ServerSession>, String> {
+    match conn {
+        DbConnection::Sql(c) => sessions::list_sql(c).await,
+        DbConnection::Mongo(c) => sessions::list_mongo(c).await,
+        DbConnection::Redis(c) => sessions::list_redis(c).await,
+    }
+}
+
+/// Cancels the statement a session is running, leaving it connected.
+#[tauri::command]
+async fn cancel_session(
+    state: tauri::State<'_, AppState>,
+    audit: tauri::State<'_, AuditLog>,
+    connection_id: String,
+    session_id: String,
+) -> Result<bool, String> {
+    signal_session(&state, &audit, &connection_id, &session_id, false).await
+}
+
+/// Ends a session.
+#[tauri::command]
+async fn kill_session(
+    state: tauri::State<'_, AppState>,
+    audit: tauri::State<'_, AuditLog>,
+    connection_id: String,
+    session_id: String,
+) -> Result<bool, String> {
+    signal_session(&state, &audit, &connection_id, &session_id, true).await
+}
+
+async fn signal_session(
+    state: &AppState,
+    audit: &AuditLog,
+    connection_id: &str,
+    session_id: &str,
+    terminate: bool,
+) -> Result<bool, String> {
+    let conn = connected(state, connection_id).await?;
+    conn.ensure_writable()?;
+    let action = if terminate { "kill" } else { "cancel" };
+    // Signalling our own session would cut the app off mid-request.
+    if server_sessions(&conn).await?.iter().any(|s| s.id == session_id && s.is_current) {
+        return Err(format!("Session {} is this app's own session and cannot be signalled", session_id));
+    }
+    log::info!("{} session {} on connection {}", action, session_id, connection_id);
+    let result = match &conn {
+        DbConnection::Sql(c) => sessions::signal_sql(c, session_id, terminate).await,
+        DbConnection::Mongo(c) => sessions::signal_mongo(c, session_id).await,
+        DbConnection::Redis(c) => sessions::signal_redis(c, session_id, terminate).await,
+    };
+    let outcome = result.as_ref().map(|_| (None, None)).map_err(|e| e.clone());
+    record_audit(audit, state, connection_id, "session", &format!("{} session {}", action, session_id), &outcome).await;
+    result
+}
+
+/// Who is blocking whom on a SQL connection's server.
+#[tauri::command]
+async fn analyze_locks(
+    state: tauri::State<'_, AppState>,
+    connection_id: String,
+) -> Result<BlockingReport, String> {
+    match connected(&state, &connection_id).await? {
+        DbConnection::Sql(c) => locks::analyze(&c).await,
+        _ => Err("Lock analysis is only available for SQL connections".to_string()),
+    }
+}
+
+/// Ends a root blocker. The locks are analyzed again first, so a session
+/// that already finished (or a reused id) is not killed by mistake.
+#[tauri::command]
+async fn kill_root_blocker(
+    state: tauri::State<'_, AppState>,
+    audit: tauri::State<'_, AuditLog>,
+    connection_id: String,
+    session_id: String,
+) -> Result<bool, String> {
+    let report = match connected(&state, &connection_id).await? {
+        DbConnection::Sql(c) => locks::analyze(&c).await?,
+        _ => return Err("Lock analysis is only available for SQL connections".to_string()),
+    };
+    let Some(root) = report.roots.iter().find(|root| root.session.id == session_id) else {
+        return Err(format!("Session {} is no longer blocking other sessions", session_id));
+    };
+    if root.session.is_current {
+        return Err(format!("Session {} is this app's own session and cannot be killed", session_id));
+    }
+    signal_session(&state, &audit, &connection_id, &session_id, true).await
+}
+
+/// A clone of an open connection, so slow server calls don't hold the map.
+async fn connected(state: &AppState, connection_id: &str) -> Result<DbConnection, String> {
+    let connections = state.connections.lock().await;
+    connections.get(connection_id).cloned().ok_or_else(|| "Not connected".to_string())
 }
 
 #[tauri::command]
@@ -113,59 +556,217 @@ async fn disconnect_database(
     state: tauri::State<'_, AppState>,
     id: String,
 ) -> Result<(), String> {
+    stop_health_monitor(&state, &id).await;
     let mut connections = state.connections.lock().await;
     connections.remove(&id);
     Ok(())
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn execute_query(
+    app: AppHandle,
     state: tauri::State<'_, AppState>,
+    history: tauri::State<'_, HistoryStore>,
+    audit: tauri::State<'_, AuditLog>,
     id: String,
     sql: String,
+    capture_undo: Option<bool>,
+    confirmation: Option<String>,
+    tab_id: Option<String>,
+    limits: Option<QueryLimits>,
 ) -> Result<QueryResult, String> {
-    println!("[DEBUG] execute_query called with connection_id: {}, sql: {}", id, sql);
+    log::debug!("execute_query called with connection_id: {}, sql: {}", id, sql);
+    
+    if let Err(e) = enforce_policy(&app, &state, &id, &sql, confirmation.as_deref()).await {
+        record_audit(&audit, &state, &id, "query", &sql, &Err(e.clone())).await;
+        return Err(e);
+    }
+    
+    let started_at = history::now();
+    let start = std::time::Instant::now();
+    let result = run_query(&app, &state, &id, &sql, capture_undo, limits.as_ref()).await.map_err(|e| logging::redact(&e, false));
+    let duration_ms = start.elapsed().as_millis() as u64;
+    
+    let outcome = match &result {
+        Ok((r, rows_affected)) => Ok((Some(r.row_count as u64), *rows_affected)),
+        Err(e) => Err(e.clone()),
+    };
+    record_audit(&audit, &state, &id, "query", &sql, &outcome).await;
+    record_history(&history, &state, &id, "query", &sql, started_at, duration_ms, outcome, tab_id).await;
+    result.map(|(r, _)| QueryResult { execution_time: duration_ms, ..r })
+}
+
+/// Appends to the audit log when the connection is audited, including
+/// statements refused before they ran. A failure to write is logged rather
+/// than returned.
+async fn record_audit(
+    audit: &AuditLog,
+    state: &AppState,
+    connection_id: &str,
+    source: &str,
+    sql: &str,
+    outcome: &Result<(Option<u64>, Option<u64>), String>,
+) {
+    let config = {
+        let connections = state.connections.lock().await;
+        match connections.get(connection_id) {
+            Some(c) if c.config().is_audited() => c.config().clone(),
+            _ => return,
+        }
+    };
+    let record = AuditRecord {
+        seq: 0,
+        timestamp: history::now(),
+        os_user: audit::os_user(),
+        db_user: config.username,
+        connection_id: connection_id.to_string(),
+        connection_name: config.name,
+        db_type: config.r#type,
+        source: source.to_string(),
+        statement: sql.to_string(),
+        success: outcome.is_ok(),
+        error: outcome.as_ref().err().cloned(),
+        rows_affected: outcome.as_ref().ok().and_then(|counts| counts.1),
+        prev_hash: String::new(),
+    };
+    if let Err(e) = audit.append(record) {
+        log::error!("Failed to write audit log entry for {}: {}", connection_id, e);
+    }
+}
+
+/// Stores one execution in the history database. Failures to record are
+/// logged and otherwise ignored, so history never gets in the way of a query.
+#[allow(clippy::too_many_arguments)]
+async fn record_history(
+    history: &HistoryStore,
+    state: &AppState,
+    connection_id: &str,
+    source: &str,
+    sql: &str,
+    started_at: String,
+    duration_ms: u64,
+    outcome: Result<(Option<u64>, Option<u64>), String>,
+    tab_id: Option<String>,
+) {
+    let config = {
+        let connections = state.connections.lock().await;
+        connections.get(connection_id).map(|c| c.config().clone())
+    };
+    let (counts, error) = match outcome {
+        Ok(counts) => (counts, None),
+        Err(e) => ((None, None), Some(e)),
+    };
+    
+    let entry = HistoryEntry {
+        id: 0,
+        connection_id: connection_id.to_string(),
+        connection_name: config.as_ref().map(|c| c.name.clone()),
+        db_type: config.as_ref().map(|c| c.r#type.clone()),
+        source: source.to_string(),
+        sql: sql.to_string(),
+        parameters: None,
+        started_at,
+        duration_ms,
+        row_count: counts.0,
+        affected_rows: counts.1,
+        success: error.is_none(),
+        error,
+        tab_id,
+    };
+    if let Err(e) = history.record(&entry) {
+        log::warn!("Failed to record query history: {}", e);
+    }
+}
+
+async fn run_query(
+    app: &AppHandle,
+    state: &AppState,
+    id: &str,
+    sql: &str,
+    capture_undo: Option<bool>,
+    limits: Option<&QueryLimits>,
+) -> Result<(QueryResult, Option<u64>), String> {
+    if capture_undo.unwrap_or(false) {
+        if let Ok(conn) = sql_connection(state, id).await {
+            if undo::is_row_modification(conn.db_type(), sql) {
+                let start = std::time::Instant::now();
+                // Before-images are held in memory, so the query's row cap
+                // bounds them too.
+                let max_rows = conn.config().resolve_limits(limits).max_rows
+                    .map_or(undo::DEFAULT_MAX_UNDO_ROWS, |max| max.min(undo::DEFAULT_MAX_UNDO_ROWS));
+                let captured = undo::execute_with_undo(&conn, sql, max_rows).await?;
+                let change_id = record_undo(
+                    app,
+                    conn.config(),
+                    &captured.table,
+                    "statement",
+                    Some(sql.to_string()),
+                    captured.rows_affected,
+                    captured.inverse,
+                )?;
+                
+                return Ok((QueryResult {
+                    columns: Vec::new(),
+                    types: Vec::new(),
+                    rows: Vec::new(),
+                    row_count: 0,
+                    execution_time: start.elapsed().as_millis() as u64,
+                    affected_rows: captured.rows_affected as usize,
+                    error: None,
+                    change_id: Some(change_id),
+                    truncated: false,
+                }, Some(captured.rows_affected)));
+            }
+        }
+    }
     
     // Use separate scope for each lock to prevent deadlock
-    let result = {
+    let (result, truncated, rows_affected) = {
         let mut connections = state.connections.lock().await;
-        let conn = connections.get_mut(&id).ok_or("Not connected")?;
+        let conn = connections.get_mut(id).ok_or("Not connected")?;
+        let limits = conn.config().resolve_limits(limits);
         
-        println!("[DEBUG] Found connection, executing query...");
+        log::debug!("Found connection, executing query...");
         
         match conn {
-            DbConnection::Sql(c) => c.execute_query(&sql).await
+            DbConnection::Sql(c) => c.execute_query_limited(sql, &limits).await
+                .map(|(rows, truncated, affected)| (rows, truncated, Some(affected)))
                 .map_err(|e| {
-                    println!("[DEBUG] SQL query error: {:?}", e);
+                    log::warn!("SQL query error: {:?}", e);
                     e.to_string()
                 })?,
-            DbConnection::Mongo(c) => c.execute_mql(&sql).await
+            DbConnection::Mongo(c) => c.execute_mql_limited(sql, &limits).await
+                .map(|(rows, truncated)| (rows, truncated, None))
                 .map_err(|e| {
-                    println!("[DEBUG] MongoDB query error: {:?}", e);
+                    log::warn!("MongoDB query error: {:?}", e);
                     e.to_string()
                 })?,
-            DbConnection::Redis(c) => c.execute_redis_cmd(&sql).await
+            DbConnection::Redis(c) => c.execute_redis_cmd_limited(sql, &limits).await
+                .map(|(rows, truncated)| (rows, truncated, None))
                 .map_err(|e| {
-                    println!("[DEBUG] Redis command error: {:?}", e);
+                    log::warn!("Redis command error: {:?}", e);
                     e.to_string()
                 })?,
         }
     };
     
-    let start = std::time::Instant::now();
     let row_count = result.rows.len();
     
-    println!("[DEBUG] Query completed, returning {} rows", row_count);
+    log::debug!("Query completed, returning {} rows", row_count);
     
-    Ok(QueryResult {
+    Ok((QueryResult {
         columns: result.columns,
         types: result.types,
         rows: result.rows,
         row_count,
-        execution_time: start.elapsed().as_millis() as u64,
-        affected_rows: 0,
+        // Filled in by execute_query, which times the whole call.
+        execution_time: 0,
+        affected_rows: rows_affected.unwrap_or(0) as usize,
         error: None,
-    })
+        change_id: None,
+        truncated,
+    }, rows_affected))
 }
 
 #[tauri::command]
@@ -173,63 +774,738 @@ async fn get_schema(
     state: tauri::State<'_, AppState>,
     connection_id: String,
 ) -> Result<Vec<TableInfo>, String> {
-    let mut connections = state.connections.lock().await;
-    let conn = connections.get_mut(&connection_id).ok_or("Not connected")?;
+    let connections = state.connections.lock().await;
+    let conn = connections.get(&connection_id).ok_or("Not connected")?;
     
-    match conn {
-        DbConnection::Sql(c) => c.get_schema().await
-            .map_err(|e| e.to_string()),
-        DbConnection::Mongo(c) => c.get_collections().await
-            .map_err(|e| e.to_string()),
-        DbConnection::Redis(c) => c.get_info().await
-            .map_err(|e| e.to_string()),
+    conn.fetch_schema().await
+}
+
+#[tauri::command]
+async fn capture_schema_snapshot(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    connection_id: String,
+    label: Option<String>,
+) -> Result<SnapshotSummary, String> {
+    let snapshot = {
+        let connections = state.connections.lock().await;
+        let conn = connections.get(&connection_id).ok_or("Not connected")?;
+        let config = conn.config();
+        let contents = conn.fetch_schema_contents().await?;
+        
+        SchemaSnapshot {
+            format_version: snapshot::SNAPSHOT_FORMAT_VERSION,
+            id: snapshot::new_snapshot_id(),
+            connection_id: connection_id.clone(),
+            connection_name: config.name.clone(),
+            db_type: config.r#type.clone(),
+            database: config.database.clone(),
+            captured_at: chrono::Utc::now().to_rfc3339(),
+            label,
+            tables: contents.tables,
+            objects: contents.objects.unwrap_or_default(),
+        }
+    };
+    
+    let store = SnapshotStore::new(app_data_subdir(&app, "snapshots")?);
+    let path = store.save(&snapshot).map_err(|e| e.to_string())?;
+    log::info!("Schema snapshot written to {:?}", path);
+    
+    Ok(snapshot.summary())
+}
+
+#[tauri::command]
+async fn list_schema_snapshots(
+    app: AppHandle,
+    connection_id: String,
+) -> Result<Vec<SnapshotSummary>, String> {
+    let store = SnapshotStore::new(app_data_subdir(&app, "snapshots")?);
+    store.list(&connection_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_schema_snapshot(
+    app: AppHandle,
+    connection_id: String,
+    snapshot_id: String,
+) -> Result<SchemaSnapshot, String> {
+    let store = SnapshotStore::new(app_data_subdir(&app, "snapshots")?);
+    store.load(&connection_id, &snapshot_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_schema_snapshot(
+    app: AppHandle,
+    connection_id: String,
+    snapshot_id: String,
+) -> Result<(), String> {
+    let store = SnapshotStore::new(app_data_subdir(&app, "snapshots")?);
+    store.delete(&connection_id, &snapshot_id).map_err(|e| e.to_string())
+}
+
+async fn resolve_schema_source(
+    app: &AppHandle,
+    state: &AppState,
+    source: &SchemaSource,
+) -> Result<SchemaContents, String> {
+    match source {
+        SchemaSource::Live { connection_id } => {
+            let connections = state.connections.lock().await;
+            let conn = connections.get(connection_id).ok_or("Not connected")?;
+            conn.fetch_schema_contents().await
+        }
+        SchemaSource::Snapshot { connection_id, snapshot_id } => {
+            let store = SnapshotStore::new(app_data_subdir(app, "snapshots")?);
+            store.load(connection_id, snapshot_id)
+                .map(SchemaSnapshot::into_contents)
+                .map_err(|e| e.to_string())
+        }
+        SchemaSource::File { path } => {
+            snapshot::load_snapshot_file(std::path::Path::new(path))
+                .map(SchemaSnapshot::into_contents)
+                .map_err(|e| e.to_string())
+        }
     }
 }
 
 #[tauri::command]
+async fn diff_schemas(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    source: SchemaSource,
+    target: SchemaSource,
+) -> Result<SchemaDiff, String> {
+    let source_contents = resolve_schema_source(&app, &state, &source).await?;
+    let target_contents = resolve_schema_source(&app, &state, &target).await?;
+    
+    Ok(schema_diff::diff_schemas(&source_contents, &target_contents))
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn execute_ddl(
+    app: AppHandle,
     state: tauri::State<'_, AppState>,
+    history: tauri::State<'_, HistoryStore>,
+    audit: tauri::State<'_, AuditLog>,
     connection_id: String,
     ddl: String,
+    confirmation: Option<String>,
+    tab_id: Option<String>,
 ) -> Result<(), String> {
-    println!("[DEBUG] execute_ddl called with connection_id: {}, ddl: {}", connection_id, ddl);
+    log::debug!("execute_ddl called with connection_id: {}, ddl: {}", connection_id, ddl);
     
+    if let Err(e) = enforce_policy(&app, &state, &connection_id, &ddl, confirmation.as_deref()).await {
+        record_audit(&audit, &state, &connection_id, "ddl", &ddl, &Err(e.clone())).await;
+        return Err(e);
+    }
+    
+    let started_at = history::now();
+    let start = std::time::Instant::now();
+    let result = run_ddl(&state, &connection_id, &ddl).await.map_err(|e| logging::redact(&e, false));
+    let outcome = result.as_ref().map(|rows_affected| (None, *rows_affected)).map_err(|e| e.clone());
+    record_audit(&audit, &state, &connection_id, "ddl", &ddl, &outcome).await;
+    record_history(&history, &state, &connection_id, "ddl", &ddl, started_at, start.elapsed().as_millis() as u64, outcome, tab_id).await;
+    result.map(|_| ())
+}
+
+/// Returns the rows affected, where the driver reports them.
+async fn run_ddl(state: &AppState, connection_id: &str, ddl: &str) -> Result<Option<u64>, String> {
     let mut connections = state.connections.lock().await;
-    let conn = connections.get_mut(&connection_id).ok_or("Not connected")?;
+    let conn = connections.get_mut(connection_id).ok_or("Not connected")?;
+    conn.ensure_writable()?;
     
-    println!("[DEBUG] Found connection, executing DDL...");
+    log::debug!("Found connection, executing DDL...");
     
-    match conn {
-        DbConnection::Sql(c) => c.execute_ddl(&ddl).await
+    let rows_affected = match conn {
+        DbConnection::Sql(c) => Some(c.execute_ddl(ddl).await
             .map_err(|e| {
-                println!("[DEBUG] DDL execution error: {:?}", e);
+                log::warn!("DDL execution error: {:?}", e);
                 e.to_string()
-            })?,
+            })?),
         DbConnection::Mongo(c) => { 
-            c.execute_mql(&ddl).await
+            c.execute_mql(ddl).await
             .map_err(|e| {
-                println!("[DEBUG] MongoDB DDL error: {:?}", e);
+                log::warn!("MongoDB DDL error: {:?}", e);
                 e.to_string()
             })?; 
+            None
         }
         DbConnection::Redis(c) => { 
-            c.execute_redis_cmd(&ddl).await
+            c.execute_redis_cmd(ddl).await
             .map_err(|e| {
-                println!("[DEBUG] Redis DDL error: {:?}", e);
+                log::warn!("Redis DDL error: {:?}", e);
                 e.to_string()
             })?; 
+            None
+        }
+    };
+    
+    log::debug!("DDL executed successfully");
+    Ok(rows_affected)
+}
+
+/// Watches the saved-query directory and emits `library-changed` with the
+/// current entries whenever files are added, removed or modified.
+fn start_library_watcher(app: AppHandle) {
+    let handle = app.clone();
+    let watched = app.state::<SavedQueryLibrary>().watch(move || {
+        match handle.state::<SavedQueryLibrary>().list() {
+            Ok(entries) => { let _ = handle.emit("library-changed", entries); }
+            Err(e) => log::warn!("Failed to reload saved queries: {}", e),
         }
+    });
+    if let Err(e) = watched {
+        log::warn!("Failed to watch the saved-query directory: {}", e);
     }
+}
+
+#[tauri::command]
+async fn verify_audit_log(
+    audit: tauri::State<'_, AuditLog>,
+) -> Result<AuditVerification, String> {
+    audit.verify().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn export_audit_log(
+    audit: tauri::State<'_, AuditLog>,
+    request: AuditExportRequest,
+) -> Result<usize, String> {
+    audit.export(&request).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_log_config() -> Result<LogConfig, String> {
+    Ok(logging::config())
+}
+
+#[tauri::command]
+async fn set_log_config(app: AppHandle, config: LogConfig) -> Result<(), String> {
+    logging::set_config(config.clone())?;
+    let path = app_data_subdir(&app, "logging.json")?;
+    logging::save_config(&path, &config).map_err(|e| e.to_string())
+}
+
+/// Directory holding `app.log` and its rotations, for opening in the file
+/// manager.
+#[tauri::command]
+async fn get_log_dir() -> Result<String, String> {
+    logging::log_dir()
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .ok_or_else(|| "Logging is not initialized".to_string())
+}
+
+/// Last `lines` lines of the current log, or those written after `since`
+/// (the `offset` of a previous call) when polling.
+#[tauri::command]
+async fn tail_log(lines: Option<usize>, since: Option<u64>) -> Result<LogTail, String> {
+    logging::tail(lines.unwrap_or(500), since).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_library_root(
+    library: tauri::State<'_, SavedQueryLibrary>,
+) -> Result<Option<String>, String> {
+    Ok(library.root().map(|root| root.to_string_lossy().into_owned()))
+}
+
+#[tauri::command]
+async fn set_library_root(
+    library: tauri::State<'_, SavedQueryLibrary>,
+    path: String,
+) -> Result<Vec<LibraryEntry>, String> {
+    library.set_root(std::path::PathBuf::from(path)).map_err(|e| e.to_string())?;
+    library.list().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn list_saved_queries(
+    library: tauri::State<'_, SavedQueryLibrary>,
+) -> Result<Vec<LibraryEntry>, String> {
+    library.list().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_saved_query(
+    library: tauri::State<'_, SavedQueryLibrary>,
+    id: String,
+) -> Result<LibraryEntry, String> {
+    library.read(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn create_saved_query(
+    library: tauri::State<'_, SavedQueryLibrary>,
+    folder: Option<String>,
+    entry: LibraryEntryInput,
+) -> Result<LibraryEntry, String> {
+    library.create(folder.as_deref().unwrap_or_default(), &entry).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn update_saved_query(
+    library: tauri::State<'_, SavedQueryLibrary>,
+    id: String,
+    entry: LibraryEntryInput,
+) -> Result<LibraryEntry, String> {
+    library.update(&id, &entry).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn rename_saved_query(
+    library: tauri::State<'_, SavedQueryLibrary>,
+    id: String,
+    name: String,
+) -> Result<LibraryEntry, String> {
+    library.rename(&id, &name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn move_saved_query(
+    library: tauri::State<'_, SavedQueryLibrary>,
+    id: String,
+    folder: String,
+) -> Result<LibraryEntry, String> {
+    library.move_to(&id, &folder).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_saved_query(
+    library: tauri::State<'_, SavedQueryLibrary>,
+    id: String,
+) -> Result<(), String> {
+    library.delete(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn search_history(
+    history: tauri::State<'_, HistoryStore>,
+    query: Option<HistoryQuery>,
+) -> Result<Vec<HistoryEntry>, String> {
+    history.search(&query.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+/// Deletes history older than `before` (RFC 3339), or all of it.
+#[tauri::command]
+async fn clear_history(
+    history: tauri::State<'_, HistoryStore>,
+    before: Option<String>,
+) -> Result<usize, String> {
+    history.clear(before.as_deref()).map_err(|e| e.to_string())
+}
+
+/// Runs a statement or script in a transaction that is rolled back, reporting
+/// per-statement row counts and optionally a sample of the changed rows.
+#[tauri::command]
+async fn dry_run_query(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    request: DryRunRequest,
+) -> Result<DryRunResult, String> {
+    log::debug!("dry_run_query called with connection_id: {}, sql: {}", request.connection_id, request.sql);
+    enforce_policy(&app, &state, &request.connection_id, &request.sql, request.confirmation.as_deref()).await?;
+    let conn = sql_connection(&state, &request.connection_id).await?;
+    let sample_rows = request.sample_rows.unwrap_or(0);
+    let sample_rows = conn.config().resolve_limits(None).max_rows.map_or(sample_rows, |max| sample_rows.min(max));
+    dry_run::dry_run(&conn, &request.sql, sample_rows).await.map_err(|e| {
+        log::warn!("Dry run error: {}", e);
+        e
+    })
+}
+
+/// Lists what dropping or truncating a table would affect, for the
+/// confirmation dialog shown before `execute_ddl`.
+#[tauri::command]
+async fn analyze_impact(
+    state: tauri::State<'_, AppState>,
+    request: ImpactRequest,
+) -> Result<ImpactReport, String> {
+    let conn = sql_connection(&state, &request.connection_id).await?;
+    impact::analyze_impact(&conn, &request.table, request.action).await.map_err(|e| {
+        log::warn!("Impact analysis error: {}", e);
+        e
+    })
+}
+
+/// Classifies a statement and reports what the safety policy would require,
+/// so the UI can ask for confirmation before calling `execute_query`.
+#[tauri::command]
+async fn check_statement_policy(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    connection_id: String,
+    sql: String,
+) -> Result<PolicyDecision, String> {
+    let config = {
+        let connections = state.connections.lock().await;
+        connections.get(&connection_id).ok_or("Not connected")?.config().clone()
+    };
+    let rules = policy_store(&app)?.effective_rules(&connection_id).map_err(|e| e.to_string())?;
+    Ok(policy::evaluate(&rules, &config, &sql))
+}
+
+/// The global policy when `connection_id` is omitted, otherwise the
+/// connection's own rules (`None` if it has no policy file).
+#[tauri::command]
+async fn get_safety_policy(
+    app: AppHandle,
+    connection_id: Option<String>,
+) -> Result<Option<SafetyPolicy>, String> {
+    policy_store(&app)?.load(connection_id.as_deref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn save_safety_policy(
+    app: AppHandle,
+    connection_id: Option<String>,
+    policy: SafetyPolicy,
+) -> Result<(), String> {
+    policy_store(&app)?.save(connection_id.as_deref(), &policy).map_err(|e| e.to_string())
+}
+
+async fn sql_connection(state: &AppState, id: &str) -> Result<DatabaseConnection, String> {
+    let connections = state.connections.lock().await;
+    match connections.get(id).ok_or("Not connected")? {
+        DbConnection::Sql(c) => Ok(c.clone()),
+        _ => Err(format!("Connection {} is not a SQL connection", id)),
+    }
+}
+
+#[tauri::command]
+async fn diff_table_data(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    request: DataDiffRequest,
+) -> Result<DataDiffResult, String> {
+    if let Some(query) = &request.query {
+        for id in [&request.source_connection_id, &request.target_connection_id] {
+            enforce_policy(&app, &state, id, query, request.confirmation.as_deref()).await?;
+        }
+    }
+    let source = sql_connection(&state, &request.source_connection_id).await?;
+    let target = sql_connection(&state, &request.target_connection_id).await?;
     
-    println!("[DEBUG] DDL executed successfully");
+    data_diff::diff_table_data(&source, &target, &request).await
+        .map_err(|e| {
+            log::warn!("Data diff error: {:?}", e);
+            e.to_string()
+        })
+}
+
+#[tauri::command]
+async fn export_to_file(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    request: ExportRequest,
+) -> Result<ExportSummary, String> {
+    let source = {
+        let connections = state.connections.lock().await;
+        match connections.get(&request.connection_id).ok_or("Not connected")? {
+            DbConnection::Sql(c) => ExportSource::Sql(c.clone()),
+            DbConnection::Mongo(c) => ExportSource::Mongo(c.clone()),
+            DbConnection::Redis(_) => return Err("Export is not supported for Redis connections".to_string()),
+        }
+    };
+    if let Some(query) = &request.query {
+        enforce_policy(&app, &state, &request.connection_id, query, request.confirmation.as_deref()).await?;
+    }
+    
+    let cancel = Arc::new(AtomicBool::new(false));
+    state.exports.lock().await.insert(request.export_id.clone(), cancel.clone());
+    
+    let export_id = request.export_id.clone();
+    let result = export::run_export(&source, &request, cancel, |rows_written| {
+        let _ = app.emit("export-progress", ExportProgress {
+            export_id: export_id.clone(),
+            rows_written,
+            finished: false,
+        });
+    }).await.map_err(|e| {
+        log::warn!("Export error: {:?}", e);
+        e.to_string()
+    });
+    
+    state.exports.lock().await.remove(&request.export_id);
+    
+    let summary = result?;
+    let _ = app.emit("export-progress", ExportProgress {
+        export_id: summary.export_id.clone(),
+        rows_written: summary.rows_written,
+        finished: true,
+    });
+    
+    log::info!("Export {} wrote {} rows to {}", summary.export_id, summary.rows_written, summary.path);
+    Ok(summary)
+}
+
+#[tauri::command]
+async fn cancel_export(
+    state: tauri::State<'_, AppState>,
+    export_id: String,
+) -> Result<(), String> {
+    let exports = state.exports.lock().await;
+    let cancel = exports.get(&export_id).ok_or("No export running with this id")?;
+    cancel.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+#[tauri::command]
+async fn preview_columnar_file(
+    state: tauri::State<'_, AppState>,
+    path: String,
+    connection_id: Option<String>,
+) -> Result<ColumnarPreview, String> {
+    let db_type = match connection_id {
+        Some(id) => {
+            let connections = state.connections.lock().await;
+            connections.get(&id).ok_or("Not connected")?.config().r#type.clone()
+        }
+        None => "postgresql".to_string(),
+    };
+    
+    columnar::preview_file(std::path::Path::new(&path), &db_type, 20)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn import_columnar_file(
+    state: tauri::State<'_, AppState>,
+    request: ColumnarImportRequest,
+) -> Result<ColumnarImportSummary, String> {
+    let target = {
+        let connections = state.connections.lock().await;
+        let conn = connections.get(&request.connection_id).ok_or("Not connected")?;
+        conn.ensure_writable()?;
+        match conn {
+            DbConnection::Sql(c) => ImportTarget::Sql(c.clone()),
+            DbConnection::Mongo(c) => ImportTarget::Mongo(c.clone()),
+            DbConnection::Redis(_) => return Err("Import is not supported for Redis connections".to_string()),
+        }
+    };
+    
+    let cancel = Arc::new(AtomicBool::new(false));
+    state.imports.lock().await.insert(request.import_id.clone(), cancel.clone());
+    
+    let result = match &target {
+        ImportTarget::Sql(conn) => columnar::import_into_sql(conn, &request, &cancel).await,
+        ImportTarget::Mongo(conn) => columnar::import_into_mongo(conn, &request, &cancel).await,
+    }
+    .map_err(|e| {
+        log::warn!("Columnar import error: {:?}", e);
+        e.to_string()
+    });
+    
+    state.imports.lock().await.remove(&request.import_id);
+    result
+}
+
+#[tauri::command]
+async fn preview_import_file(
+    state: tauri::State<'_, AppState>,
+    path: String,
+    options: Option<ImportFileOptions>,
+    connection_id: Option<String>,
+) -> Result<ImportPreview, String> {
+    let db_type = match connection_id {
+        Some(id) => {
+            let connections = state.connections.lock().await;
+            connections.get(&id).ok_or("Not connected")?.config().r#type.clone()
+        }
+        None => "postgresql".to_string(),
+    };
+    let options = options.unwrap_or_default();
+    
+    tokio::task::spawn_blocking(move || {
+        import::preview_file(std::path::Path::new(&path), &options, &db_type, 1000)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn import_file(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    request: ImportRequest,
+) -> Result<ImportSummary, String> {
+    let target = {
+        let connections = state.connections.lock().await;
+        let conn = connections.get(&request.connection_id).ok_or("Not connected")?;
+        conn.ensure_writable()?;
+        match conn {
+            DbConnection::Sql(c) => ImportTarget::Sql(c.clone()),
+            DbConnection::Mongo(c) => ImportTarget::Mongo(c.clone()),
+            DbConnection::Redis(_) => return Err("Import is not supported for Redis connections".to_string()),
+        }
+    };
+    
+    let cancel = Arc::new(AtomicBool::new(false));
+    state.imports.lock().await.insert(request.import_id.clone(), cancel.clone());
+    
+    let result = import::run_import(&target, &request, cancel, |progress| {
+        let _ = app.emit("import-progress", progress.clone());
+    }).await.map_err(|e| {
+        log::warn!("Import error: {:?}", e);
+        e.to_string()
+    });
+    
+    state.imports.lock().await.remove(&request.import_id);
+    
+    let summary = result?;
+    let _ = app.emit("import-progress", ImportProgress {
+        import_id: summary.import_id.clone(),
+        rows_read: summary.rows_read,
+        rows_imported: summary.rows_imported,
+        rows_failed: summary.rows_failed,
+        finished: true,
+    });
+    
+    log::info!("Import {} loaded {} rows into {} ({} failed)", summary.import_id, summary.rows_imported, summary.table, summary.rows_failed);
+    Ok(summary)
+}
+
+#[tauri::command]
+async fn cancel_import(
+    state: tauri::State<'_, AppState>,
+    import_id: String,
+) -> Result<(), String> {
+    let imports = state.imports.lock().await;
+    let cancel = imports.get(&import_id).ok_or("No import running with this id")?;
+    cancel.store(true, Ordering::Relaxed);
     Ok(())
 }
 
+#[tauri::command]
+async fn preview_row_changes(
+    state: tauri::State<'_, AppState>,
+    request: RowChangeRequest,
+) -> Result<RowChangePlan, String> {
+    let connections = state.connections.lock().await;
+    match connections.get(&request.connection_id).ok_or("Not connected")? {
+        DbConnection::Sql(c) => {
+            let conn = c.clone();
+            drop(connections);
+            row_edit::plan_sql_changes(&conn, &request.table, &request.changes).await
+        }
+        DbConnection::Mongo(_) => {
+            row_edit::plan_document_changes(&request.table, &request.changes).map(|(plan, _)| plan)
+        }
+        DbConnection::Redis(_) => Err("Row editing is not supported for Redis connections".to_string()),
+    }
+}
+
+#[tauri::command]
+async fn apply_row_changes(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    request: RowChangeRequest,
+) -> Result<RowChangeResult, String> {
+    let capture_undo = request.capture_undo.unwrap_or(false);
+    let connections = state.connections.lock().await;
+    let target = connections.get(&request.connection_id).ok_or("Not connected")?;
+    target.ensure_writable()?;
+    let (config, result) = match target {
+        DbConnection::Sql(c) => {
+            let conn = c.clone();
+            drop(connections);
+            let result = row_edit::apply_sql_changes(&conn, &request.table, &request.changes, capture_undo).await;
+            (conn.config().clone(), result)
+        }
+        DbConnection::Mongo(c) => {
+            let conn = c.clone();
+            drop(connections);
+            let result = row_edit::apply_document_changes(&conn, &request.table, &request.changes, capture_undo).await;
+            (conn.config().clone(), result)
+        }
+        DbConnection::Redis(_) => return Err("Row editing is not supported for Redis connections".to_string()),
+    };
+    
+    let (mut result, inverse) = result.map_err(|e| {
+        log::warn!("Row change error: {}", e);
+        e
+    })?;
+    
+    if capture_undo && !inverse.is_empty() {
+        result.change_id = Some(record_undo(&app, &config, &request.table, "grid", None, result.rows_affected, inverse)?);
+    }
+    Ok(result)
+}
+
+#[tauri::command]
+async fn search_undo_journal(
+    app: AppHandle,
+    query: Option<JournalQuery>,
+) -> Result<Vec<JournalSummary>, String> {
+    let store = JournalStore::new(app_data_subdir(&app, "undo")?);
+    store.search(&query.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_undo_entry(
+    app: AppHandle,
+    change_id: String,
+) -> Result<JournalEntry, String> {
+    let store = JournalStore::new(app_data_subdir(&app, "undo")?);
+    store.load(&change_id).map_err(|e| e.to_string())
+}
+
+/// Reapplies the before-images of a journaled change. The connection it was
+/// captured on must be open.
+#[tauri::command]
+async fn undo_change(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    change_id: String,
+) -> Result<RowChangeResult, String> {
+    let store = JournalStore::new(app_data_subdir(&app, "undo")?);
+    let mut entry = store.load(&change_id).map_err(|e| e.to_string())?;
+    if let Some(undone_at) = &entry.undone_at {
+        return Err(format!("Change {} was already undone at {}", change_id, undone_at));
+    }
+    
+    let connections = state.connections.lock().await;
+    let target = connections.get(&entry.connection_id).ok_or("The connection this change was made on is not open")?;
+    target.ensure_writable()?;
+    let result = match target {
+        DbConnection::Sql(c) => {
+            let conn = c.clone();
+            drop(connections);
+            row_edit::restore_sql_changes(&conn, &entry.table, &entry.inverse).await
+        }
+        DbConnection::Mongo(c) => {
+            let conn = c.clone();
+            drop(connections);
+            row_edit::apply_document_changes(&conn, &entry.table, &entry.inverse, false).await
+        }
+        DbConnection::Redis(_) => return Err("Undo is not supported for Redis connections".to_string()),
+    };
+    let (result, _) = result.map_err(|e| format!("Undo failed: {}", e))?;
+    
+    entry.undone_at = Some(chrono::Utc::now().to_rfc3339());
+    store.save(&entry).map_err(|e| e.to_string())?;
+    log::info!("Undid change {} ({} rows)", change_id, result.rows_affected);
+    Ok(result)
+}
+
 fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
+        .setup(|app| {
+            let data_dir = app.path().app_data_dir()?;
+            logging::init(app.path().app_log_dir()?, logging::load_config(&data_dir.join("logging.json")))?;
+            app.manage(HistoryStore::open(&data_dir.join("history.sqlite3"))?);
+            app.manage(SavedQueryLibrary::open(data_dir.join("library.json")));
+            app.manage(AuditLog::open(data_dir.join("audit").join("audit.jsonl")));
+            start_library_watcher(app.handle().clone());
+            Ok(())
+        })
         .manage(AppState {
             connections: Arc::new(Mutex::new(HashMap::new())),
+            exports: Arc::new(Mutex::new(HashMap::new())),
+            imports: Arc::new(Mutex::new(HashMap::new())),
+            monitors: Arc::new(Mutex::new(HashMap::new())),
         })
         .invoke_handler(tauri::generate_handler![
             load_connections,
@@ -238,9 +1514,59 @@ fn main() {
             get_connection,
             connect_database,
             disconnect_database,
+            pool_stats,
+            list_sessions,
+            cancel_session,
+            kill_session,
+            analyze_locks,
+            kill_root_blocker,
+            test_connection,
+            parse_connection_string,
+            connection_string,
+            list_driver_options,
             execute_query,
             get_schema,
             execute_ddl,
+            capture_schema_snapshot,
+            list_schema_snapshots,
+            get_schema_snapshot,
+            delete_schema_snapshot,
+            diff_schemas,
+            diff_table_data,
+            export_to_file,
+            cancel_export,
+            preview_columnar_file,
+            import_columnar_file,
+            preview_import_file,
+            import_file,
+            cancel_import,
+            preview_row_changes,
+            apply_row_changes,
+            search_undo_journal,
+            get_undo_entry,
+            undo_change,
+            get_library_root,
+            set_library_root,
+            list_saved_queries,
+            get_saved_query,
+            create_saved_query,
+            update_saved_query,
+            rename_saved_query,
+            move_saved_query,
+            delete_saved_query,
+            search_history,
+            clear_history,
+            dry_run_query,
+            analyze_impact,
+            check_statement_policy,
+            get_safety_policy,
+            save_safety_policy,
+            get_log_config,
+            set_log_config,
+            get_log_dir,
+            tail_log,
+            verify_audit_log,
+            export_audit_log,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,36 +1,235 @@
 use futures_util::stream::TryStreamExt;
 use mongodb::{Client, Database};
 use mongodb::options::ClientOptions;
-use crate::types::{ConnectionConfig, ColumnInfo, TableInfo, QueryRow};
+use crate::dsn;
+use crate::driver_options;
+use crate::ssh::{self, SshTunnel};
+use crate::tls::{self, TempPem};
+use crate::tls_relay::{self, ServerNameRelay};
+use crate::types::{ConnectionConfig, ColumnInfo, TableInfo, QueryRow, ResolvedLimits, TlsMode};
 
-#[derive(Debug)]
+/// Shell methods that never modify data. Anything else, including methods
+/// this client does not support yet, is treated as a write on read-only
+/// connections.
+const READ_METHODS: &[&str] = &[
+    "find", "findOne", "count", "countDocuments", "estimatedDocumentCount",
+    "distinct", "aggregate", "getIndexes", "stats", "explain",
+];
+
+pub fn shell_method(command: &str) -> &str {
+    command.split(['(', '.', ' ']).next().unwrap_or(command)
+}
+
+/// Whether a `collection.method(...)` command only reads. `aggregate` is a
+/// write when the pipeline ends in `$out` or `$merge`.
+pub fn is_read_command(command: &str) -> bool {
+    let method = shell_method(command);
+    if method == "aggregate" {
+        return !command.contains("$out") && !command.contains("$merge");
+    }
+    READ_METHODS.contains(&method)
+}
+
+#[derive(Debug, Clone)]
 pub struct MongoConnection {
     config: ConnectionConfig,
     client: Client,
     database: Database,
+    _tunnel: Option<std::sync::Arc<SshTunnel>>,
+    _client_pem: Option<std::sync::Arc<TempPem>>,
+    _relay: Option<std::sync::Arc<ServerNameRelay>>,
 }
 
 impl MongoConnection {
     pub async fn new(config: ConnectionConfig) -> Result<Self, Box<dyn std::error::Error>> {
-        let password = config.password.clone().unwrap_or_default();
+        driver_options::validate(&config)?;
+        let (host, port, tunnel) = ssh::connect_target(&config).await?;
+        let tls_config = config.tls_config();
+        let several_hosts = config.srv.unwrap_or(false) || host.contains(',');
+        if several_hosts && tls_relay::verification_name(&tls_config, &host).is_some() {
+            return Err("A TLS server name override needs a single host, not SRV lookup or a host list".into());
+        }
+        let relay = tls_relay::open("mongodb", &tls_config, &host, port).await?;
         
-        let connection_string = if config.username.is_empty() {
-            format!("mongodb://{}:{}/{}", config.host, config.port, config.database)
-        } else {
-            format!(
-                "mongodb://{}:{}@{}:{}/{}",
-                config.username, password, config.host, config.port, config.database
-            )
+        let connection_string = dsn::driver_url(&config, &host, port, &tls_config);
+        
+        let mut options = ClientOptions::parse(&connection_string).await?;
+        if tunnel.is_some() {
+            // Replica set members advertise addresses the tunnel does not
+            // forward; talk only to the tunnelled server.
+            options.direct_connection = Some(true);
+        }
+        let client_pem = match &relay {
+            // The relay does the TLS handshake; the driver talks to it in
+            // plaintext over its Unix socket.
+            Some(relay) => {
+                #[cfg(unix)]
+                {
+                    options.hosts = vec![mongodb::options::ServerAddress::Unix { path: relay.socket_path().to_path_buf() }];
+                }
+                #[cfg(not(unix))]
+                let _ = relay;
+                options.direct_connection = Some(true);
+                options.tls = Some(mongodb::options::Tls::Disabled);
+                None
+            }
+            None => {
+                let (tls_options, client_pem) = tls::mongo_options(&tls_config)?;
+                options.tls = Some(tls_options);
+                client_pem
+            }
+        };
+        let mut client = Client::with_options(options.clone())?;
+        let mut database = client.database(&config.database);
+        
+        // The driver connects lazily; ping so certificate problems surface
+        // here rather than on the first query.
+        if tls_config.mode != TlsMode::Disable {
+            let ping = database.run_command(mongodb::bson::doc! { "ping": 1 }, None).await;
+            match ping {
+                Ok(_) => {}
+                Err(e) if tls_config.mode == TlsMode::Prefer && relay.is_none() && tls::is_handshake_error(&e.to_string()) => {
+                    log::info!("TLS handshake with {} failed, retrying without TLS: {}", host, e);
+                    options.tls = Some(mongodb::options::Tls::Disabled);
+                    client = Client::with_options(options)?;
+                    database = client.database(&config.database);
+                    database.run_command(mongodb::bson::doc! { "ping": 1 }, None).await?;
+                }
+                Err(e) => return Err(tls::explain_error(&e.to_string(), &tls_config, &host).into()),
+            }
+        }
+        
+        Ok(Self {
+            config,
+            client,
+            database,
+            _tunnel: tunnel,
+            _client_pem: client_pem.map(std::sync::Arc::new),
+            _relay: relay,
+        })
+    }
+    
+    pub fn config(&self) -> &ConnectionConfig {
+        &self.config
+    }
+    
+    /// Opens a streaming cursor for `collection.find({...})`, or for a whole
+    /// collection when given just its name.
+    pub async fn find_cursor(&self, mql: &str) -> Result<mongodb::Cursor<mongodb::bson::Document>, Box<dyn std::error::Error>> {
+        let mql = mql.trim().trim_end_matches(';');
+        let (collection_name, filter_doc) = match mql.split_once('.') {
+            Some((collection, command)) if command.trim_start().starts_with("find(") => {
+                let filter_str = command.trim_start().trim_start_matches("find(").trim_end_matches(')').trim();
+                let filter_doc = if filter_str.is_empty() || filter_str == "{}" {
+                    mongodb::bson::Document::new()
+                } else {
+                    serde_json::from_str::<mongodb::bson::Document>(filter_str)?
+                };
+                (collection, filter_doc)
+            }
+            Some(_) => return Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Only collection.find(...) can be streamed"
+            ))),
+            None => (mql, mongodb::bson::Document::new()),
         };
         
-        let options = ClientOptions::parse(&connection_string).await?;
-        let client = Client::with_options(options)?;
-        let database = client.database(&config.database);
+        let coll = self.database.collection::<mongodb::bson::Document>(collection_name);
+        Ok(coll.find(filter_doc, None).await?)
+    }
+    
+    pub async fn insert_documents(
+        &self,
+        collection_name: &str,
+        documents: Vec<serde_json::Map<String, serde_json::Value>>,
+    ) -> Result<u64, Box<dyn std::error::Error>> {
+        if documents.is_empty() {
+            return Ok(0);
+        }
+        
+        let mut docs = Vec::with_capacity(documents.len());
+        for document in &documents {
+            docs.push(mongodb::bson::to_document(document)?);
+        }
+        self.insert_bson_documents(collection_name, docs).await
+    }
+    
+    pub async fn insert_bson_documents(
+        &self,
+        collection_name: &str,
+        documents: Vec<mongodb::bson::Document>,
+    ) -> Result<u64, Box<dyn std::error::Error>> {
+        if documents.is_empty() {
+            return Ok(0);
+        }
+        
+        let coll = self.database.collection::<mongodb::bson::Document>(collection_name);
+        let result = coll.insert_many(documents, None).await?;
+        Ok(result.inserted_ids.len() as u64)
+    }
+    
+    pub async fn find_documents(
+        &self,
+        collection_name: &str,
+        filter: mongodb::bson::Document,
+    ) -> Result<Vec<mongodb::bson::Document>, Box<dyn std::error::Error>> {
+        let coll = self.database.collection::<mongodb::bson::Document>(collection_name);
+        let cursor = coll.find(filter, None).await?;
+        Ok(cursor.try_collect().await?)
+    }
+    
+    /// Applies single-document writes in order. Replica sets and sharded
+    /// clusters run them in one transaction; a standalone server cannot, so
+    /// the writes are applied one by one and the second tuple field is false.
+    /// Updates, replacements and deletes must match exactly one document.
+    pub async fn apply_document_writes(
+        &self,
+        collection_name: &str,
+        writes: &[DocumentWrite],
+    ) -> Result<(u64, bool), Box<dyn std::error::Error>> {
+        let coll = self.database.collection::<mongodb::bson::Document>(collection_name);
+        let hello = self.database.run_command(mongodb::bson::doc! { "hello": 1 }, None).await?;
+        let supports_transactions = hello.contains_key("setName") || hello.get_str("msg") == Ok("isdbgrid");
+        
+        if !supports_transactions {
+            for (index, write) in writes.iter().enumerate() {
+                apply_write(&coll, write, None).await.map_err(|e| {
+                    format!("Write {} failed after {} of {} writes were applied: {}", index + 1, index, writes.len(), e)
+                })?;
+            }
+            return Ok((writes.len() as u64, false));
+        }
         
-        Ok(Self { config, client, database })
+        let mut session = self.client.start_session(None).await?;
+        session.start_transaction(None).await?;
+        for (index, write) in writes.iter().enumerate() {
+            if let Err(e) = apply_write(&coll, write, Some(&mut session)).await {
+                let _ = session.abort_transaction().await;
+                return Err(format!("Write {} failed, no changes were applied: {}", index + 1, e).into());
+            }
+        }
+        session.commit_transaction().await?;
+        Ok((writes.len() as u64, true))
     }
     
     pub async fn execute_mql(&self, mql: &str) -> Result<QueryRow, Box<dyn std::error::Error>> {
+        Ok(self.execute_mql_limited(mql, &ResolvedLimits::default()).await?.0)
+    }
+    
+    /// `execute_mql` with `maxTimeMS` and a document cap; also returns
+    /// whether documents were left unread at the cap.
+    pub async fn execute_mql_limited(&self, mql: &str, limits: &ResolvedLimits) -> Result<(QueryRow, bool), Box<dyn std::error::Error>> {
+        match limits.timeout {
+            // maxTimeMS normally ends the operation first; this covers
+            // server selection and slow networks.
+            Some(timeout) => tokio::time::timeout(timeout + std::time::Duration::from_secs(1), self.run_mql(mql, limits))
+                .await
+                .map_err(|_| format!("Query cancelled after exceeding the {} ms timeout", timeout.as_millis()))?,
+            None => self.run_mql(mql, limits).await,
+        }
+    }
+    
+    async fn run_mql(&self, mql: &str, limits: &ResolvedLimits) -> Result<(QueryRow, bool), Box<dyn std::error::Error>> {
         let start = std::time::Instant::now();
         
         let parts: Vec<&str> = mql.splitn(2, '.').collect();
@@ -44,8 +243,16 @@ impl MongoConnection {
         let collection_name = parts[0];
         let command = parts[1].trim_start();
         
+        if self.config.is_read_only() && !is_read_command(command) {
+            return Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("Connection is read-only; refusing {}", shell_method(command))
+            )));
+        }
+        
         let coll = self.database.collection(collection_name);
         
+        let mut truncated = false;
         let result: Result<QueryRow, Box<dyn std::error::Error>> = if command.starts_with("find(") {
             let filter_str = command.trim_start_matches("find(").trim_end_matches(')');
             let filter_doc = if filter_str.is_empty() || filter_str == "{}" {
@@ -54,13 +261,22 @@ impl MongoConnection {
                 serde_json::from_str::<mongodb::bson::Document>(filter_str)?
             };
             
-            let mut cursor = coll.find(filter_doc, None).await?;
+            // One document past the cap tells whether there were more.
+            let options = mongodb::options::FindOptions::builder()
+                .max_time(limits.timeout)
+                .limit(limits.max_rows.map(|max| max as i64 + 1))
+                .build();
+            let mut cursor = coll.find(filter_doc, options).await?;
             
             let mut columns = vec!["_id".to_string()];
             let mut types = vec!["ObjectId".to_string()];
             let mut rows = Vec::new();
             
             while let Some(doc_result) = cursor.try_next().await? {
+                if limits.max_rows.is_some_and(|max| rows.len() >= max) {
+                    truncated = true;
+                    break;
+                }
                 let doc: mongodb::bson::Document = doc_result;
                 let id = doc.get_object_id("_id")
                     .map(|oid| oid.to_hex())
@@ -95,7 +311,8 @@ impl MongoConnection {
                 rows,
             })
         } else if command.starts_with("count()") {
-            let count = coll.count_documents(mongodb::bson::Document::new(), None).await?;
+            let options = mongodb::options::CountOptions::builder().max_time(limits.timeout).build();
+            let count = coll.count_documents(mongodb::bson::Document::new(), options).await?;
             
             Ok(QueryRow {
                 columns: vec!["count".to_string()],
@@ -110,11 +327,31 @@ impl MongoConnection {
         };
         
         let execution_time = start.elapsed();
-        println!("MQL executed in {:?}", execution_time);
+        log::debug!("MQL executed in {:?}", execution_time);
         
-        result
+        Ok((result?, truncated))
     }
     
+    /// Round trip to the server; authenticates if the driver has not yet.
+    pub async fn ping(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.database.run_command(mongodb::bson::doc! { "ping": 1 }, None).await?;
+        Ok(())
+    }
+
+    /// Runs a command against the `admin` database.
+    pub async fn admin_command(&self, command: mongodb::bson::Document) -> Result<mongodb::bson::Document, Box<dyn std::error::Error>> {
+        Ok(self.client.database("admin").run_command(command, None).await?)
+    }
+
+    pub async fn server_version(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let info = self.database.run_command(mongodb::bson::doc! { "buildInfo": 1 }, None).await?;
+        Ok(info.get_str("version")?.to_string())
+    }
+
+    pub async fn collection_count(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        Ok(self.database.list_collection_names(None).await?.len())
+    }
+
     pub async fn get_collections(&self) -> Result<Vec<TableInfo>, Box<dyn std::error::Error>> {
         let cursor = self.database.list_collection_names(None).await?;
         
@@ -145,7 +382,90 @@ impl MongoConnection {
     }
 }
 
-fn convert_bson_to_json(doc: &mongodb::bson::Document) -> serde_json::Value {
+/// A single-document write, as produced by the result grid editor.
+#[derive(Debug, Clone)]
+pub enum DocumentWrite {
+    Insert(mongodb::bson::Document),
+    Update { filter: mongodb::bson::Document, update: mongodb::bson::Document },
+    Replace { filter: mongodb::bson::Document, replacement: mongodb::bson::Document },
+    Delete { filter: mongodb::bson::Document },
+}
+
+impl DocumentWrite {
+    /// Shell syntax of the write, for previews.
+    pub fn to_shell(&self, collection: &str) -> String {
+        let json = |doc: &mongodb::bson::Document| {
+            mongodb::bson::Bson::Document(doc.clone()).into_relaxed_extjson().to_string()
+        };
+        match self {
+            DocumentWrite::Insert(doc) => format!("db.{}.insertOne({})", collection, json(doc)),
+            DocumentWrite::Update { filter, update } => {
+                format!("db.{}.updateOne({}, {})", collection, json(filter), json(update))
+            }
+            DocumentWrite::Replace { filter, replacement } => {
+                format!("db.{}.replaceOne({}, {})", collection, json(filter), json(replacement))
+            }
+            DocumentWrite::Delete { filter } => format!("db.{}.deleteOne({})", collection, json(filter)),
+        }
+    }
+}
+
+async fn apply_write(
+    coll: &mongodb::Collection<mongodb::bson::Document>,
+    write: &DocumentWrite,
+    session: Option<&mut mongodb::ClientSession>,
+) -> Result<(), String> {
+    let matched = match (write, session) {
+        (DocumentWrite::Insert(doc), Some(session)) => {
+            coll.insert_one_with_session(doc, None, session).await.map_err(|e| e.to_string())?;
+            return Ok(());
+        }
+        (DocumentWrite::Insert(doc), None) => {
+            coll.insert_one(doc, None).await.map_err(|e| e.to_string())?;
+            return Ok(());
+        }
+        (DocumentWrite::Update { filter, update }, Some(session)) => coll
+            .update_one_with_session(filter.clone(), update.clone(), None, session)
+            .await
+            .map_err(|e| e.to_string())?
+            .matched_count,
+        (DocumentWrite::Update { filter, update }, None) => coll
+            .update_one(filter.clone(), update.clone(), None)
+            .await
+            .map_err(|e| e.to_string())?
+            .matched_count,
+        (DocumentWrite::Replace { filter, replacement }, Some(session)) => coll
+            .replace_one_with_session(filter.clone(), replacement, None, session)
+            .await
+            .map_err(|e| e.to_string())?
+            .matched_count,
+        (DocumentWrite::Replace { filter, replacement }, None) => coll
+            .replace_one(filter.clone(), replacement, None)
+            .await
+            .map_err(|e| e.to_string())?
+            .matched_count,
+        (DocumentWrite::Delete { filter }, Some(session)) => coll
+            .delete_one_with_session(filter.clone(), None, session)
+            .await
+            .map_err(|e| e.to_string())?
+            .deleted_count,
+        (DocumentWrite::Delete { filter }, None) => coll
+            .delete_one(filter.clone(), None)
+            .await
+            .map_err(|e| e.to_string())?
+            .deleted_count,
+    };
+    
+    if matched != 1 {
+        return Err(format!(
+            "matched {} documents instead of 1; it may have been changed or removed by someone else",
+            matched
+        ));
+    }
+    Ok(())
+}
+
+pub fn convert_bson_to_json(doc: &mongodb::bson::Document) -> serde_json::Value {
     let mut obj = serde_json::Map::new();
     for (key, value) in doc {
         obj.insert(key.clone(), convert_bson_value(value));
@@ -170,7 +490,7 @@ fn convert_bson_value(value: &mongodb::bson::Bson) -> serde_json::Value {
     }
 }
 
-fn mongo_type_to_string(value: &serde_json::Value) -> String {
+pub fn mongo_type_to_string(value: &serde_json::Value) -> String {
     match value {
         serde_json::Value::Null => "Null".to_string(),
         serde_json::Value::Bool(_) => "Boolean".to_string(),
@@ -0,0 +1,584 @@
+//! Safety policy: rules evaluated against a classified statement before it is
+//! sent to the server. Classification works on tokens, so comments, string
+//! literals and CTE wrappers cannot hide a `DROP` or an unfiltered `DELETE`.
+
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use crate::snapshot::safe_file_component;
+use crate::statement::{self, Token};
+use crate::types::ConnectionConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationCategory {
+    Read,
+    /// Changes rows, documents or keys.
+    Write,
+    /// Creates, alters or drops objects.
+    Schema,
+    /// Permissions, server settings and anything unrecognized.
+    Admin,
+}
+
+/// One operation found in the submitted text. A script yields one per
+/// statement, plus one per data-modifying CTE.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Operation {
+    /// Leading keyword (`DROP`), shell method (`dropDatabase`) or Redis command.
+    pub verb: String,
+    pub category: OperationCategory,
+    /// `UPDATE`/`DELETE` with no `WHERE`, or a trivially true one.
+    pub missing_where: bool,
+    pub statement: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyAction {
+    Allow,
+    Confirm,
+    /// The user must type the connection name.
+    TypedConfirm,
+    Block,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyRule {
+    pub name: String,
+    pub action: PolicyAction,
+    /// Database types the rule applies to; `sql` stands for every SQL
+    /// engine. Empty matches all.
+    #[serde(default)]
+    pub engines: Vec<String>,
+    /// Connection tags, any of which must be present. Empty matches all.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Verbs, compared case-insensitively. Empty matches all.
+    #[serde(default)]
+    pub operations: Vec<String>,
+    /// Empty matches all.
+    #[serde(default)]
+    pub categories: Vec<OperationCategory>,
+    /// Only match `UPDATE`/`DELETE` statements without a real `WHERE`.
+    #[serde(default)]
+    pub without_where: bool,
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SafetyPolicy {
+    pub rules: Vec<PolicyRule>,
+    /// Per-connection policies add to the global rules unless this is false.
+    #[serde(default = "default_inherit_global")]
+    pub inherit_global: bool,
+}
+
+fn default_inherit_global() -> bool {
+    true
+}
+
+impl Default for SafetyPolicy {
+    /// The rules in effect until a global policy file is saved.
+    fn default() -> Self {
+        Self {
+            rules: vec![
+                PolicyRule {
+                    name: "confirm-drop-truncate".to_string(),
+                    action: PolicyAction::Confirm,
+                    engines: vec!["sql".to_string()],
+                    tags: Vec::new(),
+                    operations: vec!["DROP".to_string(), "TRUNCATE".to_string()],
+                    categories: Vec::new(),
+                    without_where: false,
+                    message: Some("Dropping or truncating permanently removes data".to_string()),
+                },
+                PolicyRule {
+                    name: "block-unfiltered-dml".to_string(),
+                    action: PolicyAction::Block,
+                    engines: vec!["sql".to_string()],
+                    tags: Vec::new(),
+                    operations: vec!["UPDATE".to_string(), "DELETE".to_string()],
+                    categories: Vec::new(),
+                    without_where: true,
+                    message: Some("UPDATE and DELETE need a WHERE clause".to_string()),
+                },
+                PolicyRule {
+                    name: "production-changes".to_string(),
+                    action: PolicyAction::TypedConfirm,
+                    engines: Vec::new(),
+                    tags: vec!["production".to_string()],
+                    operations: Vec::new(),
+                    categories: vec![OperationCategory::Write, OperationCategory::Schema, OperationCategory::Admin],
+                    without_where: false,
+                    message: Some("This connection is tagged production".to_string()),
+                },
+                PolicyRule {
+                    name: "redis-flush".to_string(),
+                    action: PolicyAction::TypedConfirm,
+                    engines: vec!["redis".to_string()],
+                    tags: Vec::new(),
+                    operations: vec!["FLUSHALL".to_string(), "FLUSHDB".to_string()],
+                    categories: Vec::new(),
+                    without_where: false,
+                    message: Some("Flushing deletes every key".to_string()),
+                },
+                PolicyRule {
+                    name: "mongo-drop-database".to_string(),
+                    action: PolicyAction::Block,
+                    engines: vec!["mongodb".to_string()],
+                    tags: Vec::new(),
+                    operations: vec!["dropDatabase".to_string()],
+                    categories: Vec::new(),
+                    without_where: false,
+                    message: Some("Dropping a database is not allowed from the app".to_string()),
+                },
+            ],
+            inherit_global: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyViolation {
+    pub rule: String,
+    pub action: PolicyAction,
+    pub verb: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyDecision {
+    /// Strictest action among the matching rules.
+    pub action: PolicyAction,
+    pub operations: Vec<Operation>,
+    pub violations: Vec<PolicyViolation>,
+    /// Text the user must type for `typed_confirm`.
+    pub confirmation_phrase: Option<String>,
+}
+
+impl PolicyDecision {
+    /// Checks the confirmation sent along with the statement. Any value
+    /// satisfies `confirm`; `typed_confirm` needs the exact phrase.
+    pub fn check_confirmation(&self, confirmation: Option<&str>) -> Result<(), String> {
+        let reasons = self.violations.iter()
+            .map(|v| format!("{} ({})", v.message, v.rule))
+            .collect::<Vec<_>>()
+            .join("; ");
+        match self.action {
+            PolicyAction::Allow => Ok(()),
+            PolicyAction::Block => Err(format!("Blocked by safety policy: {}", reasons)),
+            PolicyAction::Confirm if confirmation.is_some() => Ok(()),
+            PolicyAction::Confirm => Err(format!("Confirmation required by safety policy: {}", reasons)),
+            PolicyAction::TypedConfirm => {
+                let phrase = self.confirmation_phrase.as_deref().unwrap_or_default();
+                if confirmation == Some(phrase) {
+                    Ok(())
+                } else {
+                    Err(format!("Type \"{}\" to confirm: {}", phrase, reasons))
+                }
+            }
+        }
+    }
+}
+
+/// Evaluates the effective rules against every operation in `text`.
+pub fn evaluate(rules: &[PolicyRule], config: &ConnectionConfig, text: &str) -> PolicyDecision {
+    let operations = classify(&config.r#type, text);
+    let tags = config.tags.as_deref().unwrap_or_default();
+
+    let mut violations = Vec::new();
+    for operation in &operations {
+        for rule in rules {
+            if rule_matches(rule, &config.r#type, tags, operation) {
+                violations.push(PolicyViolation {
+                    rule: rule.name.clone(),
+                    action: rule.action,
+                    verb: operation.verb.clone(),
+                    message: rule.message.clone()
+                        .unwrap_or_else(|| format!("{} matches rule {}", operation.verb, rule.name)),
+                });
+            }
+        }
+    }
+
+    let action = violations.iter().map(|v| v.action).max().unwrap_or(PolicyAction::Allow);
+    PolicyDecision {
+        action,
+        operations,
+        violations,
+        confirmation_phrase: (action == PolicyAction::TypedConfirm).then(|| config.name.clone()),
+    }
+}
+
+/// The first operation in `text` a read-only connection must refuse: any
+/// non-read, and transaction starts that ask for `READ WRITE`. `SET
+/// TRANSACTION` and `SET SESSION CHARACTERISTICS` are not reads, so they
+/// cannot switch the session back either.
+pub fn read_only_violation(db_type: &str, text: &str) -> Option<Operation> {
+    classify(db_type, text).into_iter().find(|operation| {
+        if operation.category != OperationCategory::Read {
+            return true;
+        }
+        if !matches!(operation.verb.as_str(), "BEGIN" | "START") {
+            return false;
+        }
+        let tokens = statement::tokenize(db_type, &operation.statement);
+        tokens.windows(2).any(|pair| {
+            pair[0].is_keyword(&operation.statement, "READ") && pair[1].is_keyword(&operation.statement, "WRITE")
+        })
+    })
+}
+
+fn rule_matches(rule: &PolicyRule, db_type: &str, tags: &[String], operation: &Operation) -> bool {
+    let engine = rule.engines.is_empty() || rule.engines.iter().any(|e| {
+        e.eq_ignore_ascii_case(db_type) || e.eq_ignore_ascii_case("sql") && is_sql(db_type)
+    });
+    let tagged = rule.tags.is_empty() || rule.tags.iter().any(|t| tags.iter().any(|c| c.eq_ignore_ascii_case(t)));
+    let verb = rule.operations.is_empty() || rule.operations.iter().any(|o| o.eq_ignore_ascii_case(&operation.verb));
+    let category = rule.categories.is_empty() || rule.categories.contains(&operation.category);
+    engine && tagged && verb && category && (!rule.without_where || operation.missing_where)
+}
+
+fn is_sql(db_type: &str) -> bool {
+    matches!(db_type, "sqlite" | "postgresql" | "mysql" | "mariadb")
+}
+
+/// Splits `text` into operations in the connection's query language.
+pub fn classify(db_type: &str, text: &str) -> Vec<Operation> {
+    match db_type {
+        "mongodb" => classify_mongo(text).into_iter().collect(),
+        "redis" => classify_redis(text).into_iter().collect(),
+        _ => {
+            let tokens = statement::tokenize(db_type, text);
+            let mut operations = Vec::new();
+            for tokens in tokens.split(|t| t.is_symbol(text, ";")).filter(|s| !s.is_empty()) {
+                classify_sql(text, tokens, &mut operations);
+            }
+            operations
+        }
+    }
+}
+
+const READ_VERBS: &[&str] = &[
+    "SELECT", "SHOW", "DESCRIBE", "DESC", "VALUES", "TABLE", "EXPLAIN", "FETCH",
+    "BEGIN", "START", "COMMIT", "ROLLBACK", "SAVEPOINT", "RELEASE", "END", "USE",
+];
+const WRITE_VERBS: &[&str] = &[
+    "INSERT", "UPDATE", "DELETE", "MERGE", "REPLACE", "UPSERT", "LOAD", "CALL", "DO", "EXEC", "EXECUTE",
+];
+const SCHEMA_VERBS: &[&str] = &["CREATE", "ALTER", "DROP", "TRUNCATE", "RENAME", "COMMENT"];
+
+fn classify_sql(sql: &str, tokens: &[Token], operations: &mut Vec<Operation>) {
+    let statement = sql[tokens[0].start..tokens[tokens.len() - 1].end].to_string();
+    let mut i = 0;
+    while tokens.get(i).is_some_and(|t| t.is_symbol(sql, "(")) {
+        i += 1;
+    }
+
+    // WITH [RECURSIVE] name [(columns)] AS [NOT] [MATERIALIZED] (body), ...
+    // Bodies are classified on their own: Postgres allows DML inside them.
+    if tokens.get(i).is_some_and(|t| t.is_keyword(sql, "WITH")) {
+        i += 1;
+        if tokens.get(i).is_some_and(|t| t.is_keyword(sql, "RECURSIVE")) {
+            i += 1;
+        }
+        loop {
+            i += 1;
+            if tokens.get(i).is_some_and(|t| t.is_symbol(sql, "(")) {
+                i = matching_paren(sql, tokens, i) + 1;
+            }
+            while tokens.get(i).is_some_and(|t| ["AS", "NOT", "MATERIALIZED"].iter().any(|k| t.is_keyword(sql, k))) {
+                i += 1;
+            }
+            if !tokens.get(i).is_some_and(|t| t.is_symbol(sql, "(")) {
+                break;
+            }
+            let close = matching_paren(sql, tokens, i);
+            if close > i + 1 {
+                classify_sql(sql, &tokens[i + 1..close], operations);
+            }
+            i = close + 1;
+            if !tokens.get(i).is_some_and(|t| t.is_symbol(sql, ",")) {
+                break;
+            }
+        }
+    }
+
+    let Some(verb_token) = tokens.get(i) else { return };
+    let verb = verb_token.text(sql).to_uppercase();
+
+    // EXPLAIN ANALYZE runs the statement it explains.
+    if verb == "EXPLAIN" {
+        let mut j = i + 1;
+        let mut analyze = false;
+        while let Some(token) = tokens.get(j) {
+            if token.is_symbol(sql, "(") {
+                let close = matching_paren(sql, tokens, j);
+                analyze |= tokens[j..close].iter().any(|t| t.is_keyword(sql, "ANALYZE"));
+                j = close + 1;
+            } else if token.is_keyword(sql, "ANALYZE") || token.is_keyword(sql, "ANALYSE") {
+                analyze = true;
+                j += 1;
+            } else if token.is_keyword(sql, "VERBOSE") || token.kind != statement::TokenKind::Word {
+                j += 1;
+            } else {
+                break;
+            }
+        }
+        if analyze && j < tokens.len() {
+            classify_sql(sql, &tokens[j..], operations);
+            return;
+        }
+    }
+
+    let category = if verb == "SELECT" && statement::find_top_level_keyword(sql, tokens, i + 1, "INTO").is_some() {
+        // SELECT ... INTO creates a table on Postgres and writes variables
+        // or files on MySQL.
+        OperationCategory::Write
+    } else if verb == "COPY" {
+        if statement::find_top_level_keyword(sql, tokens, i + 1, "FROM").is_some() {
+            OperationCategory::Write
+        } else {
+            OperationCategory::Read
+        }
+    } else if verb == "PRAGMA" {
+        if tokens[i..].iter().any(|t| t.is_symbol(sql, "=") || t.is_symbol(sql, "(")) {
+            OperationCategory::Admin
+        } else {
+            OperationCategory::Read
+        }
+    } else if READ_VERBS.contains(&verb.as_str()) {
+        OperationCategory::Read
+    } else if WRITE_VERBS.contains(&verb.as_str()) {
+        OperationCategory::Write
+    } else if SCHEMA_VERBS.contains(&verb.as_str()) {
+        OperationCategory::Schema
+    } else {
+        OperationCategory::Admin
+    };
+
+    let missing_where = (verb == "UPDATE" || verb == "DELETE") && !has_real_where(sql, tokens, i + 1);
+    operations.push(Operation { verb, category, missing_where, statement });
+}
+
+/// A top-level `WHERE` whose predicate is not a constant such as `TRUE`,
+/// `1` or `1 = 1`.
+fn has_real_where(sql: &str, tokens: &[Token], from: usize) -> bool {
+    let Some(index) = statement::find_top_level_keyword(sql, tokens, from, "WHERE") else {
+        return false;
+    };
+    let end = ["RETURNING", "ORDER", "LIMIT"].iter()
+        .filter_map(|k| statement::find_top_level_keyword(sql, tokens, index + 1, k))
+        .min()
+        .unwrap_or(tokens.len());
+    let predicate: Vec<String> = tokens[index + 1..end].iter()
+        .filter(|t| !t.is_symbol(sql, "(") && !t.is_symbol(sql, ")"))
+        .map(|t| t.text(sql).to_uppercase())
+        .collect();
+    let trivially_true = match predicate.as_slice() {
+        [] => true,
+        [value] => value == "TRUE" || value == "1",
+        [left, op, right] => op == "=" && left == right && !left.starts_with(|c: char| c.is_alphabetic() || c == '_'),
+        _ => false,
+    };
+    !trivially_true
+}
+
+fn matching_paren(sql: &str, tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate().skip(open) {
+        if token.is_symbol(sql, "(") {
+            depth += 1;
+        } else if token.is_symbol(sql, ")") {
+            depth -= 1;
+            if depth == 0 {
+                return index;
+            }
+        }
+    }
+    tokens.len()
+}
+
+const MONGO_SCHEMA_METHODS: &[&str] = &[
+    "drop", "dropDatabase", "createCollection", "renameCollection",
+    "createIndex", "createIndexes", "dropIndex", "dropIndexes",
+];
+
+/// `collection.method(...)`, the form `execute_mql` accepts.
+fn classify_mongo(text: &str) -> Option<Operation> {
+    let command = text.trim().split_once('.')?.1.trim_start();
+    let verb = crate::mongodb::shell_method(command).to_string();
+    let category = if crate::mongodb::is_read_command(command) {
+        OperationCategory::Read
+    } else if MONGO_SCHEMA_METHODS.contains(&verb.as_str()) {
+        OperationCategory::Schema
+    } else {
+        OperationCategory::Write
+    };
+    Some(Operation { verb, category, missing_where: false, statement: text.trim().to_string() })
+}
+
+const REDIS_ADMIN_COMMANDS: &[&str] = &[
+    "FLUSHALL", "FLUSHDB", "CONFIG", "SHUTDOWN", "DEBUG", "SCRIPT", "FUNCTION", "ACL",
+    "CLIENT", "REPLICAOF", "SLAVEOF", "MIGRATE", "SWAPDB", "MODULE", "SAVE", "BGSAVE",
+    "BGREWRITEAOF", "FAILOVER",
+];
+
+fn classify_redis(text: &str) -> Option<Operation> {
+    let verb = text.split_whitespace().next()?.to_uppercase();
+    let category = if crate::redis::is_read_command(&verb) {
+        OperationCategory::Read
+    } else if REDIS_ADMIN_COMMANDS.contains(&verb.as_str()) {
+        OperationCategory::Admin
+    } else {
+        OperationCategory::Write
+    };
+    Some(Operation { verb, category, missing_where: false, statement: text.trim().to_string() })
+}
+
+/// Stores the global policy as `<root>/global.json` and per-connection
+/// policies as `<root>/connections/<connection_id>.json`.
+pub struct PolicyStore {
+    root: PathBuf,
+}
+
+impl PolicyStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn path(&self, connection_id: Option<&str>) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        Ok(match connection_id {
+            Some(id) => self.root.join("connections").join(format!("{}.json", safe_file_component(id)?)),
+            None => self.root.join("global.json"),
+        })
+    }
+
+    /// The stored policy, the built-in defaults for a missing global file,
+    /// or `None` for a connection without its own file.
+    pub fn load(&self, connection_id: Option<&str>) -> Result<Option<SafetyPolicy>, Box<dyn std::error::Error>> {
+        let path = self.path(connection_id)?;
+        if !path.exists() {
+            return Ok(connection_id.is_none().then(SafetyPolicy::default));
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        Ok(Some(serde_json::from_str(&contents)?))
+    }
+
+    pub fn save(&self, connection_id: Option<&str>, policy: &SafetyPolicy) -> Result<(), Box<dyn std::error::Error>> {
+        let path = self.path(connection_id)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(policy)?)?;
+        Ok(())
+    }
+
+    /// Connection rules first, then the global ones unless the connection
+    /// opts out.
+    pub fn effective_rules(&self, connection_id: &str) -> Result<Vec<PolicyRule>, Box<dyn std::error::Error>> {
+        let mut rules = Vec::new();
+        let inherit = match self.load(Some(connection_id))? {
+            Some(policy) => {
+                rules.extend(policy.rules);
+                policy.inherit_global
+            }
+            None => true,
+        };
+        if inherit {
+            rules.extend(self.load(None)?.unwrap_or_default().rules);
+        }
+        Ok(rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verbs(db_type: &str, text: &str) -> Vec<(String, OperationCategory)> {
+        classify(db_type, text).into_iter().map(|o| (o.verb, o.category)).collect()
+    }
+
+    fn violation(db_type: &str, text: &str) -> Option<String> {
+        read_only_violation(db_type, text).map(|o| o.verb)
+    }
+
+    fn config(db_type: &str) -> ConnectionConfig {
+        serde_json::from_value(serde_json::json!({
+            "id": "c1",
+            "name": "prod",
+            "type": db_type,
+            "host": "localhost",
+            "port": 5432,
+            "database": "app",
+            "username": "app",
+        }))
+        .unwrap()
+    }
+
+    fn block_drops() -> Vec<PolicyRule> {
+        vec![PolicyRule {
+            name: "no-drop".to_string(),
+            action: PolicyAction::Block,
+            engines: Vec::new(),
+            tags: Vec::new(),
+            operations: vec!["DROP".to_string()],
+            categories: Vec::new(),
+            without_where: false,
+            message: None,
+        }]
+    }
+
+    #[test]
+    fn leading_comments_do_not_hide_the_verb() {
+        assert_eq!(verbs("postgresql", "/* report */ DROP TABLE t"), [("DROP".to_string(), OperationCategory::Schema)]);
+        assert_eq!(verbs("postgresql", "-- SELECT\nDELETE FROM t"), [("DELETE".to_string(), OperationCategory::Write)]);
+        assert_eq!(violation("postgresql", "/* DELETE */ SELECT 1"), None);
+        assert_eq!(violation("postgresql", "-- SELECT 1\nDELETE FROM t WHERE id = 1").as_deref(), Some("DELETE"));
+    }
+
+    #[test]
+    fn ctes_are_classified_by_what_they_do() {
+        let operations = classify("postgresql", "WITH x AS (SELECT id FROM t) DELETE FROM t WHERE id IN (SELECT id FROM x)");
+        assert!(operations.iter().any(|o| o.verb == "DELETE" && o.category == OperationCategory::Write));
+        assert_eq!(violation("postgresql", "WITH x AS (SELECT 1) DELETE FROM t").as_deref(), Some("DELETE"));
+        assert!(classify("postgresql", "WITH x AS (SELECT 1) DELETE FROM t").iter().any(|o| o.missing_where));
+        assert_eq!(violation("postgresql", "WITH gone AS (DELETE FROM t RETURNING *) SELECT * FROM gone").as_deref(), Some("DELETE"));
+        assert_eq!(violation("postgresql", "WITH x AS (SELECT 1) SELECT * FROM x"), None);
+    }
+
+    #[test]
+    fn every_statement_of_a_script_is_checked() {
+        assert_eq!(
+            verbs("postgresql", "SELECT 1; DROP TABLE t"),
+            [("SELECT".to_string(), OperationCategory::Read), ("DROP".to_string(), OperationCategory::Schema)]
+        );
+        assert_eq!(violation("mysql", "SELECT 1; DROP TABLE t").as_deref(), Some("DROP"));
+        assert_eq!(evaluate(&block_drops(), &config("postgresql"), "SELECT 1; DROP TABLE t").action, PolicyAction::Block);
+        assert_eq!(evaluate(&block_drops(), &config("postgresql"), "-- cleanup\n/* ok */ DROP TABLE t").action, PolicyAction::Block);
+    }
+
+    #[test]
+    fn quoted_text_is_not_a_statement() {
+        assert_eq!(violation("postgresql", "SELECT $$; DROP TABLE t; $$"), None);
+        assert_eq!(violation("postgresql", "SELECT $q$ DROP TABLE t $q$"), None);
+        assert_eq!(violation("mysql", "SELECT `; DROP TABLE t` FROM x"), None);
+        assert_eq!(violation("sqlite", "SELECT '; DROP TABLE t'"), None);
+        assert_eq!(evaluate(&block_drops(), &config("postgresql"), "SELECT 'DROP TABLE t'").action, PolicyAction::Allow);
+    }
+
+    #[test]
+    fn mysql_executable_comments_are_classified() {
+        assert_eq!(violation("mysql", "/*! DROP TABLE t */").as_deref(), Some("DROP"));
+        assert_eq!(violation("mysql", "SELECT 1 /*! ; DELETE FROM t */").as_deref(), Some("DELETE"));
+        assert_eq!(evaluate(&block_drops(), &config("mysql"), "/*!50700 DROP TABLE t */").action, PolicyAction::Block);
+    }
+
+    #[test]
+    fn read_write_transactions_are_refused_on_read_only_connections() {
+        assert_eq!(violation("postgresql", "BEGIN READ WRITE").as_deref(), Some("BEGIN"));
+        assert_eq!(violation("postgresql", "BEGIN READ ONLY"), None);
+    }
+}
@@ -1,112 +1,166 @@
+use redis::aio::MultiplexedConnection;
 use redis::Client;
-use crate::types::{ConnectionConfig, ColumnInfo, TableInfo, QueryRow};
+use crate::dsn;
+use crate::driver_options;
+use crate::ssh::{self, SshTunnel};
+use crate::tls;
+use crate::tls_relay::{self, ServerNameRelay};
+use crate::types::{ConnectionConfig, ColumnInfo, TableInfo, QueryRow, ResolvedLimits, TlsConfig, TlsMode};
 
-#[derive(Debug)]
+/// Commands that never modify the keyspace or server state. Unknown
+/// commands are treated as writes on read-only connections.
+const READ_COMMANDS: &[&str] = &[
+    "GET", "MGET", "GETRANGE", "STRLEN", "EXISTS", "TYPE", "TTL", "PTTL",
+    "EXPIRETIME", "PEXPIRETIME", "KEYS", "SCAN", "RANDOMKEY", "DBSIZE",
+    "DUMP", "OBJECT", "TOUCH",
+    "HGET", "HMGET", "HGETALL", "HKEYS", "HVALS", "HLEN", "HEXISTS", "HSTRLEN",
+    "HSCAN", "HRANDFIELD",
+    "LRANGE", "LLEN", "LINDEX", "LPOS",
+    "SMEMBERS", "SISMEMBER", "SMISMEMBER", "SCARD", "SRANDMEMBER", "SSCAN",
+    "SINTER", "SUNION", "SDIFF", "SINTERCARD",
+    "ZRANGE", "ZRANGEBYSCORE", "ZRANGEBYLEX", "ZREVRANGE", "ZREVRANGEBYSCORE",
+    "ZREVRANGEBYLEX", "ZCARD", "ZCOUNT", "ZLEXCOUNT", "ZSCORE", "ZMSCORE",
+    "ZRANK", "ZREVRANK", "ZSCAN", "ZRANDMEMBER", "ZINTER", "ZUNION", "ZDIFF",
+    "XRANGE", "XREVRANGE", "XLEN", "XREAD", "XINFO", "XPENDING",
+    "GETBIT", "BITCOUNT", "BITPOS", "PFCOUNT",
+    "GEOPOS", "GEODIST", "GEOHASH", "GEOSEARCH",
+    "PING", "ECHO", "INFO", "TIME", "LASTSAVE", "ROLE", "COMMAND", "PUBSUB",
+];
+
+pub fn is_read_command(command: &str) -> bool {
+    READ_COMMANDS.iter().any(|c| c.eq_ignore_ascii_case(command))
+}
+
+/// Waits at most `timeout` for a reply; a late one is discarded by the
+/// multiplexer.
+async fn with_timeout<T>(timeout: Option<std::time::Duration>, reply: impl std::future::Future<Output = T>) -> Result<T, std::io::Error> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, reply)
+            .await
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, format!("Redis did not reply within {} seconds", timeout.as_secs()))),
+        None => Ok(reply.await),
+    }
+}
+
+/// Opens the shared async connection, applies the connection's
+/// `client_name` option and checks the server answers.
+async fn open_connection(client: &Client, config: &ConnectionConfig) -> redis::RedisResult<MultiplexedConnection> {
+    let mut con = client.get_multiplexed_tokio_connection().await?;
+    if let Some(name) = driver_options::option(config, "client_name") {
+        redis::Cmd::new().arg("CLIENT").arg("SETNAME").arg(name).query_async::<_, ()>(&mut con).await?;
+    }
+    redis::Cmd::new().arg("PING").query_async::<_, String>(&mut con).await?;
+    Ok(con)
+}
+
+/// Plaintext client for a server name relay's socket; the relay does TLS.
+fn relay_client(config: &ConnectionConfig, host: &str, port: u16, relay: &ServerNameRelay) -> redis::RedisResult<Client> {
+    #[allow(unused_mut)]
+    let mut info = redis::IntoConnectionInfo::into_connection_info(
+        dsn::driver_url(config, host, port, &TlsConfig::default()).as_str(),
+    )?;
+    #[cfg(unix)]
+    {
+        info.addr = redis::ConnectionAddr::Unix(relay.socket_path().to_path_buf());
+    }
+    #[cfg(not(unix))]
+    let _ = relay;
+    Client::open(info)
+}
+
+/// One multiplexed connection shared by every command, so session state
+/// such as the client name is set once and no call blocks the runtime.
+#[derive(Clone)]
 pub struct RedisConnection {
     config: ConnectionConfig,
-    client: Client,
+    con: MultiplexedConnection,
+    _tunnel: Option<std::sync::Arc<SshTunnel>>,
+    _relay: Option<std::sync::Arc<ServerNameRelay>>,
+}
+
+impl std::fmt::Debug for RedisConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisConnection").field("config", &self.config).finish_non_exhaustive()
+    }
 }
 
 impl RedisConnection {
     pub async fn new(config: ConnectionConfig) -> Result<Self, Box<dyn std::error::Error>> {
-        let password = config.password.clone().unwrap_or_default();
+        driver_options::validate(&config)?;
+        let (host, port, tunnel) = ssh::connect_target(&config).await?;
+        let tls_config = config.tls_config();
+        let relay = tls_relay::open("redis", &tls_config, &host, port).await?;
         
-        let url = format!(
-            "redis://{}:{}@{}:{}/{}",
-            if config.username.is_empty() { "" } else { &config.username },
-            if password.is_empty() { "" } else { &password },
-            config.host,
-            config.port,
-            config.database
-        );
-        
-        let client = redis::Client::open(url)?;
-        let mut con = client.get_connection()?;
-        let _: String = redis::Cmd::new().arg("PING").query(&mut con)?;
+        let client = match &relay {
+            Some(relay) => relay_client(&config, &host, port, relay)?,
+            None => tls::redis_client(&tls_config, &dsn::driver_url(&config, &host, port, &tls_config))?,
+        };
+        let con = match open_connection(&client, &config).await {
+            Ok(con) => con,
+            Err(e) if tls_config.mode != TlsMode::Prefer || relay.is_some() || !tls::is_handshake_error(&e.to_string()) => {
+                return Err(tls::explain_error(&e.to_string(), &tls_config, &host).into());
+            }
+            Err(e) => {
+                log::info!("TLS handshake with {} failed, retrying without TLS: {}", host, e);
+                let client = redis::Client::open(dsn::driver_url(&config, &host, port, &TlsConfig::default()))?;
+                open_connection(&client, &config).await?
+            }
+        };
         
-        Ok(Self { config, client })
+        Ok(Self { config, con, _tunnel: tunnel, _relay: relay })
     }
     
-    pub async fn list_keys(&self, pattern: &str) -> Result<Vec<(String, String, usize, Option<i64>)>, Box<dyn std::error::Error>> {
-        let mut con = self.client.get_connection()?;
-        
-        let keys: Vec<String> = redis::Cmd::new()
-            .arg("KEYS")
-            .arg(pattern)
-            .query(&mut con)?;
-        
-        let mut key_infos = Vec::new();
-        
-        for key in keys {
-            let key_type: String = redis::Cmd::new()
-                .arg("TYPE")
-                .arg(&key)
-                .query(&mut con)?;
-            
-            let size = match key_type.as_str() {
-                "string" => {
-                    let len: usize = redis::Cmd::new()
-                        .arg("STRLEN")
-                        .arg(&key)
-                        .query(&mut con)?;
-                    len
-                }
-                "list" => {
-                    let len: usize = redis::Cmd::new()
-                        .arg("LLEN")
-                        .arg(&key)
-                        .query(&mut con)?;
-                    len
-                }
-                "set" => {
-                    let len: usize = redis::Cmd::new()
-                        .arg("SCARD")
-                        .arg(&key)
-                        .query(&mut con)?;
-                    len
-                }
-                "zset" => {
-                    let len: usize = redis::Cmd::new()
-                        .arg("ZCARD")
-                        .arg(&key)
-                        .query(&mut con)?;
-                    len
-                }
-                "hash" => {
-                    let len: usize = redis::Cmd::new()
-                        .arg("HLEN")
-                        .arg(&key)
-                        .query(&mut con)?;
-                    len
+    pub fn config(&self) -> &ConnectionConfig {
+        &self.config
+    }
+
+    fn connection(&self) -> MultiplexedConnection {
+        self.con.clone()
+    }
+    
+    /// Keys matching `pattern`, walked with `SCAN` so the server is not
+    /// blocked the way `KEYS` blocks it. Stops after `max` keys and reports
+    /// whether more were left.
+    async fn scan_keys(con: &mut MultiplexedConnection, pattern: &str, max: Option<usize>) -> redis::RedisResult<(Vec<String>, bool)> {
+        let mut keys = Vec::new();
+        let mut cursor = 0u64;
+        loop {
+            let (next, batch): (u64, Vec<String>) = redis::Cmd::new()
+                .arg("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(pattern)
+                .arg("COUNT")
+                .arg(1000)
+                .query_async(con)
+                .await?;
+            for key in batch {
+                if max.is_some_and(|max| keys.len() >= max) {
+                    return Ok((keys, true));
                 }
-                _ => 0,
-            };
-            
-            let ttl: i64 = redis::Cmd::new()
-                .arg("TTL")
-                .arg(&key)
-                .query(&mut con)?;
-            
-            key_infos.push((key, key_type, size, if ttl == -1 { None } else { Some(ttl) }));
+                keys.push(key);
+            }
+            if next == 0 {
+                return Ok((keys, false));
+            }
+            cursor = next;
         }
-        
-        Ok(key_infos)
     }
     
     pub async fn get_value(&self, key: &str) -> Result<QueryRow, Box<dyn std::error::Error>> {
-        let mut con = self.client.get_connection()?;
+        let mut con = self.connection();
         
         let key_type: String = redis::Cmd::new()
             .arg("TYPE")
             .arg(key)
-            .query(&mut con)?;
+            .query_async(&mut con).await?;
         
         match key_type.as_str() {
             "string" => {
                 let value: String = redis::Cmd::new()
                     .arg("GET")
                     .arg(key)
-                    .query(&mut con)?;
+                    .query_async(&mut con).await?;
                 
                 Ok(QueryRow {
                     columns: vec!["key".to_string(), "value".to_string()],
@@ -123,7 +177,7 @@ impl RedisConnection {
                     .arg(key)
                     .arg(0)
                     .arg(-1)
-                    .query(&mut con)?;
+                    .query_async(&mut con).await?;
                 
                 let rows: Vec<Vec<serde_json::Value>> = values.into_iter()
                     .enumerate()
@@ -143,7 +197,7 @@ impl RedisConnection {
                 let entries: Vec<(String, String)> = redis::Cmd::new()
                     .arg("HGETALL")
                     .arg(key)
-                    .query(&mut con)?;
+                    .query_async(&mut con).await?;
                 
                 let rows: Vec<Vec<serde_json::Value>> = entries.into_iter()
                     .map(|(field, value)| vec![
@@ -162,7 +216,7 @@ impl RedisConnection {
                 let members: Vec<String> = redis::Cmd::new()
                     .arg("SMEMBERS")
                     .arg(key)
-                    .query(&mut con)?;
+                    .query_async(&mut con).await?;
                 
                 let rows: Vec<Vec<serde_json::Value>> = members.into_iter()
                     .map(|m| vec![serde_json::Value::String(m)])
@@ -181,7 +235,7 @@ impl RedisConnection {
                     .arg(0)
                     .arg(-1)
                     .arg("WITHSCORES")
-                    .query(&mut con)?;
+                    .query_async(&mut con).await?;
                 
                 let rows: Vec<Vec<serde_json::Value>> = members.into_iter()
                     .map(|(member, score)| vec![
@@ -205,7 +259,15 @@ impl RedisConnection {
     }
     
     pub async fn execute_redis_cmd(&self, cmd: &str) -> Result<QueryRow, Box<dyn std::error::Error>> {
-        let mut con = self.client.get_connection()?;
+        Ok(self.execute_redis_cmd_limited(cmd, &ResolvedLimits::default()).await?.0)
+    }
+    
+    /// `execute_redis_cmd` with a timeout and a row cap; also returns whether
+    /// keys were left unread at the cap. Redis has no per-command server
+    /// timeout, so the reply is waited for at most that long. `KEYS` is run
+    /// as a `SCAN` so the cap stops it early.
+    pub async fn execute_redis_cmd_limited(&self, cmd: &str, limits: &ResolvedLimits) -> Result<(QueryRow, bool), Box<dyn std::error::Error>> {
+        let mut con = self.connection();
         
         let parts: Vec<&str> = cmd.split_whitespace().collect();
         if parts.is_empty() {
@@ -215,6 +277,23 @@ impl RedisConnection {
             )));
         }
         
+        if self.config.is_read_only() && !is_read_command(parts[0]) {
+            return Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("Connection is read-only; refusing {}", parts[0].to_uppercase())
+            )));
+        }
+        
+        if parts[0].eq_ignore_ascii_case("KEYS") && parts.len() == 2 {
+            let scan = Self::scan_keys(&mut con, parts[1], limits.max_rows);
+            let (keys, truncated) = with_timeout(limits.timeout, scan).await??;
+            return Ok((QueryRow {
+                columns: vec!["key".to_string()],
+                types: vec!["String".to_string()],
+                rows: keys.into_iter().map(|key| vec![serde_json::Value::String(key)]).collect(),
+            }, truncated));
+        }
+        
         let mut redis_cmd = redis::Cmd::new();
         redis_cmd.arg(parts[0]);
         
@@ -224,22 +303,57 @@ impl RedisConnection {
         
         let start = std::time::Instant::now();
         
-        let result = redis_cmd.query::<String>(&mut con);
+        let result = with_timeout(limits.timeout, redis_cmd.query_async::<_, String>(&mut con)).await?;
         let execution_time = start.elapsed();
-        println!("Redis command executed in {:?}", execution_time);
+        log::debug!("Redis command executed in {:?}", execution_time);
         
         match result {
-            Ok(_) => Ok(QueryRow {
+            Ok(_) => Ok((QueryRow {
                 columns: vec!["OK".to_string()],
                 types: vec!["String".to_string()],
                 rows: vec![vec![serde_json::Value::String("OK".to_string())]],
-            }),
+            }, false)),
             Err(e) => Err(Box::new(e)),
         }
     }
     
+    pub async fn ping(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut con = self.connection();
+        redis::Cmd::new().arg("PING").query_async::<_, String>(&mut con).await?;
+        Ok(())
+    }
+
+    /// `CLIENT LIST` output together with the id of the connection that ran it.
+    pub async fn client_list(&self) -> Result<(i64, String), Box<dyn std::error::Error>> {
+        let mut con = self.connection();
+        let own_id: i64 = redis::Cmd::new().arg("CLIENT").arg("ID").query_async(&mut con).await?;
+        let list: String = redis::Cmd::new().arg("CLIENT").arg("LIST").query_async(&mut con).await?;
+        Ok((own_id, list))
+    }
+
+    /// Runs a `CLIENT` subcommand that replies with an integer.
+    pub async fn client_command(&self, args: &[&str]) -> Result<i64, Box<dyn std::error::Error>> {
+        let mut con = self.connection();
+        Ok(redis::Cmd::new().arg("CLIENT").arg(args).query_async(&mut con).await?)
+    }
+
+    pub async fn server_version(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let mut con = self.connection();
+        let info: String = redis::Cmd::new().arg("INFO").arg("server").query_async(&mut con).await?;
+        info.lines()
+            .find_map(|line| line.strip_prefix("redis_version:"))
+            .map(|version| version.trim().to_string())
+            .ok_or_else(|| "INFO server did not report redis_version".into())
+    }
+
+    /// Number of keys in the selected database.
+    pub async fn key_count(&self) -> Result<u64, Box<dyn std::error::Error>> {
+        let mut con = self.connection();
+        Ok(redis::Cmd::new().arg("DBSIZE").query_async(&mut con).await?)
+    }
+
     pub async fn get_info(&self) -> Result<Vec<TableInfo>, Box<dyn std::error::Error>> {
-        let keys = self.list_keys("*").await?;
+        let keys = self.key_count().await?;
         
         Ok(vec![TableInfo {
             name: "keys".to_string(),
@@ -277,7 +391,7 @@ impl RedisConnection {
                     foreign_key_column: None,
                 },
             ],
-            row_count: Some(keys.len() as u64),
+            row_count: Some(keys),
             size: None,
         }])
     }
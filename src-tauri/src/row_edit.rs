@@ -0,0 +1,733 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use crate::database::{DatabaseConnection, GeneratedColumns};
+use crate::dialect;
+use crate::mongodb::{DocumentWrite, MongoConnection};
+
+/// One edited row from the result grid. `original` is the row as it was
+/// loaded and is only used to locate it by key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RowChange {
+    /// Cell edits; `values` holds only the changed columns.
+    Update { original: Map<String, Value>, values: Map<String, Value> },
+    /// Writes every column in `values`; `replaceOne` on MongoDB.
+    Replace { original: Map<String, Value>, values: Map<String, Value> },
+    Insert { values: Map<String, Value> },
+    Delete { original: Map<String, Value> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RowChangeRequest {
+    pub connection_id: String,
+    /// Source table, or collection for MongoDB.
+    pub table: String,
+    pub changes: Vec<RowChange>,
+    /// Record the before-images in the undo journal.
+    pub capture_undo: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedStatement {
+    /// SQL with bind placeholders, or the shell form of a MongoDB write.
+    pub statement: String,
+    pub params: Vec<Value>,
+    /// The statement with parameters inlined, for display only.
+    pub preview: String,
+    /// Targets one existing row by key and must affect exactly one row.
+    pub keyed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RowChangePlan {
+    /// `primary`, `unique` or `_id`; None when only inserts were requested.
+    pub key_kind: Option<String>,
+    pub key_columns: Vec<String>,
+    pub statements: Vec<PlannedStatement>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RowChangeResult {
+    pub statements_executed: usize,
+    pub rows_affected: u64,
+    /// False only for MongoDB deployments without transaction support.
+    pub atomic: bool,
+    /// Undo journal entry, when the change was captured.
+    pub change_id: Option<String>,
+}
+
+/// Columns that identify a row: the primary key, or the first unique
+/// constraint when there is none.
+pub struct RowKey {
+    pub kind: &'static str,
+    pub columns: Vec<String>,
+}
+
+fn targets_existing_rows(changes: &[RowChange]) -> bool {
+    changes.iter().any(|change| !matches!(change, RowChange::Insert { .. }))
+}
+
+pub async fn resolve_key(conn: &DatabaseConnection, table: &str) -> Result<RowKey, String> {
+    let primary = conn.primary_key_columns(table).await.map_err(|e| e.to_string())?;
+    if !primary.is_empty() {
+        return Ok(RowKey { kind: "primary", columns: primary });
+    }
+    let unique = conn.unique_key_columns(table).await.map_err(|e| e.to_string())?;
+    if !unique.is_empty() {
+        return Ok(RowKey { kind: "unique", columns: unique });
+    }
+    Err(format!(
+        "Table {} has no primary key or unique constraint, so edited rows cannot be identified safely",
+        table
+    ))
+}
+
+fn key_values(key: &RowKey, original: &Map<String, Value>, row: usize) -> Result<Vec<Value>, String> {
+    key.columns
+        .iter()
+        .map(|column| match original.get(column) {
+            None => Err(format!("Change {} is missing key column {}", row + 1, column)),
+            Some(Value::Null) => Err(format!("Change {} has NULL in key column {}", row + 1, column)),
+            Some(value) => Ok(value.clone()),
+        })
+        .collect()
+}
+
+/// Builds bind-parameter SQL for one connection's dialect. On Postgres each
+/// placeholder is cast to the column's declared type, since grid values
+/// arrive as JSON text and numbers.
+struct StatementBuilder<'a> {
+    db_type: &'a str,
+    column_types: &'a HashMap<String, String>,
+    params: Vec<Value>,
+}
+
+impl<'a> StatementBuilder<'a> {
+    fn new(db_type: &'a str, column_types: &'a HashMap<String, String>) -> Self {
+        Self { db_type, column_types, params: Vec::new() }
+    }
+
+    fn param(&mut self, column: &str, value: Value) -> String {
+        self.params.push(value);
+        let marker = dialect::placeholder(self.db_type, self.params.len());
+        match self.column_types.get(column) {
+            Some(column_type) => format!("CAST({} AS {})", marker, column_type),
+            None => marker,
+        }
+    }
+
+    fn assignments(&mut self, values: &Map<String, Value>) -> String {
+        values
+            .iter()
+            .map(|(column, value)| {
+                let marker = self.param(column, value.clone());
+                format!("{} = {}", dialect::quote_identifier(self.db_type, column), marker)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn key_predicate(&mut self, columns: &[String], values: Vec<Value>) -> String {
+        columns
+            .iter()
+            .zip(values)
+            .map(|(column, value)| {
+                let marker = self.param(column, value);
+                format!("{} = {}", dialect::quote_identifier(self.db_type, column), marker)
+            })
+            .collect::<Vec<_>>()
+            .join(" AND ")
+    }
+
+    fn finish(self, statement: String, keyed: bool) -> PlannedStatement {
+        let preview = inline_params(self.db_type, &statement, &self.params);
+        PlannedStatement { statement, params: self.params, preview, keyed }
+    }
+}
+
+/// Substitutes literals for placeholders. Only used for display, never executed.
+fn inline_params(db_type: &str, statement: &str, params: &[Value]) -> String {
+    if dialect::is_postgres(db_type) {
+        let mut preview = statement.to_string();
+        // Highest index first so `$1` does not clobber `$10`.
+        for (index, value) in params.iter().enumerate().rev() {
+            preview = preview.replace(&format!("${}", index + 1), &dialect::literal(db_type, value));
+        }
+        preview
+    } else {
+        let mut parts = statement.split('?');
+        let mut preview = parts.next().unwrap_or_default().to_string();
+        for (part, value) in parts.zip(params) {
+            preview.push_str(&dialect::literal(db_type, value));
+            preview.push_str(part);
+        }
+        preview
+    }
+}
+
+/// Per-table state for turning changes into statements.
+struct SqlPlanner {
+    db_type: String,
+    table: String,
+    quoted_table: String,
+    key: Option<RowKey>,
+    column_types: HashMap<String, String>,
+    /// Set when writing back undo images.
+    restore: Option<GeneratedColumns>,
+}
+
+impl SqlPlanner {
+    async fn new(conn: &DatabaseConnection, table: &str, needs_key: bool) -> Result<Self, String> {
+        let key = if needs_key { Some(resolve_key(conn, table).await?) } else { None };
+        Ok(Self {
+            db_type: conn.db_type().to_string(),
+            table: table.to_string(),
+            quoted_table: dialect::quote_table(conn.db_type(), table),
+            key,
+            column_types: conn.column_type_names(table).await.map_err(|e| e.to_string())?,
+            restore: None,
+        })
+    }
+
+    /// Undo images are written back without server-computed columns, and
+    /// without identity columns on updates since Postgres only lets those be
+    /// set to DEFAULT. Images journaled before generated columns were left
+    /// out of the capture still carry them.
+    fn for_restore<'c>(&self, change: &'c RowChange) -> Cow<'c, RowChange> {
+        let Some(generated) = &self.restore else {
+            return Cow::Borrowed(change);
+        };
+        let writable = |values: &Map<String, Value>, identity: bool| -> Map<String, Value> {
+            values
+                .iter()
+                .filter(|(c, _)| !generated.computed.contains(c) && (identity || !generated.identity_always.contains(c)))
+                .map(|(c, v)| (c.clone(), v.clone()))
+                .collect()
+        };
+        Cow::Owned(match change {
+            RowChange::Update { original, values } => RowChange::Update { original: original.clone(), values: writable(values, false) },
+            RowChange::Replace { original, values } => RowChange::Replace { original: original.clone(), values: writable(values, false) },
+            RowChange::Insert { values } => RowChange::Insert { values: writable(values, true) },
+            RowChange::Delete { .. } => return Cow::Borrowed(change),
+        })
+    }
+
+    fn key(&self) -> &RowKey {
+        self.key.as_ref().expect("key is resolved before planning changes to existing rows")
+    }
+
+    /// `WHERE` predicate locating the row `original` refers to.
+    fn locate(&self, original: &Map<String, Value>, index: usize) -> Result<(String, Vec<Value>), String> {
+        let mut builder = StatementBuilder::new(&self.db_type, &self.column_types);
+        let predicate = builder.key_predicate(&self.key().columns, key_values(self.key(), original, index)?);
+        Ok((predicate, builder.params))
+    }
+
+    /// None for updates without changed columns.
+    fn plan(&self, index: usize, change: &RowChange) -> Result<Option<PlannedStatement>, String> {
+        let change = self.for_restore(change);
+        let change = change.as_ref();
+        let db_type = self.db_type.as_str();
+        let mut builder = StatementBuilder::new(db_type, &self.column_types);
+        let statement = match change {
+            RowChange::Update { values, .. } | RowChange::Replace { values, .. } if values.is_empty() => return Ok(None),
+            RowChange::Update { original, values } | RowChange::Replace { original, values } => {
+                let key_values = key_values(self.key(), original, index)?;
+                let assignments = builder.assignments(values);
+                let predicate = builder.key_predicate(&self.key().columns, key_values);
+                format!("UPDATE {} SET {} WHERE {}", self.quoted_table, assignments, predicate)
+            }
+            RowChange::Insert { values } if values.is_empty() => {
+                if dialect::is_mysql(db_type) {
+                    format!("INSERT INTO {} () VALUES ()", self.quoted_table)
+                } else {
+                    format!("INSERT INTO {} DEFAULT VALUES", self.quoted_table)
+                }
+            }
+            RowChange::Insert { values } => {
+                let columns: Vec<String> = values.keys().map(|c| dialect::quote_identifier(db_type, c)).collect();
+                let markers: Vec<String> = values.iter().map(|(c, v)| builder.param(c, v.clone())).collect();
+                // Only filled in on Postgres.
+                let overriding = self
+                    .restore
+                    .as_ref()
+                    .is_some_and(|g| values.keys().any(|c| g.identity_always.contains(c)));
+                format!(
+                    "INSERT INTO {} ({}){} VALUES ({})",
+                    self.quoted_table,
+                    columns.join(", "),
+                    if overriding { " OVERRIDING SYSTEM VALUE" } else { "" },
+                    markers.join(", ")
+                )
+            }
+            RowChange::Delete { original } => {
+                let key_values = key_values(self.key(), original, index)?;
+                let predicate = builder.key_predicate(&self.key().columns, key_values);
+                format!("DELETE FROM {} WHERE {}", self.quoted_table, predicate)
+            }
+        };
+        let keyed = !matches!(change, RowChange::Insert { .. });
+        Ok(Some(builder.finish(statement, keyed)))
+    }
+}
+
+/// Turns grid changes into keyed UPDATE/INSERT/DELETE statements. Rows are
+/// located by primary key, falling back to the first unique constraint;
+/// tables with neither are refused unless the batch only inserts.
+pub async fn plan_sql_changes(
+    conn: &DatabaseConnection,
+    table: &str,
+    changes: &[RowChange],
+) -> Result<RowChangePlan, String> {
+    let planner = SqlPlanner::new(conn, table, targets_existing_rows(changes)).await?;
+    let mut statements = Vec::new();
+    for (index, change) in changes.iter().enumerate() {
+        if let Some(planned) = planner.plan(index, change)? {
+            statements.push(planned);
+        }
+    }
+
+    Ok(RowChangePlan {
+        key_kind: planner.key.as_ref().map(|k| k.kind.to_string()),
+        key_columns: planner.key.map(|k| k.columns).unwrap_or_default(),
+        statements,
+    })
+}
+
+pub fn bind_param<'q>(
+    query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>,
+    value: &Value,
+) -> sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>> {
+    match value {
+        Value::Null => query.bind(None::<String>),
+        Value::Bool(b) => query.bind(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => query.bind(i),
+            None => query.bind(n.as_f64()),
+        },
+        Value::String(s) => query.bind(s.clone()),
+        other => query.bind(other.to_string()),
+    }
+}
+
+/// SELECT returning each matching row as one JSON object in a text column.
+/// Rows are serialized server-side so dates, decimals and JSON columns keep
+/// a form the engine accepts back. `table_ref` may carry an alias; `columns`
+/// is only needed outside Postgres.
+pub fn row_image_select(
+    db_type: &str,
+    table_ref: &str,
+    columns: &[String],
+    predicate: Option<&str>,
+    limit: Option<usize>,
+) -> String {
+    let filter = predicate.map(|p| format!(" WHERE {}", p)).unwrap_or_default();
+    let limit = limit.map(|n| format!(" LIMIT {}", n)).unwrap_or_default();
+    if dialect::is_postgres(db_type) {
+        return format!(
+            "SELECT row_to_json(image)::text FROM (SELECT * FROM {}{}{} FOR UPDATE) image",
+            table_ref, filter, limit
+        );
+    }
+
+    let pairs: Vec<String> = columns
+        .iter()
+        .map(|c| format!("{}, {}", dialect::quote_string(db_type, c), dialect::quote_identifier(db_type, c)))
+        .collect();
+    if dialect::is_mysql(db_type) {
+        format!(
+            "SELECT CAST(JSON_OBJECT({}) AS CHAR) FROM {}{}{} FOR UPDATE",
+            pairs.join(", "), table_ref, filter, limit
+        )
+    } else {
+        format!("SELECT json_object({}) FROM {}{}{}", pairs.join(", "), table_ref, filter, limit)
+    }
+}
+
+/// Column names of a table, for `row_image_select`.
+pub async fn image_columns(conn: &DatabaseConnection, table: &str) -> Result<Vec<String>, String> {
+    if dialect::is_postgres(conn.db_type()) {
+        return Ok(Vec::new());
+    }
+    let sql = format!("SELECT * FROM {}", dialect::quote_table(conn.db_type(), table));
+    conn.describe_columns(&sql).await.map_err(|e| e.to_string())
+}
+
+/// Drops server-computed columns from row images; they cannot be written
+/// back when a change is undone.
+pub fn strip_generated(images: &mut [Map<String, Value>], generated: &GeneratedColumns) {
+    for image in images {
+        image.retain(|column, _| !generated.computed.contains(column));
+    }
+}
+
+pub async fn fetch_row_images(
+    tx: &mut sqlx::Transaction<'static, sqlx::Any>,
+    sql: &str,
+    params: &[Value],
+) -> Result<Vec<Map<String, Value>>, String> {
+    let mut query = sqlx::query(sql);
+    for value in params {
+        query = bind_param(query, value);
+    }
+    let rows = query.fetch_all(&mut **tx).await.map_err(|e| e.to_string())?;
+
+    let mut images = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let json: String = sqlx::Row::try_get(row, 0).map_err(|e| e.to_string())?;
+        match serde_json::from_str(&json).map_err(|e| e.to_string())? {
+            Value::Object(map) => images.push(map),
+            _ => return Err("Row image is not a JSON object".to_string()),
+        }
+    }
+    Ok(images)
+}
+
+/// Key of a row inserted without explicit key values, read back with
+/// `RETURNING` (or `LAST_INSERT_ID()` on MySQL, for a single `AUTO_INCREMENT`
+/// key column only).
+async fn inserted_key(
+    tx: &mut sqlx::Transaction<'static, sqlx::Any>,
+    db_type: &str,
+    key: &RowKey,
+    generated: &GeneratedColumns,
+    planned: &PlannedStatement,
+) -> Result<Map<String, Value>, String> {
+    if dialect::is_mysql(db_type) {
+        if key.columns.len() != 1 || !generated.auto_increment.contains(&key.columns[0]) {
+            return Err(format!(
+                "MySQL cannot report the key of the inserted row unless it is a single AUTO_INCREMENT column; give values for {}",
+                key.columns.join(", ")
+            ));
+        }
+        let execute = bind_all(sqlx::query(&planned.statement), &planned.params).execute(&mut **tx);
+        execute.await.map_err(|e| e.to_string())?;
+        let id: i64 = sqlx::query_scalar("SELECT CAST(LAST_INSERT_ID() AS SIGNED)")
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        return Ok(std::iter::once((key.columns[0].clone(), Value::from(id))).collect());
+    }
+
+    let pairs: Vec<String> = key
+        .columns
+        .iter()
+        .map(|c| format!("{}, {}", dialect::quote_string(db_type, c), dialect::quote_identifier(db_type, c)))
+        .collect();
+    let returning = if dialect::is_postgres(db_type) {
+        format!("{} RETURNING json_build_object({})::text", planned.statement, pairs.join(", "))
+    } else {
+        format!("{} RETURNING json_object({})", planned.statement, pairs.join(", "))
+    };
+    fetch_row_images(tx, &returning, &planned.params)
+        .await?
+        .pop()
+        .ok_or_else(|| "INSERT returned no row".to_string())
+}
+
+fn bind_all<'q>(
+    mut query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>,
+    params: &[Value],
+) -> sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>> {
+    for value in params {
+        query = bind_param(query, value);
+    }
+    query
+}
+
+fn conflict(index: usize, matched: usize) -> String {
+    format!(
+        "Change {} matched {} rows instead of 1; the row may have been changed or removed by someone else. No changes were applied.",
+        index + 1,
+        matched
+    )
+}
+
+/// Applies changes in one transaction. UPDATE and DELETE must hit exactly
+/// one row; anything else means the row changed underneath the grid, and the
+/// whole batch is rolled back. With `capture_undo` the before-image of every
+/// touched row is read inside the same transaction and the inverse changes
+/// are returned, in the order that undoes them.
+pub async fn apply_sql_changes(
+    conn: &DatabaseConnection,
+    table: &str,
+    changes: &[RowChange],
+    capture_undo: bool,
+) -> Result<(RowChangeResult, Vec<RowChange>), String> {
+    let planner = SqlPlanner::new(conn, table, capture_undo || targets_existing_rows(changes)).await?;
+    run_sql_changes(conn, planner, changes, capture_undo).await
+}
+
+/// Applies the inverse changes of an undo journal entry. Identity columns
+/// Postgres generates `ALWAYS` are inserted with `OVERRIDING SYSTEM VALUE`
+/// so deleted rows come back under their old keys.
+pub async fn restore_sql_changes(
+    conn: &DatabaseConnection,
+    table: &str,
+    inverse: &[RowChange],
+) -> Result<(RowChangeResult, Vec<RowChange>), String> {
+    let mut planner = SqlPlanner::new(conn, table, targets_existing_rows(inverse)).await?;
+    planner.restore = Some(conn.generated_columns(table).await.map_err(|e| e.to_string())?);
+    run_sql_changes(conn, planner, inverse, false).await
+}
+
+async fn run_sql_changes(
+    conn: &DatabaseConnection,
+    planner: SqlPlanner,
+    changes: &[RowChange],
+    capture_undo: bool,
+) -> Result<(RowChangeResult, Vec<RowChange>), String> {
+    let (columns, generated) = if capture_undo {
+        let table = planner.table.as_str();
+        let generated = conn.generated_columns(table).await.map_err(|e| e.to_string())?;
+        (image_columns(conn, table).await?, generated)
+    } else {
+        (Vec::new(), GeneratedColumns::default())
+    };
+    let db_type = conn.db_type();
+
+    let mut tx = conn.begin().await.map_err(|e| e.to_string())?;
+    let mut inverse = Vec::new();
+    let mut statements_executed = 0;
+    let mut rows_affected = 0;
+
+    for (index, change) in changes.iter().enumerate() {
+        let Some(planned) = planner.plan(index, change)? else {
+            continue;
+        };
+
+        let before = match change {
+            RowChange::Update { original, .. } | RowChange::Replace { original, .. } | RowChange::Delete { original } if capture_undo => {
+                let (predicate, params) = planner.locate(original, index)?;
+                let sql = row_image_select(db_type, &planner.quoted_table, &columns, Some(&predicate), None);
+                let mut images = fetch_row_images(&mut tx, &sql, &params).await?;
+                if images.len() != 1 {
+                    return Err(conflict(index, images.len()));
+                }
+                strip_generated(&mut images, &generated);
+                images.pop()
+            }
+            _ => None,
+        };
+
+        match change {
+            RowChange::Insert { values } if capture_undo => {
+                let key = planner.key();
+                let provided: Option<Map<String, Value>> = key
+                    .columns
+                    .iter()
+                    .map(|c| values.get(c).filter(|v| !v.is_null()).map(|v| (c.clone(), v.clone())))
+                    .collect();
+                let inserted = match provided {
+                    Some(key_values) => {
+                        let execute = bind_all(sqlx::query(&planned.statement), &planned.params).execute(&mut *tx);
+                        execute.await.map_err(|e| format!("Change {} failed, no changes were applied: {}", index + 1, e))?;
+                        key_values
+                    }
+                    None => inserted_key(&mut tx, db_type, key, &generated, &planned)
+                        .await
+                        .map_err(|e| format!("Change {} failed, no changes were applied: {}", index + 1, e))?,
+                };
+                inverse.push(RowChange::Delete { original: inserted });
+                rows_affected += 1;
+            }
+            _ => {
+                let execute = bind_all(sqlx::query(&planned.statement), &planned.params).execute(&mut *tx);
+                let affected = execute
+                    .await
+                    .map_err(|e| format!("Change {} failed, no changes were applied: {}", index + 1, e))?
+                    .rows_affected();
+                if planned.keyed && affected != 1 {
+                    let _ = tx.rollback().await;
+                    return Err(conflict(index, affected as usize));
+                }
+                rows_affected += affected;
+            }
+        }
+
+        if let Some(before) = before {
+            inverse.push(match change {
+                RowChange::Update { values, .. } | RowChange::Replace { values, .. } => {
+                    // Locate the row by its key after this change.
+                    let original = planner
+                        .key()
+                        .columns
+                        .iter()
+                        .map(|c| (c.clone(), values.get(c).or_else(|| before.get(c)).cloned().unwrap_or(Value::Null)))
+                        .collect();
+                    RowChange::Replace { original, values: before }
+                }
+                _ => RowChange::Insert { values: before },
+            });
+        }
+        statements_executed += 1;
+    }
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    inverse.reverse();
+    Ok((
+        RowChangeResult {
+            statements_executed,
+            rows_affected,
+            atomic: true,
+            change_id: None,
+        },
+        inverse,
+    ))
+}
+
+/// `_id` filter for a grid value. ObjectIds are shown as hex strings, so a
+/// 24-hex string matches either the ObjectId or the literal string. Extended
+/// JSON (`{"$oid": ...}`) is matched exactly.
+fn id_filter(id: &Value) -> Result<mongodb::bson::Document, String> {
+    let bson = mongodb::bson::Bson::try_from(id.clone()).map_err(|e| e.to_string())?;
+    let filter = match id {
+        Value::String(s) => match mongodb::bson::oid::ObjectId::parse_str(s) {
+            Ok(oid) => mongodb::bson::doc! { "_id": { "$in": [oid, bson] } },
+            Err(_) => mongodb::bson::doc! { "_id": bson },
+        },
+        _ => mongodb::bson::doc! { "_id": bson },
+    };
+    Ok(filter)
+}
+
+/// Values may use extended JSON, which is how before-images are journaled.
+fn to_document(values: &Map<String, Value>) -> Result<mongodb::bson::Document, String> {
+    mongodb::bson::Document::try_from(values.clone()).map_err(|e| e.to_string())
+}
+
+fn to_extended_json(document: &mongodb::bson::Document) -> Map<String, Value> {
+    match mongodb::bson::Bson::Document(document.clone()).into_canonical_extjson() {
+        Value::Object(map) => map,
+        _ => Map::new(),
+    }
+}
+
+fn document_id(original: &Map<String, Value>, row: usize) -> Result<&Value, String> {
+    match original.get("_id") {
+        None | Some(Value::Null) => Err(format!("Change {} has no _id, so the document cannot be identified", row + 1)),
+        Some(id) => Ok(id),
+    }
+}
+
+/// MongoDB counterpart of `plan_sql_changes`: `updateOne` with `$set` for cell
+/// edits, `replaceOne` for whole documents, both keyed by `_id`.
+pub fn plan_document_changes(collection: &str, changes: &[RowChange]) -> Result<(RowChangePlan, Vec<DocumentWrite>), String> {
+    let mut writes = Vec::new();
+    for (index, change) in changes.iter().enumerate() {
+        let write = match change {
+            RowChange::Update { original, values } => {
+                if values.is_empty() {
+                    continue;
+                }
+                if values.contains_key("_id") {
+                    return Err(format!("Change {} modifies _id, which is immutable", index + 1));
+                }
+                DocumentWrite::Update {
+                    filter: id_filter(document_id(original, index)?)?,
+                    update: mongodb::bson::doc! { "$set": to_document(values)? },
+                }
+            }
+            RowChange::Replace { original, values } => {
+                let mut replacement = values.clone();
+                replacement.remove("_id");
+                DocumentWrite::Replace {
+                    filter: id_filter(document_id(original, index)?)?,
+                    replacement: to_document(&replacement)?,
+                }
+            }
+            RowChange::Insert { values } => {
+                // Assign the ObjectId here rather than in the driver so the
+                // undo journal knows which document to remove.
+                let mut document = to_document(values)?;
+                if !document.contains_key("_id") {
+                    document.insert("_id", mongodb::bson::oid::ObjectId::new());
+                }
+                DocumentWrite::Insert(document)
+            }
+            RowChange::Delete { original } => DocumentWrite::Delete {
+                filter: id_filter(document_id(original, index)?)?,
+            },
+        };
+        writes.push(write);
+    }
+
+    let statements = writes
+        .iter()
+        .map(|write| {
+            let shell = write.to_shell(collection);
+            PlannedStatement {
+                statement: shell.clone(),
+                params: Vec::new(),
+                preview: shell,
+                keyed: !matches!(write, DocumentWrite::Insert(_)),
+            }
+        })
+        .collect();
+    let plan = RowChangePlan {
+        key_kind: targets_existing_rows(changes).then(|| "_id".to_string()),
+        key_columns: if targets_existing_rows(changes) { vec!["_id".to_string()] } else { Vec::new() },
+        statements,
+    };
+    Ok((plan, writes))
+}
+
+/// MongoDB counterpart of `apply_sql_changes`. Before-images are read just
+/// before the writes, outside the write transaction.
+pub async fn apply_document_changes(
+    conn: &MongoConnection,
+    collection: &str,
+    changes: &[RowChange],
+    capture_undo: bool,
+) -> Result<(RowChangeResult, Vec<RowChange>), String> {
+    let (_, writes) = plan_document_changes(collection, changes)?;
+
+    let mut inverse = Vec::new();
+    if capture_undo {
+        for (index, write) in writes.iter().enumerate() {
+            let filter = match write {
+                DocumentWrite::Insert(document) => {
+                    let id = document.get("_id").cloned().unwrap_or(mongodb::bson::Bson::Null);
+                    let original = std::iter::once(("_id".to_string(), id.into_canonical_extjson())).collect();
+                    inverse.push(RowChange::Delete { original });
+                    continue;
+                }
+                DocumentWrite::Update { filter, .. }
+                | DocumentWrite::Replace { filter, .. }
+                | DocumentWrite::Delete { filter } => filter.clone(),
+            };
+            let mut found = conn.find_documents(collection, filter).await.map_err(|e| e.to_string())?;
+            if found.len() != 1 {
+                return Err(conflict(index, found.len()));
+            }
+            let before = to_extended_json(&found.remove(0));
+            inverse.push(match write {
+                DocumentWrite::Delete { .. } => RowChange::Insert { values: before },
+                _ => {
+                    let id = before.get("_id").cloned().unwrap_or(Value::Null);
+                    RowChange::Replace {
+                        original: std::iter::once(("_id".to_string(), id)).collect(),
+                        values: before,
+                    }
+                }
+            });
+        }
+        inverse.reverse();
+    }
+
+    let (rows_affected, atomic) = conn
+        .apply_document_writes(collection, &writes)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok((
+        RowChangeResult {
+            statements_executed: writes.len(),
+            rows_affected,
+            atomic,
+            change_id: None,
+        },
+        inverse,
+    ))
+}
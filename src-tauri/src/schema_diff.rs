@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::types::{ColumnInfo, SchemaObject, TableInfo};
+
+/// One side of a schema comparison: a live connection, a stored snapshot, or a
+/// snapshot file exported from another machine.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SchemaSource {
+    Live { connection_id: String },
+    Snapshot { connection_id: String, snapshot_id: String },
+    File { path: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnChange {
+    pub name: String,
+    pub before: ColumnInfo,
+    pub after: ColumnInfo,
+    pub changes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableDiff {
+    pub schema: Option<String>,
+    pub name: String,
+    pub type_changed: Option<(String, String)>,
+    pub added_columns: Vec<ColumnInfo>,
+    pub removed_columns: Vec<ColumnInfo>,
+    pub changed_columns: Vec<ColumnChange>,
+}
+
+impl TableDiff {
+    fn is_empty(&self) -> bool {
+        self.type_changed.is_none()
+            && self.added_columns.is_empty()
+            && self.removed_columns.is_empty()
+            && self.changed_columns.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectChange {
+    pub before: SchemaObject,
+    pub after: SchemaObject,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaDiff {
+    pub added_tables: Vec<TableInfo>,
+    pub removed_tables: Vec<TableInfo>,
+    pub changed_tables: Vec<TableDiff>,
+    pub unchanged_tables: usize,
+    /// False when either side predates object capture (a version 1 snapshot),
+    /// in which case the object lists below are empty.
+    pub objects_compared: bool,
+    pub added_objects: Vec<SchemaObject>,
+    pub removed_objects: Vec<SchemaObject>,
+    pub changed_objects: Vec<ObjectChange>,
+    pub unchanged_objects: usize,
+}
+
+/// One side of a comparison. `objects` is `None` for snapshots taken before
+/// indexes, constraints, views, triggers and routines were captured.
+pub struct SchemaContents {
+    pub tables: Vec<TableInfo>,
+    pub objects: Option<Vec<SchemaObject>>,
+}
+
+/// Compares two introspected schemas. Tables are matched by schema and name,
+/// columns by name, and other objects by kind, schema, table and name;
+/// `source` is treated as the "before" side and `target` as the "after" side.
+pub fn diff_schemas(source: &SchemaContents, target: &SchemaContents) -> SchemaDiff {
+    let source_tables: BTreeMap<_, &TableInfo> = source.tables.iter().map(|t| (table_key(t), t)).collect();
+    let target_tables: BTreeMap<_, &TableInfo> = target.tables.iter().map(|t| (table_key(t), t)).collect();
+
+    let mut diff = SchemaDiff {
+        added_tables: Vec::new(),
+        removed_tables: Vec::new(),
+        changed_tables: Vec::new(),
+        unchanged_tables: 0,
+        objects_compared: false,
+        added_objects: Vec::new(),
+        removed_objects: Vec::new(),
+        changed_objects: Vec::new(),
+        unchanged_objects: 0,
+    };
+
+    for (key, table) in &target_tables {
+        if !source_tables.contains_key(key) {
+            diff.added_tables.push((*table).clone());
+        }
+    }
+
+    for (key, before) in &source_tables {
+        let Some(after) = target_tables.get(key) else {
+            diff.removed_tables.push((*before).clone());
+            continue;
+        };
+
+        let table_diff = diff_table(before, after);
+        if table_diff.is_empty() {
+            diff.unchanged_tables += 1;
+        } else {
+            diff.changed_tables.push(table_diff);
+        }
+    }
+
+    if let (Some(source_objects), Some(target_objects)) = (&source.objects, &target.objects) {
+        diff.objects_compared = true;
+        diff_objects(&mut diff, source_objects, target_objects);
+    }
+
+    diff
+}
+
+fn table_key(table: &TableInfo) -> (Option<&str>, &str) {
+    (table.schema.as_deref(), table.name.as_str())
+}
+
+fn object_key(object: &SchemaObject) -> (&str, Option<&str>, Option<&str>, &str) {
+    (object.kind.as_str(), object.schema.as_deref(), object.table.as_deref(), object.name.as_str())
+}
+
+fn diff_objects(diff: &mut SchemaDiff, source: &[SchemaObject], target: &[SchemaObject]) {
+    let source_objects: BTreeMap<_, &SchemaObject> = source.iter().map(|o| (object_key(o), o)).collect();
+    let target_objects: BTreeMap<_, &SchemaObject> = target.iter().map(|o| (object_key(o), o)).collect();
+
+    for (key, object) in &target_objects {
+        if !source_objects.contains_key(key) {
+            diff.added_objects.push((*object).clone());
+        }
+    }
+
+    for (key, before) in &source_objects {
+        match target_objects.get(key) {
+            None => diff.removed_objects.push((*before).clone()),
+            Some(after) if normalize(&before.definition) != normalize(&after.definition) => {
+                diff.changed_objects.push(ObjectChange {
+                    before: (*before).clone(),
+                    after: (*after).clone(),
+                });
+            }
+            Some(_) => diff.unchanged_objects += 1,
+        }
+    }
+}
+
+/// Definitions are compared ignoring whitespace layout, which servers and
+/// dump tools reformat freely.
+fn normalize(definition: &str) -> String {
+    definition.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn diff_table(before: &TableInfo, after: &TableInfo) -> TableDiff {
+    let before_columns: BTreeMap<&str, &ColumnInfo> = before.columns.iter().map(|c| (c.name.as_str(), c)).collect();
+    let after_columns: BTreeMap<&str, &ColumnInfo> = after.columns.iter().map(|c| (c.name.as_str(), c)).collect();
+
+    let added_columns = after.columns.iter()
+        .filter(|c| !before_columns.contains_key(c.name.as_str()))
+        .cloned()
+        .collect();
+    let removed_columns = before.columns.iter()
+        .filter(|c| !after_columns.contains_key(c.name.as_str()))
+        .cloned()
+        .collect();
+
+    let mut changed_columns = Vec::new();
+    for column in &before.columns {
+        if let Some(other) = after_columns.get(column.name.as_str()) {
+            let changes = column_changes(column, other);
+            if !changes.is_empty() {
+                changed_columns.push(ColumnChange {
+                    name: column.name.clone(),
+                    before: column.clone(),
+                    after: (*other).clone(),
+                    changes,
+                });
+            }
+        }
+    }
+
+    TableDiff {
+        schema: before.schema.clone(),
+        name: before.name.clone(),
+        type_changed: if before.r#type != after.r#type {
+            Some((before.r#type.clone(), after.r#type.clone()))
+        } else {
+            None
+        },
+        added_columns,
+        removed_columns,
+        changed_columns,
+    }
+}
+
+fn column_changes(before: &ColumnInfo, after: &ColumnInfo) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    if !before.r#type.eq_ignore_ascii_case(&after.r#type) {
+        changes.push(format!("type: {} -> {}", before.r#type, after.r#type));
+    }
+    if before.nullable != after.nullable {
+        changes.push(format!("nullable: {} -> {}", before.nullable, after.nullable));
+    }
+    if before.default_value != after.default_value {
+        changes.push(format!(
+            "default: {} -> {}",
+            before.default_value.as_deref().unwrap_or("NULL"),
+            after.default_value.as_deref().unwrap_or("NULL")
+        ));
+    }
+    if before.is_primary_key != after.is_primary_key {
+        changes.push(format!("primary key: {} -> {}", before.is_primary_key, after.is_primary_key));
+    }
+    if before.is_foreign_key != after.is_foreign_key
+        || before.foreign_key_table != after.foreign_key_table
+        || before.foreign_key_column != after.foreign_key_column
+    {
+        changes.push(format!(
+            "foreign key: {} -> {}",
+            describe_foreign_key(before),
+            describe_foreign_key(after)
+        ));
+    }
+
+    changes
+}
+
+fn describe_foreign_key(column: &ColumnInfo) -> String {
+    if !column.is_foreign_key {
+        return "none".to_string();
+    }
+    format!(
+        "{}.{}",
+        column.foreign_key_table.as_deref().unwrap_or("?"),
+        column.foreign_key_column.as_deref().unwrap_or("?")
+    )
+}
@@ -0,0 +1,225 @@
+//! Server sessions: who is connected, what they are running and for how
+//! long, in one shape across engines; plus cancelling a session's current
+//! statement or ending the session.
+
+use mongodb::bson::{doc, Bson, Document};
+use serde::Serialize;
+use serde_json::Value;
+use crate::database::DatabaseConnection;
+use crate::mongodb::MongoConnection;
+use crate::redis::RedisConnection;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerSession {
+    /// Backend pid, thread id, opid or client id; what `kill_session` takes.
+    pub id: String,
+    pub user: Option<String>,
+    pub database: Option<String>,
+    pub client_address: Option<String>,
+    pub application: Option<String>,
+    /// `active`, `idle`, `idle in transaction`, ... as the server reports it.
+    pub state: Option<String>,
+    pub statement: Option<String>,
+    /// Time spent in the current statement, or idle.
+    pub duration_ms: Option<u64>,
+    pub wait_event: Option<String>,
+    /// The session this app used to list the others.
+    pub is_current: bool,
+}
+
+const POSTGRES_SESSIONS: &str = "\
+SELECT pid::bigint, usename::text, datname::text, host(client_addr) || ':' || client_port, \
+       application_name::text, state::text, query::text, \
+       (EXTRACT(EPOCH FROM now() - COALESCE(query_start, backend_start)) * 1000)::bigint, \
+       CASE WHEN wait_event IS NULL THEN NULL ELSE wait_event_type || ':' || wait_event END, \
+       pid = pg_backend_pid() \
+FROM pg_stat_activity \
+WHERE backend_type = 'client backend' \
+ORDER BY query_start DESC NULLS LAST";
+
+// The same rows as SHOW FULL PROCESSLIST.
+const MYSQL_SESSIONS: &str = "\
+SELECT CAST(ID AS SIGNED), USER, DB, HOST, NULL, COMMAND, INFO, CAST(TIME AS SIGNED) * 1000, \
+       STATE, ID = CONNECTION_ID() \
+FROM information_schema.PROCESSLIST \
+ORDER BY TIME DESC";
+
+pub async fn list_sql(conn: &DatabaseConnection) -> Result<Vec<ServerSession>, String> {
+    let sql = match conn.db_type() {
+        "postgresql" => POSTGRES_SESSIONS,
+        "mysql" | "mariadb" => MYSQL_SESSIONS,
+        other => return Err(format!("Sessions are not available for {}", other)),
+    };
+    let mysql = sql == MYSQL_SESSIONS;
+    let rows = conn.execute_query(sql).await.map_err(|e| e.to_string())?.rows;
+    Ok(rows.iter().map(|row| {
+        let mut session = ServerSession {
+            id: text(row, 0).unwrap_or_default(),
+            user: text(row, 1),
+            database: text(row, 2),
+            client_address: text(row, 3),
+            application: text(row, 4),
+            state: text(row, 5),
+            statement: text(row, 6),
+            duration_ms: row.get(7).and_then(Value::as_i64).map(|ms| ms.max(0) as u64),
+            wait_event: text(row, 8),
+            is_current: truthy(row.get(9)),
+        };
+        if mysql {
+            // The processlist state describes what a thread is doing; only
+            // the "Waiting for ..." ones are waits.
+            session.wait_event = session.wait_event.filter(|state| state.starts_with("Waiting"));
+            session.state = session.state.map(|command| command.to_lowercase());
+        }
+        session
+    }).collect())
+}
+
+/// Cancels the session's current statement, or with `terminate` ends the
+/// session. Returns whether the server found it.
+pub async fn signal_sql(conn: &DatabaseConnection, id: &str, terminate: bool) -> Result<bool, String> {
+    let id: i64 = id.parse().map_err(|_| format!("Invalid session id: {}", id))?;
+    match conn.db_type() {
+        "postgresql" => {
+            let function = if terminate { "pg_terminate_backend" } else { "pg_cancel_backend" };
+            let rows = conn.execute_query(&format!("SELECT {}({})", function, id)).await.map_err(|e| e.to_string())?.rows;
+            Ok(rows.first().is_some_and(|row| truthy(row.first())))
+        }
+        "mysql" | "mariadb" => {
+            let statement = if terminate { format!("KILL {}", id) } else { format!("KILL QUERY {}", id) };
+            conn.execute_statement(&statement).await.map_err(|e| e.to_string())?;
+            Ok(true)
+        }
+        other => Err(format!("Sessions are not available for {}", other)),
+    }
+}
+
+pub async fn list_mongo(conn: &MongoConnection) -> Result<Vec<ServerSession>, String> {
+    let reply = conn
+        .admin_command(doc! { "currentOp": 1, "$all": true })
+        .await
+        .map_err(|e| e.to_string())?;
+    let operations = reply.get_array("inprog").map_err(|e| e.to_string())?;
+    Ok(operations
+        .iter()
+        .filter_map(Bson::as_document)
+        // Internal threads have no client.
+        .filter(|op| op.contains_key("client") || op.contains_key("client_s"))
+        .map(|op| {
+            let user = op.get_array("effectiveUsers").ok()
+                .and_then(|users| users.first())
+                .and_then(Bson::as_document)
+                .and_then(|user| Some(format!("{}@{}", user.get_str("user").ok()?, user.get_str("db").ok()?)));
+            let active = op.get_bool("active").unwrap_or(false);
+            let waits: Vec<&str> = [("waitingForLock", "lock"), ("waitingForFlowControl", "flow control"), ("waitingForLatch", "latch")]
+                .iter()
+                .filter(|(field, _)| op.get(field).is_some_and(|value| !matches!(value, Bson::Boolean(false) | Bson::Null)))
+                .map(|(_, name)| *name)
+                .collect();
+            ServerSession {
+                id: op.get("opid").map(bson_text).or_else(|| op.get_str("desc").ok().map(str::to_string)).unwrap_or_default(),
+                user,
+                database: op.get_str("ns").ok().and_then(|ns| ns.split('.').next()).filter(|db| !db.is_empty()).map(str::to_string),
+                client_address: op.get_str("client").or_else(|_| op.get_str("client_s")).ok().map(str::to_string),
+                application: op.get_document("clientMetadata").ok()
+                    .and_then(|meta| meta.get_document("application").ok())
+                    .and_then(|app| app.get_str("name").ok())
+                    .map(str::to_string),
+                state: Some(if active { "active" } else { "idle" }.to_string()),
+                statement: op.get_document("command").ok().map(|command| crate::mongodb::convert_bson_to_json(command).to_string()),
+                duration_ms: op.get("microsecs_running").and_then(bson_i64).map(|us| us.max(0) as u64 / 1000),
+                wait_event: (!waits.is_empty()).then(|| waits.join(", ")),
+                is_current: op.get_document("command").is_ok_and(|command| command.contains_key("currentOp")),
+            }
+        })
+        .collect())
+}
+
+/// Mongo can only interrupt operations, so cancelling and terminating both
+/// issue `killOp`.
+pub async fn signal_mongo(conn: &MongoConnection, id: &str) -> Result<bool, String> {
+    // Sharded clusters report opids as "shard:number".
+    let op = match id.parse::<i64>() {
+        Ok(opid) => match i32::try_from(opid) {
+            Ok(opid) => Bson::Int32(opid),
+            Err(_) => Bson::Int64(opid),
+        },
+        Err(_) if id.contains(':') => Bson::String(id.to_string()),
+        Err(_) => return Err(format!("Invalid operation id: {}", id)),
+    };
+    let reply: Document = conn.admin_command(doc! { "killOp": 1, "op": op }).await.map_err(|e| e.to_string())?;
+    Ok(reply.get("ok").and_then(bson_i64).unwrap_or(0) == 1
+        || matches!(reply.get("ok"), Some(Bson::Double(ok)) if *ok == 1.0))
+}
+
+pub async fn list_redis(conn: &RedisConnection) -> Result<Vec<ServerSession>, String> {
+    let (own_id, list) = conn.client_list().await.map_err(|e| e.to_string())?;
+    Ok(list.lines().filter(|line| !line.trim().is_empty()).map(|line| {
+        let fields: std::collections::HashMap<&str, &str> = line
+            .split(' ')
+            .filter_map(|field| field.split_once('='))
+            .collect();
+        let field = |name: &str| fields.get(name).filter(|value| !value.is_empty()).map(|value| value.to_string());
+        let idle_secs = field("idle").and_then(|idle| idle.parse::<u64>().ok());
+        let blocked = fields.get("flags").is_some_and(|flags| flags.contains('b'));
+        let id = field("id").unwrap_or_default();
+        ServerSession {
+            is_current: id.parse::<i64>().ok() == Some(own_id),
+            id,
+            user: field("user"),
+            database: field("db"),
+            client_address: field("addr"),
+            application: field("name"),
+            state: Some(match (blocked, idle_secs) {
+                (true, _) => "blocked",
+                (false, Some(0)) => "active",
+                _ => "idle",
+            }.to_string()),
+            statement: field("cmd").filter(|cmd| cmd != "NULL"),
+            duration_ms: idle_secs.map(|secs| secs * 1000),
+            wait_event: blocked.then(|| "blocking command".to_string()),
+        }
+    }).collect())
+}
+
+/// `CLIENT UNBLOCK` interrupts a blocking command; `CLIENT KILL` closes the
+/// connection.
+pub async fn signal_redis(conn: &RedisConnection, id: &str, terminate: bool) -> Result<bool, String> {
+    id.parse::<u64>().map_err(|_| format!("Invalid client id: {}", id))?;
+    let args: &[&str] = if terminate { &["KILL", "ID", id] } else { &["UNBLOCK", id] };
+    Ok(conn.client_command(args).await.map_err(|e| e.to_string())? > 0)
+}
+
+fn text(row: &[Value], index: usize) -> Option<String> {
+    match row.get(index)? {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Booleans arrive as `true`, `1` or `"t"` depending on the driver.
+fn truthy(value: Option<&Value>) -> bool {
+    match value {
+        Some(Value::Bool(b)) => *b,
+        Some(Value::Number(n)) => n.as_i64() == Some(1),
+        Some(Value::String(s)) => matches!(s.as_str(), "t" | "true" | "1"),
+        _ => false,
+    }
+}
+
+fn bson_text(value: &Bson) -> String {
+    match value {
+        Bson::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn bson_i64(value: &Bson) -> Option<i64> {
+    match value {
+        Bson::Int32(n) => Some(*n as i64),
+        Bson::Int64(n) => Some(*n),
+        Bson::Double(n) => Some(*n as i64),
+        _ => None,
+    }
+}
@@ -0,0 +1,155 @@
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::schema_diff::SchemaContents;
+use crate::types::{SchemaObject, TableInfo};
+
+/// Version 2 added `objects`; version 1 files load with none.
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaSnapshot {
+    pub format_version: u32,
+    pub id: String,
+    pub connection_id: String,
+    pub connection_name: String,
+    pub db_type: String,
+    pub database: String,
+    pub captured_at: String,
+    pub label: Option<String>,
+    pub tables: Vec<TableInfo>,
+    #[serde(default)]
+    pub objects: Vec<SchemaObject>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotSummary {
+    pub id: String,
+    pub connection_id: String,
+    pub connection_name: String,
+    pub captured_at: String,
+    pub label: Option<String>,
+    pub table_count: usize,
+    pub object_count: usize,
+}
+
+impl SchemaSnapshot {
+    pub fn summary(&self) -> SnapshotSummary {
+        SnapshotSummary {
+            id: self.id.clone(),
+            connection_id: self.connection_id.clone(),
+            connection_name: self.connection_name.clone(),
+            captured_at: self.captured_at.clone(),
+            label: self.label.clone(),
+            table_count: self.tables.len(),
+            object_count: self.objects.len(),
+        }
+    }
+
+    pub fn into_contents(self) -> SchemaContents {
+        SchemaContents {
+            tables: self.tables,
+            objects: (self.format_version >= 2).then_some(self.objects),
+        }
+    }
+}
+
+/// Stores snapshots as `<root>/<connection_id>/<snapshot_id>.json`. Snapshot ids
+/// are UTC timestamps, so a lexical sort of the file names is a chronological one.
+pub struct SnapshotStore {
+    root: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn connection_dir(&self, connection_id: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        Ok(self.root.join(safe_file_component(connection_id)?))
+    }
+
+    pub fn save(&self, snapshot: &SchemaSnapshot) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let dir = self.connection_dir(&snapshot.connection_id)?;
+        std::fs::create_dir_all(&dir)?;
+
+        let path = dir.join(format!("{}.json", safe_file_component(&snapshot.id)?));
+        let json = serde_json::to_string_pretty(snapshot)?;
+        std::fs::write(&path, json)?;
+
+        Ok(path)
+    }
+
+    pub fn load(&self, connection_id: &str, snapshot_id: &str) -> Result<SchemaSnapshot, Box<dyn std::error::Error>> {
+        let path = self.connection_dir(connection_id)?
+            .join(format!("{}.json", safe_file_component(snapshot_id)?));
+        load_snapshot_file(&path)
+    }
+
+    /// Lists the snapshots of a connection, newest first.
+    pub fn list(&self, connection_id: &str) -> Result<Vec<SnapshotSummary>, Box<dyn std::error::Error>> {
+        let dir = self.connection_dir(connection_id)?;
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().map(|ext| ext == "json").unwrap_or(false))
+            .collect();
+        paths.sort();
+        paths.reverse();
+
+        let mut summaries = Vec::new();
+        for path in paths {
+            match load_snapshot_file(&path) {
+                Ok(snapshot) => summaries.push(snapshot.summary()),
+                Err(e) => log::warn!("Skipping unreadable snapshot {:?}: {}", path, e),
+            }
+        }
+
+        Ok(summaries)
+    }
+
+    pub fn delete(&self, connection_id: &str, snapshot_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let path = self.connection_dir(connection_id)?
+            .join(format!("{}.json", safe_file_component(snapshot_id)?));
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
+
+pub fn load_snapshot_file(path: &Path) -> Result<SchemaSnapshot, Box<dyn std::error::Error>> {
+    let contents = std::fs::read_to_string(path)?;
+    let snapshot: SchemaSnapshot = serde_json::from_str(&contents)?;
+
+    if snapshot.format_version > SNAPSHOT_FORMAT_VERSION {
+        return Err(Box::new(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "Snapshot format version {} is newer than supported version {}",
+                snapshot.format_version, SNAPSHOT_FORMAT_VERSION
+            )
+        )));
+    }
+
+    Ok(snapshot)
+}
+
+/// Snapshot ids double as file names: `20261018T093015123Z`.
+pub fn new_snapshot_id() -> String {
+    chrono::Utc::now().format("%Y%m%dT%H%M%S%3fZ").to_string()
+}
+
+/// Rejects ids that could escape the store directory.
+pub fn safe_file_component(value: &str) -> Result<&str, Box<dyn std::error::Error>> {
+    let valid = !value.is_empty()
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && !value.starts_with('.');
+    if !valid {
+        return Err(Box::new(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Invalid identifier for a file name: {}", value)
+        )));
+    }
+    Ok(value)
+}
@@ -0,0 +1,246 @@
+//! SSH tunnels: a local port forwarded to the database through one or more
+//! SSH servers. Drivers connect to `127.0.0.1:<local_port>` and the tunnel
+//! lives as long as the connection holding it.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use russh::client::{self, Handle};
+use russh::Disconnect;
+use russh_keys::key::PublicKey;
+use tokio::net::TcpListener;
+use crate::types::{ConnectionConfig, SshConfig, SshHop};
+
+/// Checks server keys against known_hosts. The reason for a rejection is
+/// kept so the connect error can say more than "unknown key".
+struct HostKeyCheck {
+    host: String,
+    port: u16,
+    known_hosts: PathBuf,
+    accept_new: bool,
+    rejection: Arc<Mutex<Option<String>>>,
+}
+
+#[async_trait::async_trait]
+impl client::Handler for HostKeyCheck {
+    type Error = russh::Error;
+
+    async fn check_server_key(&mut self, key: &PublicKey) -> Result<bool, Self::Error> {
+        let reason = match russh_keys::check_known_hosts_path(&self.host, self.port, key, &self.known_hosts) {
+            Ok(true) => return Ok(true),
+            Ok(false) if self.accept_new => {
+                match russh_keys::learn_known_hosts_path(&self.host, self.port, key, &self.known_hosts) {
+                    Ok(()) => {
+                        log::info!("Added host key of {}:{} to {:?}", self.host, self.port, self.known_hosts);
+                        return Ok(true);
+                    }
+                    Err(e) => format!("could not record its key in {:?}: {}", self.known_hosts, e),
+                }
+            }
+            Ok(false) => format!("its key ({}) is not in {:?}", key.fingerprint(), self.known_hosts),
+            Err(russh_keys::Error::KeyChanged { line }) => format!(
+                "its key ({}) does not match line {} of {:?}; the host key has changed",
+                key.fingerprint(), line, self.known_hosts
+            ),
+            Err(e) => format!("known_hosts check failed: {}", e),
+        };
+        if let Ok(mut rejection) = self.rejection.lock() {
+            *rejection = Some(format!("Refused SSH host {}:{}: {}", self.host, self.port, reason));
+        }
+        Ok(false)
+    }
+}
+
+type Session = Handle<HostKeyCheck>;
+
+pub struct SshTunnel {
+    local_port: u16,
+    /// Bastions first; the last session forwards to the database.
+    sessions: Vec<Arc<Session>>,
+    accept_task: tokio::task::JoinHandle<()>,
+}
+
+impl std::fmt::Debug for SshTunnel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SshTunnel").field("local_port", &self.local_port).finish()
+    }
+}
+
+impl SshTunnel {
+    /// Connects through `config.jump_hosts` and then `config.hop`, and starts
+    /// forwarding a local port to `target_host:target_port`.
+    pub async fn open(config: &SshConfig, target_host: &str, target_port: u16) -> Result<Self, Box<dyn std::error::Error>> {
+        let known_hosts = match &config.known_hosts_path {
+            Some(path) => PathBuf::from(path),
+            None => default_known_hosts()?,
+        };
+        let hops: Vec<&SshHop> = config.jump_hosts.iter().flatten().chain(std::iter::once(&config.hop)).collect();
+
+        let mut sessions: Vec<Arc<Session>> = Vec::with_capacity(hops.len());
+        for hop in hops {
+            let port = hop.port.unwrap_or(22);
+            let rejection = Arc::new(Mutex::new(None));
+            let handler = HostKeyCheck {
+                host: hop.host.clone(),
+                port,
+                known_hosts: known_hosts.clone(),
+                accept_new: config.accept_new_host_keys.unwrap_or(false),
+                rejection: rejection.clone(),
+            };
+            let ssh_config = Arc::new(client::Config {
+                keepalive_interval: Some(std::time::Duration::from_secs(30)),
+                ..Default::default()
+            });
+
+            let connected = match sessions.last() {
+                None => client::connect(ssh_config, (hop.host.as_str(), port), handler).await,
+                Some(previous) => {
+                    let channel = previous
+                        .channel_open_direct_tcpip(hop.host.as_str(), port as u32, "127.0.0.1", 0)
+                        .await?;
+                    client::connect_stream(ssh_config, channel.into_stream(), handler).await
+                }
+            };
+            let mut session = connected.map_err(|e| -> Box<dyn std::error::Error> {
+                match rejection.lock().ok().and_then(|mut r| r.take()) {
+                    Some(reason) => reason.into(),
+                    None => format!("SSH connection to {}:{} failed: {}", hop.host, port, e).into(),
+                }
+            })?;
+            authenticate(&mut session, hop).await?;
+            log::debug!("SSH session established with {}@{}:{}", hop.username, hop.host, port);
+            sessions.push(Arc::new(session));
+        }
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let local_port = listener.local_addr()?.port();
+        let forwarder = sessions.last().cloned().ok_or("SSH tunnel has no hosts")?;
+        log::info!("SSH tunnel listening on 127.0.0.1:{} for {}:{}", local_port, target_host, target_port);
+        let target_host = target_host.to_string();
+        let accept_task = tokio::spawn(async move {
+            loop {
+                let (mut socket, peer) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        log::warn!("SSH tunnel stopped accepting connections: {}", e);
+                        return;
+                    }
+                };
+                let channel = match forwarder
+                    .channel_open_direct_tcpip(target_host.as_str(), target_port as u32, peer.ip().to_string(), peer.port() as u32)
+                    .await
+                {
+                    Ok(channel) => channel,
+                    Err(e) => {
+                        log::warn!("SSH tunnel could not reach {}:{}: {}", target_host, target_port, e);
+                        continue;
+                    }
+                };
+                tokio::spawn(async move {
+                    let mut stream = channel.into_stream();
+                    if let Err(e) = tokio::io::copy_bidirectional(&mut socket, &mut stream).await {
+                        log::debug!("SSH tunnel stream closed: {}", e);
+                    }
+                });
+            }
+        });
+
+        Ok(Self { local_port, sessions, accept_task })
+    }
+
+    pub fn local_port(&self) -> u16 {
+        self.local_port
+    }
+}
+
+impl Drop for SshTunnel {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+        let sessions = std::mem::take(&mut self.sessions);
+        // Close the innermost session first; a bastion carries the ones after it.
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                for session in sessions.into_iter().rev() {
+                    let _ = session.disconnect(Disconnect::ByApplication, "", "en").await;
+                }
+            });
+        }
+    }
+}
+
+/// Host and port a driver should connect to for `config`, opening its SSH
+/// tunnel first when it has one. The tunnel must be kept for as long as the
+/// driver's connections are in use.
+pub async fn connect_target(config: &ConnectionConfig) -> Result<(String, u16, Option<Arc<SshTunnel>>), Box<dyn std::error::Error>> {
+    match &config.ssh {
+        Some(_) if config.srv.unwrap_or(false) || config.host.contains(',') => {
+            Err("An SSH tunnel forwards to a single host; it cannot be combined with SRV lookup or a host list".into())
+        }
+        Some(ssh) => {
+            let tunnel = SshTunnel::open(ssh, &config.host, config.port).await?;
+            Ok(("127.0.0.1".to_string(), tunnel.local_port(), Some(Arc::new(tunnel))))
+        }
+        None => Ok((config.host.clone(), config.port, None)),
+    }
+}
+
+/// Tries the configured methods in turn: private key, password, then agent.
+/// The agent is also tried when nothing else is configured.
+async fn authenticate(session: &mut Session, hop: &SshHop) -> Result<(), Box<dyn std::error::Error>> {
+    let passphrase = hop.passphrase.as_deref().filter(|p| !p.is_empty());
+    let key = match (&hop.private_key, &hop.private_key_path) {
+        (Some(contents), _) if !contents.trim().is_empty() => Some(russh_keys::decode_secret_key(contents, passphrase)?),
+        (_, Some(path)) if !path.is_empty() => Some(russh_keys::load_secret_key(expand_home(path), passphrase)?),
+        _ => None,
+    };
+    let password = hop.password.as_deref().filter(|p| !p.is_empty());
+    let use_agent = hop.use_agent.unwrap_or(key.is_none() && password.is_none());
+
+    if let Some(key) = key {
+        if session.authenticate_publickey(&hop.username, Arc::new(key)).await? {
+            return Ok(());
+        }
+    }
+    if let Some(password) = password {
+        if session.authenticate_password(&hop.username, password).await? {
+            return Ok(());
+        }
+    }
+    if use_agent && authenticate_with_agent(session, &hop.username).await? {
+        return Ok(());
+    }
+    Err(format!("SSH authentication failed for {}@{}", hop.username, hop.host).into())
+}
+
+#[cfg(unix)]
+async fn authenticate_with_agent(session: &mut Session, username: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut agent = russh_keys::agent::client::AgentClient::connect_env().await
+        .map_err(|e| format!("Cannot reach ssh-agent: {}", e))?;
+    for identity in agent.request_identities().await? {
+        let (returned, result) = session.authenticate_future(username, identity, agent).await;
+        agent = returned;
+        if result? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(not(unix))]
+async fn authenticate_with_agent(_session: &mut Session, _username: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    Err("ssh-agent authentication is only supported on Unix".into())
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")).map(PathBuf::from)
+}
+
+fn default_known_hosts() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(home_dir().ok_or("Cannot locate the home directory for known_hosts")?.join(".ssh").join("known_hosts"))
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
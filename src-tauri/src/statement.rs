@@ -0,0 +1,310 @@
+//! Lexical analysis of SQL text: a tokenizer that knows each dialect's
+//! comments, string literals and quoted identifiers, and helpers built on it.
+//! Nothing here validates SQL; it only finds where the keywords really are.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Keyword or bare identifier.
+    Word,
+    QuotedIdentifier,
+    String,
+    Number,
+    /// Bind parameter: `$1`, `?`, `:name`.
+    Parameter,
+    Symbol,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Byte range in the source text.
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Token {
+    pub fn text<'a>(&self, sql: &'a str) -> &'a str {
+        &sql[self.start..self.end]
+    }
+
+    pub fn is_keyword(&self, sql: &str, keyword: &str) -> bool {
+        self.kind == TokenKind::Word && self.text(sql).eq_ignore_ascii_case(keyword)
+    }
+
+    pub fn is_symbol(&self, sql: &str, symbol: &str) -> bool {
+        self.kind == TokenKind::Symbol && self.text(sql) == symbol
+    }
+}
+
+/// Splits SQL into tokens, dropping whitespace and comments. MySQL's
+/// executable comments (`/*! ... */`) are tokenized as code because the
+/// server runs them.
+pub fn tokenize(db_type: &str, sql: &str) -> Vec<Token> {
+    let mysql = crate::dialect::is_mysql(db_type);
+    let postgres = crate::dialect::is_postgres(db_type);
+    let bytes = sql.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    // Inside a MySQL `/*! ... */` block; its `*/` is skipped like whitespace.
+    let mut in_executable_comment = false;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        let next = bytes.get(i + 1).copied();
+
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == b'-' && next == Some(b'-') || mysql && c == b'#' {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if c == b'*' && next == Some(b'/') && in_executable_comment {
+            in_executable_comment = false;
+            i += 2;
+            continue;
+        }
+        if c == b'/' && next == Some(b'*') {
+            if mysql && bytes.get(i + 2) == Some(&b'!') {
+                in_executable_comment = true;
+                i += 3;
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    i += 1;
+                }
+                continue;
+            }
+            i = skip_block_comment(bytes, i, postgres);
+            continue;
+        }
+
+        let start = i;
+        let kind = match c {
+            b'\'' => {
+                i = skip_quoted(bytes, i + 1, b'\'', mysql);
+                TokenKind::String
+            }
+            b'E' | b'e' if postgres && next == Some(b'\'') => {
+                i = skip_quoted(bytes, i + 2, b'\'', true);
+                TokenKind::String
+            }
+            b'"' if mysql => {
+                i = skip_quoted(bytes, i + 1, b'"', true);
+                TokenKind::String
+            }
+            b'"' => {
+                i = skip_quoted(bytes, i + 1, b'"', false);
+                TokenKind::QuotedIdentifier
+            }
+            b'`' => {
+                i = skip_quoted(bytes, i + 1, b'`', false);
+                TokenKind::QuotedIdentifier
+            }
+            b'[' if !mysql && !postgres => {
+                i = sql[i..].find(']').map(|offset| i + offset + 1).unwrap_or(bytes.len());
+                TokenKind::QuotedIdentifier
+            }
+            b'$' if postgres => match dollar_quote_tag(sql, i) {
+                Some(tag) => {
+                    let body = i + tag.len();
+                    i = sql[body..].find(tag).map(|offset| body + offset + tag.len()).unwrap_or(bytes.len());
+                    TokenKind::String
+                }
+                None => {
+                    i += 1;
+                    while i < bytes.len() && bytes[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                    TokenKind::Parameter
+                }
+            },
+            b'?' => {
+                i += 1;
+                TokenKind::Parameter
+            }
+            b':' if next.map(|n| n.is_ascii_alphabetic() || n == b'_').unwrap_or(false) && !postgres => {
+                i += 1;
+                while i < bytes.len() && is_word_byte(bytes[i]) {
+                    i += 1;
+                }
+                TokenKind::Parameter
+            }
+            b'0'..=b'9' => {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'.') {
+                    i += 1;
+                }
+                TokenKind::Number
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' || c >= 0x80 => {
+                while i < bytes.len() && (is_word_byte(bytes[i]) || bytes[i] >= 0x80) {
+                    i += 1;
+                }
+                TokenKind::Word
+            }
+            _ => {
+                // Keep `::` and `->>` style operators out of the way of
+                // single-character checks such as `;` and `(`.
+                i += 1;
+                if matches!(c, b':' | b'<' | b'>' | b'!' | b'|' | b'-') {
+                    while i < bytes.len() && matches!(bytes[i], b':' | b'=' | b'>' | b'<' | b'|') {
+                        i += 1;
+                    }
+                }
+                TokenKind::Symbol
+            }
+        };
+        tokens.push(Token { kind, start, end: i.min(bytes.len()) });
+    }
+
+    tokens
+}
+
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'$'
+}
+
+/// Skips past the closing quote. `backslash` enables `\x` escapes; a doubled
+/// quote is always an escaped quote.
+fn skip_quoted(bytes: &[u8], mut i: usize, quote: u8, backslash: bool) -> usize {
+    while i < bytes.len() {
+        if backslash && bytes[i] == b'\\' {
+            i += 2;
+            continue;
+        }
+        if bytes[i] == quote {
+            if bytes.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+    bytes.len()
+}
+
+/// Postgres block comments nest; other engines end at the first `*/`.
+fn skip_block_comment(bytes: &[u8], mut i: usize, nested: bool) -> usize {
+    let mut depth = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'/' && bytes.get(i + 1) == Some(&b'*') {
+            depth += 1;
+            i += 2;
+            if !nested && depth > 1 {
+                depth = 1;
+            }
+        } else if bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/') {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    bytes.len()
+}
+
+/// `$tag$` or `$$` at `start`, if it is one.
+fn dollar_quote_tag(sql: &str, start: usize) -> Option<&str> {
+    let rest = &sql[start + 1..];
+    let end = rest.find('$')?;
+    let tag = &rest[..end];
+    if tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') && !tag.starts_with(|c: char| c.is_ascii_digit()) {
+        Some(&sql[start..start + end + 2])
+    } else {
+        None
+    }
+}
+
+/// Byte ranges of the individual statements in a script, split on `;`
+/// outside strings, comments and dollar-quoted bodies. Empty statements are
+/// dropped.
+pub fn split_statements(db_type: &str, sql: &str) -> Vec<(usize, usize)> {
+    let mut statements = Vec::new();
+    let mut first: Option<usize> = None;
+    let mut last = 0;
+    for token in tokenize(db_type, sql) {
+        if token.is_symbol(sql, ";") {
+            if let Some(start) = first.take() {
+                statements.push((start, last));
+            }
+            continue;
+        }
+        first.get_or_insert(token.start);
+        last = token.end;
+    }
+    if let Some(start) = first {
+        statements.push((start, last));
+    }
+    statements
+}
+
+/// Index of the first token at parenthesis depth zero matching `keyword`,
+/// searching from `from`.
+pub fn find_top_level_keyword(sql: &str, tokens: &[Token], from: usize, keyword: &str) -> Option<usize> {
+    let mut depth = 0i32;
+    for (index, token) in tokens.iter().enumerate().skip(from) {
+        if token.is_symbol(sql, "(") {
+            depth += 1;
+        } else if token.is_symbol(sql, ")") {
+            depth -= 1;
+        } else if depth == 0 && token.is_keyword(sql, keyword) {
+            return Some(index);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(db_type: &str, sql: &str) -> Vec<String> {
+        tokenize(db_type, sql)
+            .iter()
+            .filter(|t| t.kind == TokenKind::Word)
+            .map(|t| t.text(sql).to_ascii_uppercase())
+            .collect()
+    }
+
+    fn statements<'a>(db_type: &str, sql: &'a str) -> Vec<&'a str> {
+        split_statements(db_type, sql).into_iter().map(|(from, to)| &sql[from..to]).collect()
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        assert_eq!(words("postgresql", "/* DROP TABLE t; */ SELECT 1"), ["SELECT"]);
+        assert_eq!(words("postgresql", "-- DROP TABLE t;\nSELECT 1"), ["SELECT"]);
+        assert_eq!(words("mysql", "# DROP TABLE t;\nSELECT 1"), ["SELECT"]);
+        assert_eq!(statements("postgresql", "-- a; b\nSELECT 1 /* ; */"), ["SELECT 1"]);
+    }
+
+    #[test]
+    fn mysql_executable_comments_are_code() {
+        assert_eq!(words("mysql", "/*! DROP TABLE t */"), ["DROP", "TABLE", "T"]);
+        assert_eq!(words("mysql", "SELECT 1 /*!50700 ; DROP TABLE t */"), ["SELECT", "DROP", "TABLE", "T"]);
+        assert_eq!(statements("mysql", "SELECT 1 /*! ; DROP TABLE t */").len(), 2);
+        // Elsewhere it is an ordinary comment.
+        assert_eq!(words("postgresql", "/*! DROP TABLE t */ SELECT 1"), ["SELECT"]);
+    }
+
+    #[test]
+    fn quoted_text_hides_keywords_and_semicolons() {
+        assert_eq!(statements("postgresql", "SELECT $$; DROP TABLE t; $$"), ["SELECT $$; DROP TABLE t; $$"]);
+        assert_eq!(statements("postgresql", "SELECT $body$ $$; DROP $$ $body$; SELECT 2").len(), 2);
+        assert_eq!(words("postgresql", "SELECT $fn$DROP TABLE t$fn$"), ["SELECT"]);
+        assert_eq!(words("mysql", "SELECT `DROP; TABLE` FROM t"), ["SELECT", "FROM", "T"]);
+        assert_eq!(statements("mysql", "SELECT `a;b` FROM t"), ["SELECT `a;b` FROM t"]);
+        assert_eq!(statements("sqlite", "SELECT 'it''s; DROP TABLE t'"), ["SELECT 'it''s; DROP TABLE t'"]);
+    }
+
+    #[test]
+    fn splits_multiple_statements() {
+        assert_eq!(statements("postgresql", "SELECT 1; DROP TABLE t"), ["SELECT 1", "DROP TABLE t"]);
+        assert_eq!(statements("postgresql", ";; SELECT 1 ;"), ["SELECT 1"]);
+    }
+}
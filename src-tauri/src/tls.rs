@@ -0,0 +1,132 @@
+//! Applies `TlsConfig` to each driver and turns certificate failures into
+//! errors that say what was checked and how to fix it.
+
+use std::path::PathBuf;
+use crate::types::{TlsConfig, TlsMode};
+
+/// Combined certificate and key written for the Mongo driver, deleted when
+/// the connection using it is dropped.
+#[derive(Debug)]
+pub struct TempPem(PathBuf);
+
+impl Drop for TempPem {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// TLS options for the Mongo driver. Its rustls backend cannot skip only the
+/// hostname check, so `verify-ca` verifies the hostname as well.
+pub fn mongo_options(tls: &TlsConfig) -> Result<(mongodb::options::Tls, Option<TempPem>), Box<dyn std::error::Error>> {
+    if tls.mode == TlsMode::Disable {
+        return Ok((mongodb::options::Tls::Disabled, None));
+    }
+    let mut temp = None;
+    // The driver takes the client certificate and key from a single file.
+    let cert_key_file_path = match (path(&tls.client_cert_path), path(&tls.client_key_path)) {
+        (Some(cert), Some(key)) if cert != key => {
+            let mut pem = std::fs::read(&cert).map_err(|e| format!("Cannot read client certificate {:?}: {}", cert, e))?;
+            pem.push(b'\n');
+            pem.extend(std::fs::read(&key).map_err(|e| format!("Cannot read client key {:?}: {}", key, e))?);
+            let combined = std::env::temp_dir().join(format!("mongo-client-{}-{}.pem", std::process::id(), chrono::Utc::now().format("%Y%m%dT%H%M%S%9fZ")));
+            write_private(&combined, &pem)?;
+            temp = Some(TempPem(combined.clone()));
+            Some(combined)
+        }
+        (Some(cert), _) => Some(cert),
+        (None, key) => key,
+    };
+    let options = mongodb::options::TlsOptions::builder()
+        .allow_invalid_certificates(matches!(tls.mode, TlsMode::Prefer | TlsMode::Require).then_some(true))
+        .ca_file_path(path(&tls.ca_cert_path))
+        .cert_key_file_path(cert_key_file_path)
+        .build();
+    Ok((mongodb::options::Tls::Enabled(options), temp))
+}
+
+/// Builds the Redis client for a URL from `dsn::driver_url`, which is
+/// `rediss://` when TLS is on and marked `#insecure` for `prefer` and
+/// `require`. `verify-ca` verifies the hostname too, as the rustls backend
+/// cannot separate the two.
+pub fn redis_client(tls: &TlsConfig, url: &str) -> Result<redis::Client, Box<dyn std::error::Error>> {
+    if tls.mode == TlsMode::Disable {
+        return Ok(redis::Client::open(url)?);
+    }
+
+    let root_cert = path(&tls.ca_cert_path).map(|p| read_pem(&p, "CA certificate")).transpose()?;
+    let client_tls = match (path(&tls.client_cert_path), path(&tls.client_key_path)) {
+        (Some(cert), Some(key)) => Some(redis::ClientTlsConfig {
+            client_cert: read_pem(&cert, "client certificate")?,
+            client_key: read_pem(&key, "client key")?,
+        }),
+        (None, None) => None,
+        _ => return Err("Redis client authentication needs both a certificate and a key".into()),
+    };
+    if root_cert.is_none() && client_tls.is_none() {
+        return Ok(redis::Client::open(url)?);
+    }
+    Ok(redis::Client::build_with_tls(url, redis::TlsCertificates { client_tls, root_cert })?)
+}
+
+/// Whether `error` comes from the TLS handshake itself, as when the server
+/// does not speak TLS. Only these fall back to plaintext under `prefer`;
+/// authentication and network errors are reported as they are.
+pub fn is_handshake_error(error: &str) -> bool {
+    let lower = error.to_lowercase();
+    ["handshake", "corrupt message", "invalidcontenttype", "wrong version number", "peerincompatible", "peer is incompatible"]
+        .iter()
+        .any(|needle| lower.contains(needle))
+}
+
+/// Rewrites certificate validation failures into an explanation; other
+/// errors pass through unchanged.
+pub fn explain_error(error: &str, tls: &TlsConfig, connect_host: &str) -> String {
+    let lower = error.to_lowercase();
+    let hint = if lower.contains("notvalidforname") || lower.contains("hostname mismatch")
+        || lower.contains("not valid for name") || lower.contains("certificate is not valid for")
+    {
+        let name = tls.server_name.as_deref().filter(|name| !name.trim().is_empty()).unwrap_or(connect_host);
+        let tunnel = if connect_host == "127.0.0.1" && name == connect_host {
+            " Through an SSH tunnel the host connected to is 127.0.0.1; set the TLS server name to the database's name."
+        } else {
+            ""
+        };
+        format!(
+            "The server certificate is not issued for {}.{} Use verify-ca to check only the chain, or a server name the certificate lists.",
+            name, tunnel
+        )
+    } else if lower.contains("unknownissuer") || lower.contains("unknown issuer")
+        || lower.contains("self signed") || lower.contains("self-signed") || lower.contains("unable to get local issuer")
+    {
+        match path(&tls.ca_cert_path) {
+            Some(ca) => format!("The server certificate is not signed by a CA in {:?}.", ca),
+            None => "The server certificate is not signed by a trusted CA. Set a CA certificate, or use require to skip verification.".to_string(),
+        }
+    } else if lower.contains("expired") || lower.contains("notvalidyet") || lower.contains("not valid yet") {
+        "The server certificate is expired or not yet valid; check the server certificate and this machine's clock.".to_string()
+    } else if lower.contains("badsignature") || lower.contains("bad signature") {
+        "The server certificate signature does not verify against the CA.".to_string()
+    } else if lower.contains("certificate") && (lower.contains("pem") || lower.contains("no such file") || lower.contains("private key")) {
+        "A certificate or key file could not be loaded; check the paths and that they are PEM encoded.".to_string()
+    } else {
+        return error.to_string();
+    };
+    format!("TLS certificate validation failed: {} ({})", hint, error)
+}
+
+fn path(value: &Option<String>) -> Option<PathBuf> {
+    value.as_deref().filter(|p| !p.is_empty()).map(PathBuf::from)
+}
+
+fn read_pem(path: &std::path::Path, what: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    std::fs::read(path).map_err(|e| format!("Cannot read {} {:?}: {}", what, path, e).into())
+}
+
+fn write_private(path: &std::path::Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    std::io::Write::write_all(&mut options.open(path)?, contents)
+}
+
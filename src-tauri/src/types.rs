@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +23,17 @@ pub struct TableInfo {
     pub size: Option<u64>,
 }
 
+/// An index, constraint, view, trigger or routine, with the definition the
+/// server reports for it. `table` is set for objects that belong to a table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaObject {
+    pub kind: String,
+    pub schema: Option<String>,
+    pub name: String,
+    pub table: Option<String>,
+    pub definition: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryRow {
     pub columns: Vec<String>,
@@ -39,5 +51,178 @@ pub struct ConnectionConfig {
     pub database: String,
     pub username: String,
     pub password: Option<String>,
+    /// Superseded by `tls`; `true` means `require` when `tls` is absent.
     pub ssl: Option<bool>,
+    pub tls: Option<TlsConfig>,
+    /// Enforced by the backend: SQL sessions are opened read-only and write
+    /// commands are refused on Mongo and Redis.
+    pub read_only: Option<bool>,
+    /// Free-form labels such as `production`, matched by safety policy rules.
+    pub tags: Option<Vec<String>>,
+    /// Statements run through `execute_query` and `execute_ddl` are written
+    /// to the audit log.
+    pub audited: Option<bool>,
+    /// Tunnel the connection through SSH.
+    pub ssh: Option<SshConfig>,
+    /// Mongo: resolve the hosts through DNS SRV records (`mongodb+srv://`).
+    pub srv: Option<bool>,
+    /// Driver options passed as connection string query parameters.
+    pub options: Option<BTreeMap<String, String>>,
+    /// Background liveness checks while connected.
+    pub health_check: Option<HealthCheckConfig>,
+    /// SQL connection pool sizing and lifetimes.
+    pub pool: Option<PoolConfig>,
+    /// Defaults for queries run from the editor.
+    pub query_limits: Option<QueryLimits>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TlsMode {
+    #[default]
+    Disable,
+    /// TLS when the server offers it, otherwise plaintext.
+    Prefer,
+    /// TLS without certificate checks.
+    Require,
+    /// Certificate chain checked against the CA, hostname not checked.
+    VerifyCa,
+    /// Certificate chain and hostname checked.
+    VerifyFull,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TlsConfig {
+    #[serde(default)]
+    pub mode: TlsMode,
+    /// PEM bundle of trusted CAs; the system roots when absent.
+    pub ca_cert_path: Option<String>,
+    pub client_cert_path: Option<String>,
+    pub client_key_path: Option<String>,
+    /// Name expected in the server certificate when it differs from the host
+    /// connected to.
+    pub server_name: Option<String>,
+}
+
+/// One SSH server on the way to the database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SshHop {
+    pub host: String,
+    /// Defaults to 22.
+    pub port: Option<u16>,
+    pub username: String,
+    pub password: Option<String>,
+    pub private_key_path: Option<String>,
+    /// Key contents, as an alternative to `private_key_path`.
+    pub private_key: Option<String>,
+    pub passphrase: Option<String>,
+    /// Try the keys held by the running ssh-agent.
+    pub use_agent: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SshConfig {
+    /// The server that forwards to the database.
+    #[serde(flatten)]
+    pub hop: SshHop,
+    /// Bastions connected through first, in order.
+    pub jump_hosts: Option<Vec<SshHop>>,
+    /// Defaults to `~/.ssh/known_hosts`.
+    pub known_hosts_path: Option<String>,
+    /// Record keys of hosts missing from known_hosts instead of refusing them.
+    /// Changed keys are always refused.
+    pub accept_new_host_keys: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HealthCheckConfig {
+    /// Defaults to on.
+    pub enabled: Option<bool>,
+    /// Seconds between checks while healthy; defaults to 30.
+    pub interval_secs: Option<u64>,
+    /// Seconds a check may take before it counts as failed; defaults to 5.
+    pub timeout_secs: Option<u64>,
+    /// Consecutive failed checks before the connection is considered lost
+    /// and reconnected; defaults to 3.
+    pub failures_before_lost: Option<u32>,
+    /// Longest wait between reconnection attempts, in seconds; defaults to 60.
+    pub max_backoff_secs: Option<u64>,
+}
+
+/// Unset fields keep the driver defaults: at most 10 connections, none kept
+/// open, 30 s acquire timeout, 10 min idle timeout and 30 min lifetime.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PoolConfig {
+    pub max_connections: Option<u32>,
+    /// Connections kept open even when idle.
+    pub min_connections: Option<u32>,
+    /// Seconds to wait for a free connection before failing.
+    pub acquire_timeout_secs: Option<u64>,
+    /// Seconds an unused connection stays open; 0 keeps it indefinitely.
+    pub idle_timeout_secs: Option<u64>,
+    /// Seconds before a connection is replaced; 0 keeps it indefinitely.
+    pub max_lifetime_secs: Option<u64>,
+    /// Statements run on every new physical connection, e.g.
+    /// `SET search_path TO app`.
+    pub init_sql: Option<Vec<String>>,
+}
+
+/// Rows returned by an editor query when no cap is configured.
+pub const DEFAULT_MAX_ROWS: u64 = 10_000;
+
+/// Safeguards for editor queries. Set per connection as defaults and per
+/// query as overrides; 0 turns a limit off.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueryLimits {
+    /// Milliseconds a statement may run; no timeout by default.
+    pub timeout_ms: Option<u64>,
+    /// Rows fetched before the result is cut off; `DEFAULT_MAX_ROWS` by default.
+    pub max_rows: Option<u64>,
+}
+
+/// `QueryLimits` after overrides and defaults are applied.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResolvedLimits {
+    pub timeout: Option<std::time::Duration>,
+    pub max_rows: Option<usize>,
+}
+
+impl ConnectionConfig {
+    /// Limits for one editor query: `overrides` first, then the connection's
+    /// defaults.
+    pub fn resolve_limits(&self, overrides: Option<&QueryLimits>) -> ResolvedLimits {
+        let defaults = self.query_limits.clone().unwrap_or_default();
+        let pick = |field: fn(&QueryLimits) -> Option<u64>| overrides.and_then(field).or(field(&defaults));
+        ResolvedLimits {
+            timeout: pick(|l| l.timeout_ms).filter(|&ms| ms > 0).map(std::time::Duration::from_millis),
+            max_rows: Some(pick(|l| l.max_rows).unwrap_or(DEFAULT_MAX_ROWS))
+                .filter(|&rows| rows > 0)
+                .map(|rows| rows as usize),
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.unwrap_or(false)
+    }
+
+    pub fn is_audited(&self) -> bool {
+        self.audited.unwrap_or(false)
+    }
+
+    /// TLS settings, falling back to the legacy `ssl` flag. With neither set
+    /// the driver's own default applies: `prefer` for Postgres and MySQL,
+    /// `verify-full` for Mongo SRV lookups, plaintext for other Mongo and
+    /// Redis connections.
+    pub fn tls_config(&self) -> TlsConfig {
+        self.tls.clone().unwrap_or_else(|| TlsConfig {
+            mode: match self.ssl {
+                Some(true) => TlsMode::Require,
+                Some(false) => TlsMode::Disable,
+                None if matches!(self.r#type.as_str(), "postgresql" | "mysql" | "mariadb") => TlsMode::Prefer,
+                None if self.r#type == "mongodb" && self.srv.unwrap_or(false) => TlsMode::VerifyFull,
+                None => TlsMode::Disable,
+            },
+            ..TlsConfig::default()
+        })
+    }
 }
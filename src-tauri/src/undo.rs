@@ -0,0 +1,435 @@
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::Executor;
+use crate::database::DatabaseConnection;
+use crate::dialect;
+use crate::row_edit::{self, RowChange};
+use crate::snapshot::safe_file_component;
+use crate::statement::{self, Token, TokenKind};
+
+pub const JOURNAL_FORMAT_VERSION: u32 = 1;
+
+/// Default cap on before-images captured for one statement.
+pub const DEFAULT_MAX_UNDO_ROWS: usize = 10_000;
+
+/// One captured data change and the changes that revert it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub format_version: u32,
+    pub id: String,
+    pub connection_id: String,
+    pub connection_name: String,
+    pub db_type: String,
+    pub table: String,
+    pub captured_at: String,
+    /// `grid` for result grid edits, `statement` for UPDATE/DELETE run from
+    /// the editor.
+    pub source: String,
+    pub statement: Option<String>,
+    pub rows_affected: u64,
+    /// Applied in order by `undo_change`.
+    pub inverse: Vec<RowChange>,
+    /// Readable form of `inverse`, for review before undoing.
+    pub inverse_script: Vec<String>,
+    pub undone_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalSummary {
+    pub id: String,
+    pub connection_id: String,
+    pub connection_name: String,
+    pub table: String,
+    pub captured_at: String,
+    pub source: String,
+    pub statement: Option<String>,
+    pub rows_affected: u64,
+    pub undone_at: Option<String>,
+}
+
+impl JournalEntry {
+    pub fn summary(&self) -> JournalSummary {
+        JournalSummary {
+            id: self.id.clone(),
+            connection_id: self.connection_id.clone(),
+            connection_name: self.connection_name.clone(),
+            table: self.table.clone(),
+            captured_at: self.captured_at.clone(),
+            source: self.source.clone(),
+            statement: self.statement.clone(),
+            rows_affected: self.rows_affected,
+            undone_at: self.undone_at.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JournalQuery {
+    pub connection_id: Option<String>,
+    /// Case-insensitive match on the table name.
+    pub table: Option<String>,
+    /// RFC 3339 bounds on `captured_at`, inclusive.
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub include_undone: Option<bool>,
+    pub limit: Option<usize>,
+}
+
+/// Stores entries as `<root>/<change_id>.json`. Change ids are UTC timestamps,
+/// so a lexical sort of the file names is a chronological one.
+pub struct JournalStore {
+    root: PathBuf,
+}
+
+impl JournalStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn entry_path(&self, change_id: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        Ok(self.root.join(format!("{}.json", safe_file_component(change_id)?)))
+    }
+
+    pub fn save(&self, entry: &JournalEntry) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(&self.root)?;
+        let path = self.entry_path(&entry.id)?;
+        std::fs::write(&path, serde_json::to_string_pretty(entry)?)?;
+        Ok(path)
+    }
+
+    pub fn load(&self, change_id: &str) -> Result<JournalEntry, Box<dyn std::error::Error>> {
+        load_entry_file(&self.entry_path(change_id)?)
+    }
+
+    /// Matching entries, newest first.
+    pub fn search(&self, query: &JournalQuery) -> Result<Vec<JournalSummary>, Box<dyn std::error::Error>> {
+        if !self.root.exists() {
+            return Ok(Vec::new());
+        }
+
+        let since = query.since.as_deref().map(parse_time).transpose()?;
+        let until = query.until.as_deref().map(parse_time).transpose()?;
+        let limit = query.limit.unwrap_or(200);
+
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(&self.root)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().map(|ext| ext == "json").unwrap_or(false))
+            .collect();
+        paths.sort();
+        paths.reverse();
+
+        let mut summaries = Vec::new();
+        for path in paths {
+            if summaries.len() >= limit {
+                break;
+            }
+            let entry = match load_entry_file(&path) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    log::warn!("Skipping unreadable journal entry {:?}: {}", path, e);
+                    continue;
+                }
+            };
+
+            if query.connection_id.as_ref().is_some_and(|id| *id != entry.connection_id) {
+                continue;
+            }
+            if query.table.as_ref().is_some_and(|table| !table.eq_ignore_ascii_case(&entry.table)) {
+                continue;
+            }
+            if entry.undone_at.is_some() && !query.include_undone.unwrap_or(true) {
+                continue;
+            }
+            let captured_at = parse_time(&entry.captured_at)?;
+            if since.is_some_and(|since| captured_at < since) || until.is_some_and(|until| captured_at > until) {
+                continue;
+            }
+            summaries.push(entry.summary());
+        }
+
+        Ok(summaries)
+    }
+}
+
+fn load_entry_file(path: &Path) -> Result<JournalEntry, Box<dyn std::error::Error>> {
+    let entry: JournalEntry = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    if entry.format_version > JOURNAL_FORMAT_VERSION {
+        return Err(Box::new(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "Journal format version {} is newer than supported version {}",
+                entry.format_version, JOURNAL_FORMAT_VERSION
+            )
+        )));
+    }
+    Ok(entry)
+}
+
+fn parse_time(value: &str) -> Result<chrono::DateTime<chrono::Utc>, Box<dyn std::error::Error>> {
+    Ok(chrono::DateTime::parse_from_rfc3339(value)?.with_timezone(&chrono::Utc))
+}
+
+/// Change ids double as file names: `20261018T093015123456789Z`.
+pub fn new_change_id() -> String {
+    chrono::Utc::now().format("%Y%m%dT%H%M%S%9fZ").to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DmlKind {
+    Update,
+    Delete,
+}
+
+/// The parts of a single-table UPDATE or DELETE needed to read its rows
+/// before it runs.
+#[derive(Debug, Clone)]
+pub struct DmlTarget {
+    pub kind: DmlKind,
+    /// Unquoted, possibly schema-qualified table name.
+    pub table: String,
+    /// Table reference as written, including any alias.
+    pub table_ref: String,
+    pub predicate: Option<String>,
+    /// Unquoted names of the columns an UPDATE assigns.
+    pub assigned_columns: Vec<String>,
+}
+
+fn unquote(sql: &str, token: &Token) -> String {
+    let text = token.text(sql);
+    if token.kind == TokenKind::QuotedIdentifier && text.len() >= 2 {
+        let quote = &text[..1];
+        let close = if quote == "[" { "]" } else { quote };
+        text[1..text.len() - 1].replace(&format!("{}{}", close, close), close)
+    } else {
+        text.to_string()
+    }
+}
+
+fn is_name(token: &Token) -> bool {
+    matches!(token.kind, TokenKind::Word | TokenKind::QuotedIdentifier)
+}
+
+/// Recognizes `UPDATE t [AS a] SET ... [WHERE ...]` and
+/// `DELETE FROM t [AS a] [WHERE ...]`. Multi-table forms, CTEs, and
+/// ORDER BY/LIMIT (whose rows cannot be predicted by a plain SELECT) are not
+/// recognized.
+pub fn parse_dml_target(db_type: &str, sql: &str) -> Option<DmlTarget> {
+    let mut tokens = statement::tokenize(db_type, sql);
+    while tokens.last().is_some_and(|t| t.is_symbol(sql, ";")) {
+        tokens.pop();
+    }
+    if tokens.iter().any(|t| t.is_symbol(sql, ";")) {
+        return None;
+    }
+    let first = tokens.first()?;
+    let kind = if first.is_keyword(sql, "UPDATE") {
+        DmlKind::Update
+    } else if first.is_keyword(sql, "DELETE") {
+        DmlKind::Delete
+    } else {
+        return None;
+    };
+
+    let mut i = 1;
+    while tokens.get(i).is_some_and(|t| {
+        ["LOW_PRIORITY", "QUICK", "IGNORE", "ONLY"].iter().any(|k| t.is_keyword(sql, k))
+    }) {
+        i += 1;
+    }
+    if kind == DmlKind::Delete {
+        if !tokens.get(i)?.is_keyword(sql, "FROM") {
+            return None;
+        }
+        i += 1;
+    }
+
+    // Table name: name ('.' name)*
+    let table_start = i;
+    let mut parts = vec![unquote(sql, tokens.get(i).filter(|t| is_name(t))?)];
+    i += 1;
+    while tokens.get(i).is_some_and(|t| t.is_symbol(sql, ".")) {
+        parts.push(unquote(sql, tokens.get(i + 1).filter(|t| is_name(t))?));
+        i += 2;
+    }
+    let table = parts.join(".");
+
+    // Optional alias.
+    if tokens.get(i).is_some_and(|t| t.is_keyword(sql, "AS")) {
+        i += 2;
+    } else if tokens.get(i).is_some_and(|t| {
+        is_name(t) && !["SET", "WHERE", "RETURNING"].iter().any(|k| t.is_keyword(sql, k))
+    }) {
+        i += 1;
+    }
+    let table_ref = &sql[tokens[table_start].start..tokens[i - 1].end];
+
+    let end_of = |from: usize| {
+        ["RETURNING", "ORDER", "LIMIT"]
+            .iter()
+            .filter_map(|k| statement::find_top_level_keyword(sql, &tokens, from, k))
+            .min()
+            .unwrap_or(tokens.len())
+    };
+    for keyword in ["ORDER", "LIMIT", "FROM", "USING", "JOIN"] {
+        if statement::find_top_level_keyword(sql, &tokens, i, keyword).is_some() {
+            return None;
+        }
+    }
+
+    let where_index = statement::find_top_level_keyword(sql, &tokens, i, "WHERE");
+    let mut assigned_columns = Vec::new();
+    if kind == DmlKind::Update {
+        if !tokens.get(i)?.is_keyword(sql, "SET") {
+            return None;
+        }
+        let set_end = where_index.unwrap_or_else(|| end_of(i));
+        let mut depth = 0;
+        let mut expect_column = true;
+        for (index, token) in tokens.iter().enumerate().take(set_end).skip(i + 1) {
+            if token.is_symbol(sql, "(") {
+                if expect_column {
+                    // `(a, b) = (...)` row assignments are not supported.
+                    return None;
+                }
+                depth += 1;
+            } else if token.is_symbol(sql, ")") {
+                depth -= 1;
+            } else if depth == 0 && token.is_symbol(sql, ",") {
+                expect_column = true;
+            } else if expect_column && is_name(token) {
+                // `col`, or the `col` of `alias.col`
+                if tokens.get(index + 1).is_some_and(|t| t.is_symbol(sql, ".")) {
+                    continue;
+                }
+                assigned_columns.push(unquote(sql, token));
+                expect_column = false;
+            }
+        }
+    }
+
+    let predicate = match where_index {
+        Some(w) => {
+            let end = end_of(w + 1);
+            if end <= w + 1 {
+                return None;
+            }
+            Some(sql[tokens[w + 1].start..tokens[end - 1].end].to_string())
+        }
+        None => None,
+    };
+
+    Some(DmlTarget {
+        kind,
+        table,
+        table_ref: table_ref.to_string(),
+        predicate,
+        assigned_columns,
+    })
+}
+
+/// Whether the statement is an UPDATE or DELETE, the statements undo
+/// capture applies to.
+pub fn is_row_modification(db_type: &str, sql: &str) -> bool {
+    statement::tokenize(db_type, sql)
+        .first()
+        .is_some_and(|t| t.is_keyword(sql, "UPDATE") || t.is_keyword(sql, "DELETE"))
+}
+
+pub struct CapturedStatement {
+    pub table: String,
+    pub rows_affected: u64,
+    pub inverse: Vec<RowChange>,
+}
+
+/// Runs a single-table UPDATE or DELETE after reading the rows it will touch,
+/// all in one transaction, and returns the changes that put them back.
+/// Refuses statements that change key columns or touch more than `max_rows`
+/// rows; nothing is executed in that case.
+pub async fn execute_with_undo(conn: &DatabaseConnection, sql: &str, max_rows: usize) -> Result<CapturedStatement, String> {
+    let db_type = conn.db_type();
+    let target = parse_dml_target(db_type, sql).ok_or(
+        "Undo capture supports single-table UPDATE and DELETE statements without ORDER BY or LIMIT"
+    )?;
+    let key = row_edit::resolve_key(conn, &target.table).await?;
+    if let Some(column) = target.assigned_columns.iter().find(|c| key.columns.iter().any(|k| k.eq_ignore_ascii_case(c))) {
+        return Err(format!(
+            "The statement changes key column {}, so its rows cannot be matched for undo; run it without undo capture",
+            column
+        ));
+    }
+
+    let columns = row_edit::image_columns(conn, &target.table).await?;
+    let generated = conn.generated_columns(&target.table).await.map_err(|e| e.to_string())?;
+    let select = row_edit::row_image_select(db_type, &target.table_ref, &columns, target.predicate.as_deref(), Some(max_rows + 1));
+
+    let mut tx = conn.begin().await.map_err(|e| e.to_string())?;
+    let mut before = row_edit::fetch_row_images(&mut tx, &select, &[]).await?;
+    if before.len() > max_rows {
+        let _ = tx.rollback().await;
+        return Err(format!(
+            "The statement touches more than {} rows, which is over the undo capture limit; nothing was executed",
+            max_rows
+        ));
+    }
+    row_edit::strip_generated(&mut before, &generated);
+    let rows_affected = (&mut *tx).execute(sql).await.map_err(|e| e.to_string())?.rows_affected();
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    let inverse = before
+        .into_iter()
+        .map(|row| match target.kind {
+            DmlKind::Update => {
+                let original: Map<String, Value> = key
+                    .columns
+                    .iter()
+                    .map(|c| (c.clone(), row.get(c).cloned().unwrap_or(Value::Null)))
+                    .collect();
+                RowChange::Replace { original, values: row }
+            }
+            DmlKind::Delete => RowChange::Insert { values: row },
+        })
+        .collect();
+
+    Ok(CapturedStatement {
+        table: target.table,
+        rows_affected,
+        inverse,
+    })
+}
+
+/// Readable inverse script for the journal.
+pub fn describe_inverse(db_type: &str, table: &str, inverse: &[RowChange]) -> Vec<String> {
+    let quoted_table = dialect::quote_table(db_type, table);
+    inverse
+        .iter()
+        .map(|change| match change {
+            RowChange::Replace { original, values } | RowChange::Update { original, values } => {
+                let assignments: Vec<String> = values
+                    .iter()
+                    .map(|(c, v)| format!("{} = {}", dialect::quote_identifier(db_type, c), dialect::literal(db_type, v)))
+                    .collect();
+                format!("UPDATE {} SET {} WHERE {}", quoted_table, assignments.join(", "), map_predicate(db_type, original))
+            }
+            RowChange::Insert { values } => {
+                let columns: Vec<String> = values.keys().cloned().collect();
+                let literals: Vec<Value> = values.values().cloned().collect();
+                format!(
+                    "INSERT INTO {} ({}) VALUES ({})",
+                    quoted_table,
+                    columns.iter().map(|c| dialect::quote_identifier(db_type, c)).collect::<Vec<_>>().join(", "),
+                    literals.iter().map(|v| dialect::literal(db_type, v)).collect::<Vec<_>>().join(", ")
+                )
+            }
+            RowChange::Delete { original } => {
+                format!("DELETE FROM {} WHERE {}", quoted_table, map_predicate(db_type, original))
+            }
+        })
+        .collect()
+}
+
+fn map_predicate(db_type: &str, key: &Map<String, Value>) -> String {
+    let columns: Vec<String> = key.keys().cloned().collect();
+    let values: Vec<Value> = key.values().cloned().collect();
+    dialect::key_predicate(db_type, &columns, &values)
+}
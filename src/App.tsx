@@ -15,7 +15,7 @@ export default function App() {
   const [showSavedQueries, setShowSavedQueries] = useState(false);
   const [showConnectionDialog, setShowConnectionDialog] = useState(false);
   const [editingConnectionId, setEditingConnectionId] = useState<string | null>(null);
-  const { connections, selectedConnectionId, addConnection, connect, loadConnections, updateConnection } = useConnectionStore();
+  const { connections, selectedConnectionId, addConnection, connect, loadConnections, updateConnection, listenForHealth } = useConnectionStore();
   const { activeQueryId, queries, updateQuery, createQuery } = useQueryStore();
   
   const selectedConnection = connections.find(c => c.id === selectedConnectionId);
@@ -26,6 +26,14 @@ export default function App() {
     loadConnections();
   }, [loadConnections]);
 
+  useEffect(() => {
+    // Health monitor events keep the sidebar status current
+    const unlisten = listenForHealth();
+    return () => {
+      unlisten.then(stop => stop());
+    };
+  }, [listenForHealth]);
+
   useEffect(() => {
     if (selectedConnectionId && !activeQuery) {
       createQuery(selectedConnectionId);
@@ -119,7 +119,7 @@ export function QueryEditor({ query, connection }: QueryEditorProps) {
           </span>
           {query?.results && (
             <span style={styles.rowCount}>
-              {query.results.rowCount} rows
+              {query.results.rowCount} rows{query.results.truncated && ' (row limit reached, more available)'}
             </span>
           )}
         </div>
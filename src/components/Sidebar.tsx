@@ -41,7 +41,7 @@ export function Sidebar({ onAddConnection, onEditConnection }: SidebarProps) {
   const handleConnect = async (e: React.MouseEvent, id: string) => {
     e.stopPropagation();
     const connection = connections.find(c => c.id === id);
-    if (connection?.status === 'connected') {
+    if (connection && isOpen(connection.status)) {
       disconnect(id);
     } else {
       await connect(id);
@@ -102,12 +102,12 @@ export function Sidebar({ onAddConnection, onEditConnection }: SidebarProps) {
                     <button 
                       style={{
                         ...styles.actionButton,
-                        color: connection.status === 'connected' ? 'var(--accent-error)' : 'var(--accent-success)',
+                        color: isOpen(connection.status) ? 'var(--accent-error)' : 'var(--accent-success)',
                       }}
-                      title={connection.status === 'connected' ? 'Disconnect' : 'Connect'}
+                      title={isOpen(connection.status) ? 'Disconnect' : 'Connect'}
                       onClick={(e) => handleConnect(e, connection.id)}
                     >
-                      {connection.status === 'connected' ? '◼' : '▶'}
+                      {isOpen(connection.status) ? '◼' : '▶'}
                     </button>
                     <button 
                       style={styles.actionButton} 
@@ -124,7 +124,7 @@ export function Sidebar({ onAddConnection, onEditConnection }: SidebarProps) {
                       title="Delete"
                       onClick={(e) => {
                         e.stopPropagation();
-                        if (connection.status === 'connected') {
+                        if (isOpen(connection.status)) {
                           disconnect(connection.id);
                         }
                         removeConnection(connection.id);
@@ -143,10 +143,17 @@ export function Sidebar({ onAddConnection, onEditConnection }: SidebarProps) {
   );
 }
 
+/** Registered in the backend, including while it reconnects. */
+function isOpen(status: string): boolean {
+  return status === 'connected' || status === 'degraded' || status === 'reconnecting';
+}
+
 function getStatusColor(status: string): string {
   switch (status) {
     case 'connected': return 'var(--accent-success)';
     case 'connecting': return 'var(--accent-warning)';
+    case 'degraded': return 'var(--accent-warning)';
+    case 'reconnecting': return 'var(--accent-error)';
     case 'error': return 'var(--accent-error)';
     default: return 'var(--text-muted)';
   }
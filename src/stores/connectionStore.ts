@@ -1,8 +1,27 @@
 import { create } from 'zustand';
 import { invoke } from '@tauri-apps/api/core';
-import { Connection, ConnectionConfig } from '../types';
+import { listen, UnlistenFn } from '@tauri-apps/api/event';
+import { Connection, ConnectionConfig, HealthEvent, SshConfig, SshHop } from '../types';
 import { v4 as uuidv4 } from 'uuid';
 
+const toSshHop = (hop: SshHop) => ({
+  host: hop.host,
+  port: hop.port || 22,
+  username: hop.username,
+  password: hop.password || null,
+  private_key: hop.privateKey || null,
+  private_key_path: hop.privateKeyPath || null,
+  passphrase: hop.passphrase || null,
+  use_agent: hop.useAgent ?? null,
+});
+
+const toSshConfig = (ssh: SshConfig) => ({
+  ...toSshHop(ssh),
+  jump_hosts: ssh.jumpHosts?.map(toSshHop) || [],
+  known_hosts_path: ssh.knownHostsPath || null,
+  accept_new_host_keys: ssh.acceptNewHostKeys || false,
+});
+
 interface ConnectionState {
   connections: Connection[];
   selectedConnectionId: string | null;
@@ -16,6 +35,7 @@ interface ConnectionState {
   connect: (id: string) => Promise<void>;
   disconnect: (id: string) => void;
   setConnectionStatus: (id: string, status: Connection['status'], error?: string) => void;
+  listenForHealth: () => Promise<UnlistenFn>;
 }
 
 export const useConnectionStore = create<ConnectionState>((set, get) => ({
@@ -128,6 +148,37 @@ export const useConnectionStore = create<ConnectionState>((set, get) => ({
         username: connection.username,
         password: connection.password || '',
         ssl: connection.ssl || false,
+        tls: connection.tls ? {
+          mode: connection.tls.mode,
+          ca_cert_path: connection.tls.caCertPath || null,
+          client_cert_path: connection.tls.clientCertPath || null,
+          client_key_path: connection.tls.clientKeyPath || null,
+          server_name: connection.tls.serverName || null,
+        } : null,
+        read_only: connection.readOnly || false,
+        tags: connection.tags || [],
+        audited: connection.audited || false,
+        ssh: connection.ssh ? toSshConfig(connection.ssh) : null,
+        options: connection.options && Object.keys(connection.options).length > 0 ? connection.options : null,
+        health_check: connection.healthCheck ? {
+          enabled: connection.healthCheck.enabled ?? null,
+          interval_secs: connection.healthCheck.intervalSecs || null,
+          timeout_secs: connection.healthCheck.timeoutSecs || null,
+          failures_before_lost: connection.healthCheck.failuresBeforeLost || null,
+          max_backoff_secs: connection.healthCheck.maxBackoffSecs || null,
+        } : null,
+        pool: connection.pool ? {
+          max_connections: connection.pool.maxConnections || null,
+          min_connections: connection.pool.minConnections ?? null,
+          acquire_timeout_secs: connection.pool.acquireTimeoutSecs || null,
+          idle_timeout_secs: connection.pool.idleTimeoutSecs ?? null,
+          max_lifetime_secs: connection.pool.maxLifetimeSecs ?? null,
+          init_sql: connection.pool.initSql?.filter(sql => sql.trim()) || null,
+        } : null,
+        query_limits: connection.queryLimits ? {
+          timeout_ms: connection.queryLimits.timeoutMs ?? null,
+          max_rows: connection.queryLimits.maxRows ?? null,
+        } : null,
       };
       
       await invoke('connect_database', { connection: connectionData });
@@ -152,4 +203,15 @@ export const useConnectionStore = create<ConnectionState>((set, get) => ({
       ),
     }));
   },
+
+  listenForHealth: () =>
+    listen<HealthEvent>('connection-health', ({ payload }) => {
+      const status: Connection['status'] = {
+        connected: 'connected',
+        reconnected: 'connected',
+        degraded: 'degraded',
+        lost: 'reconnecting',
+      }[payload.state] as Connection['status'];
+      get().setConnectionStatus(payload.connection_id, status, payload.error || undefined);
+    }),
 }));
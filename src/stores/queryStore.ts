@@ -73,6 +73,7 @@ export const useQueryStore = create<QueryState>((set, get) => ({
         rows: any[][];
         row_count: number;
         affected_rows: number;
+        truncated: boolean;
       }>('execute_query', { id, sql });
       const executionTime = Date.now() - startTime;
       
@@ -89,6 +90,7 @@ export const useQueryStore = create<QueryState>((set, get) => ({
         rowCount: result.row_count,
         affectedRows: result.affected_rows,
         executionTime,
+        truncated: result.truncated,
       };
       
       get().updateQuery(id, {
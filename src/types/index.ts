@@ -12,6 +12,121 @@ export type DatabaseType =
   | 'cassandra'
   | 'elasticsearch';
 
+export type TlsMode = 'disable' | 'prefer' | 'require' | 'verify-ca' | 'verify-full';
+
+export interface TlsConfig {
+  mode: TlsMode;
+  caCertPath?: string;
+  clientCertPath?: string;
+  clientKeyPath?: string;
+  serverName?: string;
+}
+
+export interface SshHop {
+  host: string;
+  port: number;
+  username: string;
+  password?: string;
+  privateKey?: string;
+  privateKeyPath?: string;
+  passphrase?: string;
+  useAgent?: boolean;
+}
+
+export interface SshConfig extends SshHop {
+  jumpHosts?: SshHop[];
+  knownHostsPath?: string;
+  acceptNewHostKeys?: boolean;
+}
+
+export interface DriverOptionSpec {
+  key: string;
+  kind: { type: 'text' | 'bool' | 'integer' } | { type: 'choice'; choices: string[] };
+  description: string;
+}
+
+export interface HealthCheckConfig {
+  enabled?: boolean;
+  intervalSecs?: number;
+  timeoutSecs?: number;
+  failuresBeforeLost?: number;
+  maxBackoffSecs?: number;
+}
+
+export interface PoolConfig {
+  maxConnections?: number;
+  minConnections?: number;
+  acquireTimeoutSecs?: number;
+  idleTimeoutSecs?: number;
+  maxLifetimeSecs?: number;
+  /** Run on every new physical connection. */
+  initSql?: string[];
+}
+
+export interface PoolStats {
+  size: number;
+  idle: number;
+  in_use: number;
+  max_connections: number;
+  min_connections: number;
+  waiting: number;
+  acquires: number;
+  timeouts: number;
+  avg_wait_ms: number;
+  max_wait_ms: number;
+}
+
+/** A session on the server, from list_sessions. */
+export interface ServerSession {
+  id: string;
+  user: string | null;
+  database: string | null;
+  client_address: string | null;
+  application: string | null;
+  state: string | null;
+  statement: string | null;
+  duration_ms: number | null;
+  wait_event: string | null;
+  is_current: boolean;
+}
+
+export interface LockWait {
+  lock_type: string | null;
+  mode: string | null;
+  relation: string | null;
+  held_mode: string | null;
+  waited_ms: number | null;
+}
+
+export interface BlockingNode {
+  session: ServerSession;
+  waiting_for: LockWait | null;
+  blocked: BlockingNode[];
+  blocked_total: number;
+}
+
+/** From analyze_locks; roots are blockers that are not waiting themselves. */
+export interface BlockingReport {
+  roots: BlockingNode[];
+  waiting_sessions: number;
+}
+
+/** 0 turns a limit off. */
+export interface QueryLimits {
+  timeoutMs?: number;
+  maxRows?: number;
+}
+
+export type HealthState = 'connected' | 'degraded' | 'lost' | 'reconnected';
+
+export interface HealthEvent {
+  connection_id: string;
+  state: HealthState;
+  latency_ms: number | null;
+  error: string | null;
+  attempts: number;
+}
+
 export interface ConnectionConfig {
   id: string;
   name: string;
@@ -22,18 +137,22 @@ export interface ConnectionConfig {
   username: string;
   password?: string;
   ssl?: boolean;
-  ssh?: {
-    host: string;
-    port: number;
-    username: string;
-    privateKey?: string;
-  };
+  tls?: TlsConfig;
+  readOnly?: boolean;
+  tags?: string[];
+  audited?: boolean;
+  ssh?: SshConfig;
+  /** Engine-specific driver options, e.g. authSource or search_path. */
+  options?: Record<string, string>;
+  healthCheck?: HealthCheckConfig;
+  pool?: PoolConfig;
+  queryLimits?: QueryLimits;
   createdAt: Date;
   updatedAt: Date;
 }
 
 export interface Connection extends ConnectionConfig {
-  status: 'connected' | 'disconnected' | 'connecting' | 'error';
+  status: 'connected' | 'disconnected' | 'connecting' | 'error' | 'degraded' | 'reconnecting';
   error?: string;
 }
 
@@ -72,6 +191,8 @@ export interface QueryResult {
   executionTime: number;
   affectedRows: number;
   error?: string;
+  /** Stopped at the row limit; the query returned more rows. */
+  truncated?: boolean;
 }
 
 export interface Query {